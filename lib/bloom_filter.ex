@@ -47,7 +47,15 @@ defmodule BloomFilterEx do
 
   alias BloomFilterEx.Native
 
-  defstruct [:resource, :size, :hash_count, :capacity, :false_positive_rate, :inserted_count]
+  defstruct [
+    :resource,
+    :size,
+    :hash_count,
+    :capacity,
+    :false_positive_rate,
+    :inserted_count,
+    strict: false
+  ]
 
   @type t :: %__MODULE__{
           resource: reference(),
@@ -55,9 +63,62 @@ defmodule BloomFilterEx do
           hash_count: pos_integer(),
           capacity: pos_integer(),
           false_positive_rate: float(),
-          inserted_count: non_neg_integer()
+          inserted_count: non_neg_integer(),
+          strict: boolean()
         }
 
+  @typedoc """
+  An item hashed once by `prepare/1`, to probe any number of filters.
+  """
+  @opaque prepared_key :: reference()
+
+  @typedoc """
+  Reasons returned in `{:error, reason}` tuples by the native functions.
+
+  `{:panic, message}` means the native code panicked, for example while
+  decoding a malformed binary. The panic is caught before it reaches the VM,
+  and the filter involved remains usable.
+  """
+  @type error ::
+          :invalid_capacity
+          | :invalid_false_positive_rate
+          | :invalid_growth_factor
+          | :invalid_generations
+          | :invalid_shards
+          | :invalid_width
+          | :invalid_depth
+          | :invalid_k
+          | :invalid_precision
+          | :invalid_num_bits
+          | :invalid_num_hashes
+          | :invalid_compression_level
+          | :invalid_chunk_size
+          | :invalid_option
+          | :invalid_value
+          | :invalid_ttl
+          | :invalid_cell_bits
+          | :invalid_bucket_size
+          | :incompatible_filters
+          | :filter_full
+          | :capacity_exceeded
+          | :read_only
+          | :construction_failed
+          | :lock_poisoned
+          | :lock_timeout
+          | :memory_budget_exceeded
+          | :allocation_failed
+          | :seed_unavailable
+          | :unsupported_version
+          | :unknown_version
+          | :invalid_data
+          | :corrupt_data
+          | :enoent
+          | :eacces
+          | :eexist
+          | :enospc
+          | :io_error
+          | {:panic, String.t()}
+
   @doc """
   Creates a new Bloom filter optimized for the expected capacity and desired false positive rate.
 
@@ -65,10 +126,23 @@ defmodule BloomFilterEx do
 
   - `capacity`: Expected number of items to be inserted (n)
   - `false_positive_rate`: Desired false positive probability (p), between 0.0 and 1.0
+  - `opts`: Keyword list of options
+
+  ## Options
+
+  - `:strict` - when `true`, the add functions return `{:error, :capacity_exceeded}`
+    instead of inserting once `inserted_count` would exceed `capacity`, so the
+    filter never degrades past its configured false positive rate. Defaults to
+    `false`. Strictness is kept by `clone/1` but not by `serialize/1`.
 
   ## Returns
 
-  A new Bloom filter struct with optimally calculated bit array size and hash count.
+  A new Bloom filter struct with optimally calculated bit array size and hash count,
+  or `{:error, :memory_budget_exceeded}` if the bit array would go over the
+  budget set with `configure/1`, or `{:error, :allocation_failed}` if the
+  memory for the bit array can't be allocated. Bit arrays of more than 2^32
+  bits (512 MiB) are split into segments of that size, each allocated on its
+  own, so multi-GB filters don't need one contiguous block of memory.
 
   ## Examples
 
@@ -84,30 +158,235 @@ defmodule BloomFilterEx do
       iex> bloom.hash_count
       9
 
+      iex> bloom = BloomFilterEx.new(2, 0.01, strict: true)
+      iex> bloom = BloomFilterEx.add_many(bloom, ["a", "b"])
+      iex> BloomFilterEx.add(bloom, "c")
+      {:error, :capacity_exceeded}
+
+  """
+  @spec new(pos_integer(), float(), keyword()) :: t() | {:error, error()}
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(opts) do
+    strict = Keyword.get(opts, :strict, false)
+    capacity |> Native.new(false_positive_rate, strict) |> created()
+  end
+
+  @doc """
+  Creates a new Bloom filter like `new/2`, but with a fixed hasher seed.
+
+  Filters created by `new/2` use a random seed, so two filters built with the
+  same parameters on different nodes set different bits for the same item.
+  Filters created with the same capacity, false positive rate and `seed` are
+  bit-compatible and can be combined with `union/2` and `intersection/2`.
+
+  The seed must be a non-negative integer below `2^128`.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(100, 0.01, 42) |> BloomFilterEx.add("a")
+      iex> b = BloomFilterEx.new_with_seed(100, 0.01, 42) |> BloomFilterEx.add("b")
+      iex> {:ok, merged} = BloomFilterEx.union(a, b)
+      iex> BloomFilterEx.member?(merged, "a") and BloomFilterEx.member?(merged, "b")
+      true
+
+  """
+  @spec new_with_seed(pos_integer(), float(), non_neg_integer()) :: t() | {:error, error()}
+  def new_with_seed(capacity, false_positive_rate, seed)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_integer(seed) and seed >= 0 and
+             seed < 0x1_0000_0000_0000_0000_0000_0000_0000_0000 do
+    capacity |> Native.new_with_seed(false_positive_rate, seed) |> created()
+  end
+
+  @doc """
+  Creates a new Bloom filter like `new/2`, whose items are hashed with
+  SipHash-1-3 keyed by the 16-byte `secret_key`.
+
+  Without the key, knowing the filter's parameters doesn't tell anyone which
+  bits an item sets, so nobody can craft items that pile onto chosen bits to
+  raise the false positive rate. `new/2` already keys its filters with a
+  random secret; use this when filters built on several nodes must share a
+  key so they can be combined with `union/2`. Generate the key with
+  `:crypto.strong_rand_bytes(16)` and keep it secret.
+
+  The key is the filter's seed, so it is part of the output of `serialize/1`
+  and `save/2`; treat those as secret too.
+
+  ## Examples
+
+      iex> key = :crypto.strong_rand_bytes(16)
+      iex> a = BloomFilterEx.new_keyed(100, 0.01, key) |> BloomFilterEx.add("a")
+      iex> b = BloomFilterEx.new_keyed(100, 0.01, key) |> BloomFilterEx.add("b")
+      iex> {:ok, merged} = BloomFilterEx.union(a, b)
+      iex> BloomFilterEx.member?(merged, "a") and BloomFilterEx.member?(merged, "b")
+      true
+
+  """
+  @spec new_keyed(pos_integer(), float(), <<_::128>>) :: t() | {:error, error()}
+  def new_keyed(capacity, false_positive_rate, <<seed::unsigned-big-128>> = _secret_key) do
+    new_with_seed(capacity, false_positive_rate, seed)
+  end
+
+  @doc """
+  Creates a new Bloom filter with an explicit bit array size and hash count.
+
+  This bypasses the capacity and false positive rate derivation of `new/2`,
+  for callers who already computed their parameters or need to match an
+  external system. The number of bits is rounded up to a multiple of 64.
+
+  The `capacity` of the returned filter is the number of items at which it is
+  optimally loaded, and its `false_positive_rate` is the rate at that load.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new_with_bits(1024, 4)
+      iex> bloom.size
+      1024
+      iex> bloom.hash_count
+      4
+      iex> bloom.capacity
+      177
+
+  """
+  @spec new_with_bits(pos_integer(), pos_integer()) :: t() | {:error, error()}
+  def new_with_bits(num_bits, num_hashes)
+      when is_integer(num_bits) and num_bits > 0 and is_integer(num_hashes) and num_hashes > 0 do
+    num_bits |> Native.new_with_bits(num_hashes) |> created()
+  end
+
+  @doc """
+  Creates a new Bloom filter from a keyword list of options.
+
+  This accepts every construction parameter in one place, so new options can
+  be added without new constructors. The options are:
+  - `:capacity` (required): Expected number of items
+  - `:false_positive_rate` (required): Target false positive rate, which
+    sizes the bit array as in `new/2`
+  - `:num_hashes`: Number of hash functions, instead of the optimal count for
+    the bit array's size
+  - `:seed`: Hasher seed, as for `new_with_seed/3`; random by default
+  - `:strict`: Reject inserts past the capacity, as for `new/3`; `false` by
+    default
+  - `:hash`: The hash items are hashed with, seeded with the filter's seed:
+    `:sip13` (SipHash-1-3, the default), `:xxh3` or `:wyhash`. SipHash-1-3 is
+    keyed, so as long as the seed stays secret nobody can craft items that
+    set chosen bits to saturate the filter. xxh3 and wyhash are faster, for
+    filters of trusted items. The hash is recorded by `serialize/1`, and only
+    filters with the same hash can be combined.
+
+  Returns `{:error, :invalid_option}` for an unknown option or a value of the
+  wrong type for `:seed`, `:strict` or `:hash`, and the same errors as the
+  other constructors for invalid parameters.
+
+  There is no `:block_size` option. Earlier versions of fastbloom split the
+  bit array into fixed-size blocks and let callers pick the size, but the
+  version used here dropped blocked filters: every hash probes the whole bit
+  array, so there is no block size to tune, record in `stats/1` or serialize.
+  Passing `:block_size` returns `{:error, :invalid_option}` like any other
+  unknown option.
+
+  ## Examples
+
+      iex> {:ok, bloom} =
+      ...>   BloomFilterEx.new_with_opts(capacity: 1000, false_positive_rate: 0.01, num_hashes: 3)
+      iex> {bloom.size, bloom.hash_count, bloom.strict}
+      {9600, 3, false}
+      iex> {:ok, bloom} =
+      ...>   BloomFilterEx.new_with_opts(capacity: 1000, false_positive_rate: 0.01, hash: :xxh3)
+      iex> BloomFilterEx.stats(bloom).hash
+      :xxh3
+      iex> BloomFilterEx.new_with_opts(capacity: 1000, false_positive_rate: 0.01, colour: :blue)
+      {:error, :invalid_option}
+      iex> BloomFilterEx.new_with_opts(false_positive_rate: 0.01)
+      {:error, :invalid_capacity}
+
+  """
+  @spec new_with_opts(keyword()) :: {:ok, t()} | {:error, error()}
+  def new_with_opts(opts) when is_list(opts) do
+    with {:ok, resource} <- Native.new_with_opts(opts) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Returns the parameters `new/2` would pick for `capacity` and
+  `false_positive_rate`, without allocating a filter.
+
+  The result is a map with:
+  - `num_bits`: Number of bits in the bit array (the filter's `size`)
+  - `num_hashes`: Number of hash functions (the filter's `hash_count`)
+  - `memory_bytes`: Bytes the bit array would take, as reported by `byte_size/1`
+
+  This is cheap enough to validate configuration or plan memory for very
+  large filters before creating them.
+
+  ## Examples
+
+      iex> BloomFilterEx.optimal_params(1000, 0.01)
+      %{num_bits: 9600, num_hashes: 6, memory_bytes: 1200}
+      iex> bloom = BloomFilterEx.new(1000, 0.01)
+      iex> {bloom.size, bloom.hash_count}
+      {9600, 6}
+
   """
-  @spec new(pos_integer(), float()) :: t()
-  def new(capacity, false_positive_rate)
+  @spec optimal_params(pos_integer(), float()) :: %{
+          num_bits: pos_integer(),
+          num_hashes: pos_integer(),
+          memory_bytes: pos_integer()
+        }
+  def optimal_params(capacity, false_positive_rate)
       when is_integer(capacity) and capacity > 0 and
              is_float(false_positive_rate) and false_positive_rate > 0.0 and
              false_positive_rate < 1.0 do
-    {:ok, resource} = Native.new(capacity, false_positive_rate)
-    {:ok, {size, hash_count, fpr, inserted_count}} = Native.stats(resource)
+    {:ok, params} = Native.optimal_params(capacity, false_positive_rate)
+    params
+  end
 
-    %__MODULE__{
-      resource: resource,
-      size: size,
-      hash_count: hash_count,
-      capacity: capacity,
-      false_positive_rate: fpr,
-      inserted_count: inserted_count
-    }
+  @doc """
+  Sets options that apply to every Bloom filter in the VM.
+
+  ## Options
+
+  - `:memory_budget` - the most bytes the bit arrays and counters of all
+    filters may hold together, or `:infinity` (the default). This covers every
+    filter type, such as `BloomFilterEx.Counting` and
+    `BloomFilterEx.CountMinSketch`, not just `BloomFilterEx`. Functions that
+    would allocate past the budget, such as `new/2`, `deserialize/1` and
+    `union/2`, return `{:error, :memory_budget_exceeded}` instead. Filters
+    already allocated are unaffected, and their memory is counted until they
+    are garbage collected.
+
+  Returns `{:error, :invalid_option}` for unknown options or invalid values.
+
+  ## Examples
+
+      iex> BloomFilterEx.configure(memory_budget: 1_000_000)
+      :ok
+      iex> BloomFilterEx.new(100_000_000, 0.01)
+      {:error, :memory_budget_exceeded}
+      iex> BloomFilterEx.configure(memory_budget: :infinity)
+      :ok
+
+  """
+  @spec configure(keyword()) :: :ok | {:error, :invalid_option}
+  def configure(opts) when is_list(opts) do
+    with {:ok, {}} <- Native.configure(opts), do: :ok
   end
 
   @doc """
   Adds an item to the Bloom filter.
 
   This operation sets k bits in the bit array (where k is the hash_count).
-  The item can be any Erlang term.
+  The item can be any Erlang term. It is hashed by its external term format,
+  which the NIF encodes itself, so the term isn't copied into a binary on the
+  Elixir side first. The encoded binary is still built inside the NIF.
+
+  Items the filter (probably) already contains are not counted again, so
+  `inserted_count` tracks distinct items even when keys repeat. Use
+  `add_if_absent/2` to find out whether an item was new.
 
   ## Parameters
 
@@ -116,7 +395,8 @@ defmodule BloomFilterEx do
 
   ## Returns
 
-  Updated Bloom filter with the item added.
+  Updated Bloom filter with the item added, or `{:error, :capacity_exceeded}`
+  if the filter is strict and already holds `capacity` items.
 
   ## Examples
 
@@ -128,17 +408,232 @@ defmodule BloomFilterEx do
       true
 
       iex> bloom = BloomFilterEx.new(100, 0.01)
-      iex> bloom = bloom |> BloomFilterEx.add(1) |> BloomFilterEx.add(2)
+      iex> bloom = bloom |> BloomFilterEx.add(1) |> BloomFilterEx.add(2) |> BloomFilterEx.add(1)
       iex> bloom.inserted_count
       2
 
   """
-  @spec add(t(), term()) :: t()
+  @spec add(t(), term()) :: t() | {:error, :capacity_exceeded}
   def add(%__MODULE__{resource: resource} = bloom, item) do
-    # Convert item to string for the NIF
-    item_string = :erlang.term_to_binary(item) |> Base.encode64()
-    {:ok, new_resource} = Native.add(resource, item_string)
-    %{bloom | resource: new_resource, inserted_count: bloom.inserted_count + 1}
+    resource |> Native.add_term(item) |> inserted(bloom)
+  end
+
+  @doc """
+  Adds an item like `add/2`, but waits at most `timeout` milliseconds for the
+  filter's lock.
+
+  Inserts normally share the lock, so they only wait while an exclusive
+  operation such as `clear/1` holds it. If it is still held after `timeout`,
+  `{:error, :lock_timeout}` is returned and the item is not added, so latency
+  sensitive callers are never blocked for long. The wait happens on a dirty IO
+  scheduler, so it doesn't hold up the normal schedulers, at the cost of a
+  scheduler switch per call; use `add/2` where the timeout isn't needed.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a", 50)
+      iex> BloomFilterEx.member?(bloom, "a")
+      true
+
+  """
+  @spec add(t(), term(), non_neg_integer()) ::
+          t() | {:error, :capacity_exceeded | :lock_timeout}
+  def add(%__MODULE__{resource: resource} = bloom, item, timeout)
+      when is_integer(timeout) and timeout >= 0 do
+    resource |> Native.add_term_timeout(item, timeout) |> inserted(bloom)
+  end
+
+  @doc """
+  Adds a list of items to the Bloom filter in a single native call.
+
+  This is equivalent to calling `add/2` for each item, but crosses into
+  native code only once, which makes bulk loading much cheaper.
+
+  A strict filter rejects the whole list with `{:error, :capacity_exceeded}`
+  if it does not fit, leaving the filter unchanged.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add_many(bloom, ["a", "b", "c"])
+      iex> bloom.inserted_count
+      3
+      iex> BloomFilterEx.member?(bloom, "b")
+      true
+
+  """
+  @spec add_many(t(), [term()]) :: t() | {:error, :capacity_exceeded}
+  def add_many(%__MODULE__{resource: resource} = bloom, items) when is_list(items) do
+    resource |> Native.add_many(Enum.map(items, &encode_item/1)) |> inserted(bloom)
+  end
+
+  @doc """
+  Adds a list of items like `add_many/2`, with either a lock timeout or a
+  keyword list of options.
+
+  Given an integer, waits at most that many milliseconds for the filter's
+  lock, returning `{:error, :lock_timeout}` without adding anything if it is
+  not acquired in time. See `add/3`.
+
+  ## Options
+
+  - `:parallel` - when `true`, the items are hashed and inserted across a pool
+    of native threads, one per CPU core, instead of on a single dirty
+    scheduler. This speeds up loading very large lists, but for short lists
+    the cost of handing the work to the pool outweighs the gain. Defaults to
+    `false`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(10_000, 0.01)
+      iex> bloom = BloomFilterEx.add_many(bloom, Enum.to_list(1..500), parallel: true)
+      iex> bloom.inserted_count
+      500
+      iex> BloomFilterEx.member?(bloom, 250)
+      true
+
+  """
+  @spec add_many(t(), [term()], non_neg_integer() | keyword()) ::
+          t() | {:error, :capacity_exceeded | :lock_timeout}
+  def add_many(%__MODULE__{resource: resource} = bloom, items, timeout)
+      when is_list(items) and is_integer(timeout) and timeout >= 0 do
+    items = Enum.map(items, &encode_item/1)
+    resource |> Native.add_many_timeout(items, timeout) |> inserted(bloom)
+  end
+
+  def add_many(%__MODULE__{resource: resource} = bloom, items, opts)
+      when is_list(items) and is_list(opts) do
+    items = Enum.map(items, &encode_item/1)
+
+    if Keyword.get(opts, :parallel, false) do
+      resource |> Native.add_many_parallel(items) |> inserted(bloom)
+    else
+      resource |> Native.add_many(items) |> inserted(bloom)
+    end
+  end
+
+  @doc """
+  Builds a new filter for `capacity` and `false_positive_rate` containing
+  `items`, in a single native call.
+
+  This is meant for replacing a saturated filter with a larger one re-derived
+  from the source of truth: `items` should be the full set the new filter is
+  meant to hold, since the bits of `bloom` can't be carried over to a filter of
+  a different size. The new filter keeps the hasher seed and strict mode of
+  `bloom`, which is left unchanged.
+
+  A strict filter returns `{:error, :capacity_exceeded}` if `items` do not fit
+  in the new capacity.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(10, 0.01) |> BloomFilterEx.add("a")
+      iex> rebuilt = BloomFilterEx.rebuild(bloom, 1000, 0.001, ["a", "b"])
+      iex> {rebuilt.capacity, rebuilt.inserted_count}
+      {1000, 2}
+      iex> BloomFilterEx.member?(rebuilt, "b")
+      true
+
+  """
+  @spec rebuild(t(), pos_integer(), float(), [term()]) :: t() | {:error, :capacity_exceeded}
+  def rebuild(%__MODULE__{resource: resource}, capacity, false_positive_rate, items)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(items) do
+    items = Enum.map(items, &encode_item/1)
+
+    with {:ok, rebuilt} <- Native.rebuild(resource, capacity, false_positive_rate, items) do
+      from_resource(rebuilt)
+    end
+  end
+
+  @doc """
+  Adds a list of items on a background thread, without blocking the caller.
+
+  Returns a reference straight away. Once every item has been inserted,
+  `{ref, :ok, new_items}` is sent to `pid`, where `new_items` is the number of
+  items that were not already present. A strict filter that cannot fit the
+  items sends `{ref, :error, :capacity_exceeded}` instead.
+
+  The returned struct cannot track items added in the background, so its
+  `inserted_count` is left unchanged; use `stats/1` for the current count.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> ref = BloomFilterEx.add_many_async(bloom, ["a", "b", "a"])
+      iex> receive do
+      ...>   {^ref, :ok, new_items} -> new_items
+      ...> end
+      2
+      iex> BloomFilterEx.member?(bloom, "b")
+      true
+
+  """
+  @spec add_many_async(t(), [term()], pid()) :: reference()
+  def add_many_async(%__MODULE__{resource: resource}, items, pid \\ self())
+      when is_list(items) and is_pid(pid) do
+    ref = make_ref()
+    :ok = Native.add_many_async(resource, Enum.map(items, &encode_item/1), pid, ref)
+    ref
+  end
+
+  @doc """
+  Adds a list of items like `add_many/2`, and reports how many were new.
+
+  Returns `{{inserted_new, already_present}, bloom_filter}`, where the two
+  counts add up to the length of `items`. An item repeated within `items` is
+  new only the first time, and items that are false positives count as
+  already present.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> {report, bloom} = BloomFilterEx.add_many_report(bloom, ["a", "b", "c", "c"])
+      iex> report
+      {2, 2}
+      iex> bloom.inserted_count
+      3
+
+  """
+  @spec add_many_report(t(), [term()]) ::
+          {{non_neg_integer(), non_neg_integer()}, t()} | {:error, :capacity_exceeded}
+  def add_many_report(%__MODULE__{resource: resource} = bloom, items) when is_list(items) do
+    case Native.add_many_report(resource, Enum.map(items, &encode_item/1)) do
+      {:ok, {new_items, _} = report} ->
+        {report, %{bloom | inserted_count: bloom.inserted_count + new_items}}
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item to the Bloom filter unless it is (probably) already present.
+
+  The membership check and the insert happen atomically in a single native
+  call, so concurrent callers cannot both see the item as absent.
+
+  Returns `{already_present?, bloom_filter}`. The `inserted_count` is only
+  incremented when the item was absent. Like the other adds, it returns the
+  write's error if a write-ahead log is enabled and cannot be written.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> {false, bloom} = BloomFilterEx.add_if_absent(bloom, "item")
+      iex> {true, bloom} = BloomFilterEx.add_if_absent(bloom, "item")
+      iex> bloom.inserted_count
+      1
+
+  """
+  @spec add_if_absent(t(), term()) :: {boolean(), t()} | {:error, error()}
+  def add_if_absent(%__MODULE__{resource: resource} = bloom, item) do
+    case Native.add_if_absent(resource, encode_item(item)) do
+      {:ok, true} -> {true, bloom}
+      {:ok, false} -> {false, %{bloom | inserted_count: bloom.inserted_count + 1}}
+      {:error, _reason} = error -> error
+    end
   end
 
   @doc """
@@ -161,86 +656,1664 @@ defmodule BloomFilterEx do
   """
   @spec member?(t(), term()) :: boolean()
   def member?(%__MODULE__{resource: resource}, item) do
-    # Convert item to string for the NIF
-    item_string = :erlang.term_to_binary(item) |> Base.encode64()
-    {:ok, result} = Native.member(resource, item_string)
+    {:ok, result} = Native.member_term(resource, item)
     result
   end
 
   @doc """
-  Clears the Bloom filter, resetting it to empty state.
+  Checks a list of items against the Bloom filter in a single native call.
+
+  Returns a list of booleans in the same order as `items`, with the same
+  meaning as `member?/2`.
 
   ## Examples
 
       iex> bloom = BloomFilterEx.new(100, 0.01)
-      iex> bloom = BloomFilterEx.add(bloom, "test")
-      iex> bloom.inserted_count
-      1
-      iex> bloom = BloomFilterEx.clear(bloom)
-      iex> bloom.inserted_count
-      0
-      iex> BloomFilterEx.member?(bloom, "test")
+      iex> bloom = BloomFilterEx.add_many(bloom, ["a", "c"])
+      iex> BloomFilterEx.member_many(bloom, ["a", "b", "c"])
+      [true, false, true]
+
+  """
+  @spec member_many(t(), [term()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, results} = Native.member_many(resource, Enum.map(items, &encode_item/1))
+    results
+  end
+
+  @doc """
+  Checks a list of items like `member_many/2`, but returns the results packed
+  into a bitstring with one bit per item, in the same order as `items`.
+
+  A bit is 1 where `member?/2` would return `true`. For millions of items this
+  is far smaller than a list of booleans, and it can be walked with binary
+  pattern matching or comprehensions.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add_many(["a", "c"])
+      iex> mask = BloomFilterEx.member_many_bitmask(bloom, ["a", "b", "c"])
+      iex> mask
+      <<5::size(3)>>
+      iex> for <<bit::1 <- mask>>, do: bit == 1
+      [true, false, true]
+
+  """
+  @spec member_many_bitmask(t(), [term()]) :: bitstring()
+  def member_many_bitmask(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, packed} = Native.member_many_bitmask(resource, Enum.map(items, &encode_item/1))
+    # The NIF pads the results to whole bytes.
+    count = length(items)
+    <<mask::bitstring-size(count), _padding::bitstring>> = packed
+    mask
+  end
+
+  @doc """
+  Returns how many of `items` are possibly in the Bloom filter.
+
+  The same as counting the `true`s from `member_many/2`, but only the count
+  comes back from the native call. Repeated items are counted each time.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add_many(["a", "c"])
+      iex> BloomFilterEx.member_count(bloom, ["a", "b", "c", "a"])
+      3
+
+  """
+  @spec member_count(t(), [term()]) :: non_neg_integer()
+  def member_count(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, count} = Native.member_count(resource, Enum.map(items, &encode_item/1))
+    count
+  end
+
+  @doc """
+  Returns whether any of `items` is possibly in the Bloom filter.
+
+  Checking stops at the first item found, so this is cheaper than
+  `member_many/2` when only a single answer is needed. Returns `false` for an
+  empty list.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("blocked")
+      iex> BloomFilterEx.contains_any?(bloom, ["fine", "blocked"])
+      true
+      iex> BloomFilterEx.contains_any?(bloom, ["fine", "also-fine"])
       false
 
   """
-  @spec clear(t()) :: t()
-  def clear(%__MODULE__{resource: resource} = bloom) do
-    {:ok, new_resource} = Native.clear(resource)
-    %{bloom | resource: new_resource, inserted_count: 0}
+  @spec contains_any?(t(), [term()]) :: boolean()
+  def contains_any?(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, result} = Native.contains_any(resource, Enum.map(items, &encode_item/1))
+    result
   end
 
   @doc """
-  Returns statistics about the Bloom filter.
+  Returns whether all of `items` are possibly in the Bloom filter.
 
-  ## Returns
+  Checking stops at the first item missing, so this is cheaper than
+  `member_many/2` when only a single answer is needed. Returns `true` for an
+  empty list.
 
-  A map containing:
-  - `size`: Total number of bits in the filter
-  - `hash_count`: Number of hash functions used
-  - `capacity`: Expected capacity
-  - `false_positive_rate`: Target false positive rate
-  - `inserted_count`: Number of items inserted
-  - `saturation`: Percentage of bits set (0.0 to 100.0)
-  - `estimated_fpr`: Estimated actual false positive rate based on saturation
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add_many(["a", "b"])
+      iex> BloomFilterEx.contains_all?(bloom, ["a", "b"])
+      true
+      iex> BloomFilterEx.contains_all?(bloom, ["a", "c"])
+      false
+
+  """
+  @spec contains_all?(t(), [term()]) :: boolean()
+  def contains_all?(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, result} = Native.contains_all(resource, Enum.map(items, &encode_item/1))
+    result
+  end
+
+  @doc """
+  Adds iodata to the Bloom filter, hashing its raw bytes.
+
+  Unlike `add/2`, the item is not term-encoded: the iodata is flattened inside
+  the NIF, so items assembled as iolists such as `[prefix, ?:, id]` can be added
+  without calling `IO.iodata_to_binary/1` first. Any iodata with the same bytes
+  is treated as the same item, but it is not the same item as the equivalent
+  term passed to `add/2`, so look it up with `member_iodata?/2`.
 
   ## Examples
 
       iex> bloom = BloomFilterEx.new(100, 0.01)
-      iex> stats = BloomFilterEx.stats(bloom)
-      iex> stats.capacity
-      100
-      iex> stats.saturation
-      0.0
+      iex> bloom = BloomFilterEx.add_iodata(bloom, ["user", ?:, "42"])
+      iex> BloomFilterEx.member_iodata?(bloom, "user:42")
+      true
 
   """
-  @spec stats(t()) :: map()
-  def stats(%__MODULE__{resource: resource, capacity: capacity}) do
-    {:ok, {size, hash_count, false_positive_rate, inserted_count}} =
-      Native.stats(resource)
-
-    # For now, we don't have bits_set from the Rust side
-    # Calculate estimated saturation based on inserted items
-    # Expected saturation: (1 - e^(-k*n/m))
-    # where k = hash_count, n = inserted_count, m = size
-    expected_saturation =
-      if inserted_count > 0 do
-        (1 - :math.exp(-hash_count * inserted_count / size)) * 100.0
-      else
-        0.0
-      end
+  @spec add_iodata(t(), iodata()) :: t() | {:error, :capacity_exceeded}
+  def add_iodata(%__MODULE__{resource: resource} = bloom, iodata)
+      when is_binary(iodata) or is_list(iodata) do
+    resource |> Native.add(iodata) |> inserted(bloom)
+  end
 
-    # Estimated FPR based on saturation: (1 - e^(-k*n/m))^k
-    estimated_fpr = :math.pow(expected_saturation / 100.0, hash_count)
+  @doc """
+  Adds a list of iodata items in a single native call. See `add_iodata/2`.
 
-    %{
-      size: size,
-      hash_count: hash_count,
-      capacity: capacity,
-      false_positive_rate: false_positive_rate,
-      inserted_count: inserted_count,
-      saturation: expected_saturation,
-      estimated_fpr: estimated_fpr,
-      bits_set: round(expected_saturation * size / 100.0)
-    }
-  end
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add_many_iodata(bloom, [["a", ?1], "b2"])
+      iex> BloomFilterEx.member_many_iodata(bloom, ["a1", ["b", ?2], "c3"])
+      [true, true, false]
+
+  """
+  @spec add_many_iodata(t(), [iodata()]) :: t() | {:error, :capacity_exceeded}
+  def add_many_iodata(%__MODULE__{resource: resource} = bloom, items) when is_list(items) do
+    resource |> Native.add_many(items) |> inserted(bloom)
+  end
+
+  @doc """
+  Checks if iodata added with `add_iodata/2` is possibly in the Bloom filter.
+  """
+  @spec member_iodata?(t(), iodata()) :: boolean()
+  def member_iodata?(%__MODULE__{resource: resource}, iodata)
+      when is_binary(iodata) or is_list(iodata) do
+    {:ok, result} = Native.member(resource, iodata)
+    result
+  end
+
+  @doc """
+  Checks a list of iodata items in a single native call. See `member_iodata?/2`.
+  """
+  @spec member_many_iodata(t(), [iodata()]) :: [boolean()]
+  def member_many_iodata(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, results} = Native.member_many(resource, items)
+    results
+  end
+
+  @doc """
+  Adds a 64-bit integer to the Bloom filter without encoding it as a term.
+
+  The integer is hashed as its 8 little-endian bytes, the layout it has in
+  memory on x86-64 and ARM64, so a filter built here with the same seed agrees
+  with one built by another service hashing its integer keys directly. Negative
+  integers are hashed as their two's complement, so `-1` and
+  `0xFFFF_FFFF_FFFF_FFFF` are the same item. Integers added this way are not
+  the same items as the integers passed to `add/2`, so look them up with
+  `member_int?/2`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add_int(bloom, 1_234_567_890)
+      iex> BloomFilterEx.member_int?(bloom, 1_234_567_890)
+      true
+      iex> BloomFilterEx.member?(bloom, 1_234_567_890)
+      false
+
+  """
+  @spec add_int(t(), integer()) :: t() | {:error, :capacity_exceeded}
+  def add_int(%__MODULE__{resource: resource} = bloom, int)
+      when is_integer(int) and int in -0x8000_0000_0000_0000..0xFFFF_FFFF_FFFF_FFFF do
+    resource |> Native.add_int(Bitwise.band(int, 0xFFFF_FFFF_FFFF_FFFF)) |> inserted(bloom)
+  end
+
+  @doc """
+  Checks if an integer added with `add_int/2` is possibly in the Bloom filter.
+  """
+  @spec member_int?(t(), integer()) :: boolean()
+  def member_int?(%__MODULE__{resource: resource}, int)
+      when is_integer(int) and int in -0x8000_0000_0000_0000..0xFFFF_FFFF_FFFF_FFFF do
+    {:ok, result} = Native.member_int(resource, Bitwise.band(int, 0xFFFF_FFFF_FFFF_FFFF))
+    result
+  end
+
+  @doc """
+  Adds a precomputed hash pair to the Bloom filter.
+
+  Items can be hashed once, for example with a 128-bit hash split into two
+  64-bit halves, and the hashes used to probe many filters without rehashing
+  the payload. The pair is folded into a single 64-bit hash that the filter's
+  bit indexes are derived from, so callers with one 64-bit hash can pass `0` as
+  `hash2`. Pre-hashed items bypass the filter's hasher and seed, so they only
+  match lookups made with `member_hashed?/3`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add_hashed(bloom, 0x9E3779B97F4A7C15, 0xBF58476D1CE4E5B9)
+      iex> BloomFilterEx.member_hashed?(bloom, 0x9E3779B97F4A7C15, 0xBF58476D1CE4E5B9)
+      true
+      iex> BloomFilterEx.member_hashed?(bloom, 0x94D049BB133111EB, 0)
+      false
+
+  """
+  @spec add_hashed(t(), non_neg_integer(), non_neg_integer()) ::
+          t() | {:error, :capacity_exceeded}
+  def add_hashed(%__MODULE__{resource: resource} = bloom, hash1, hash2)
+      when is_integer(hash1) and hash1 in 0..0xFFFF_FFFF_FFFF_FFFF and
+             is_integer(hash2) and hash2 in 0..0xFFFF_FFFF_FFFF_FFFF do
+    resource |> Native.add_hashed(hash1, hash2) |> inserted(bloom)
+  end
+
+  @doc """
+  Checks if a hash pair added with `add_hashed/3` is possibly in the filter.
+  """
+  @spec member_hashed?(t(), non_neg_integer(), non_neg_integer()) :: boolean()
+  def member_hashed?(%__MODULE__{resource: resource}, hash1, hash2)
+      when is_integer(hash1) and hash1 in 0..0xFFFF_FFFF_FFFF_FFFF and
+             is_integer(hash2) and hash2 in 0..0xFFFF_FFFF_FFFF_FFFF do
+    {:ok, result} = Native.member_hashed(resource, hash1, hash2)
+    result
+  end
+
+  @doc """
+  Hashes an item once, for probing many filters with `add_prepared/2` and
+  `member_prepared?/2` without encoding and hashing it again for each.
+
+  The item is hashed by its external term format with unseeded xxh3-128, whose
+  two halves are the pair `add_hashed/3` takes. Like pre-hashed items, prepared
+  keys bypass the filter's hasher and seed, so they never match items added
+  with `add/2`, and anyone can choose items whose keys collide.
+
+  ## Examples
+
+      iex> key = BloomFilterEx.prepare("alice")
+      iex> a = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add_prepared(key)
+      iex> b = BloomFilterEx.new(1000, 0.001)
+      iex> {BloomFilterEx.member_prepared?(a, key), BloomFilterEx.member_prepared?(b, key)}
+      {true, false}
+      iex> BloomFilterEx.member?(a, "alice")
+      false
+
+  """
+  @spec prepare(term()) :: prepared_key()
+  def prepare(item), do: Native.prepare(encode_item(item))
+
+  @doc """
+  Adds an item hashed with `prepare/1` to the Bloom filter.
+  """
+  @spec add_prepared(t(), prepared_key()) :: t() | {:error, :capacity_exceeded}
+  def add_prepared(%__MODULE__{resource: resource} = bloom, key) do
+    resource |> Native.add_prepared(key) |> inserted(bloom)
+  end
+
+  @doc """
+  Checks if an item added with `add_prepared/2` is possibly in the filter.
+  """
+  @spec member_prepared?(t(), prepared_key()) :: boolean()
+  def member_prepared?(%__MODULE__{resource: resource}, key) do
+    {:ok, result} = Native.member_prepared(resource, key)
+    result
+  end
+
+  @doc """
+  Returns the bit indexes `item` maps to, one per hash function, in order.
+
+  An item is a member when all of these bits are set, so this is useful for
+  debugging false positives and for checking other implementations of the
+  serialization format against this one. Bit `i` is bit `rem(i, 64)` of the
+  `div(i, 64)`th 64-bit word of the bit array.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(1000, 0.01)
+      iex> indexes = BloomFilterEx.hash_indexes(bloom, "item")
+      iex> length(indexes) == bloom.hash_count
+      true
+      iex> Enum.all?(indexes, &(&1 in 0..(bloom.size - 1)))
+      true
+
+  """
+  @spec hash_indexes(t(), term()) :: [non_neg_integer()]
+  def hash_indexes(%__MODULE__{resource: resource}, item) do
+    {:ok, indexes} = Native.hash_indexes(resource, encode_item(item))
+    indexes
+  end
+
+  @doc """
+  Clears the Bloom filter, resetting it to empty state.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add(bloom, "test")
+      iex> bloom.inserted_count
+      1
+      iex> bloom = BloomFilterEx.clear(bloom)
+      iex> bloom.inserted_count
+      0
+      iex> BloomFilterEx.member?(bloom, "test")
+      false
+
+  """
+  @spec clear(t()) :: t()
+  def clear(%__MODULE__{resource: resource} = bloom) do
+    {:ok, new_resource} = Native.clear(resource)
+    %{bloom | resource: new_resource, inserted_count: 0}
+  end
+
+  @doc """
+  Returns statistics about the Bloom filter.
+
+  ## Returns
+
+  A map containing:
+  - `size`: Total number of bits in the filter
+  - `hash_count`: Number of hash functions used
+  - `capacity`: Expected capacity
+  - `false_positive_rate`: Target false positive rate
+  - `inserted_count`: Number of distinct items inserted
+  - `saturation`: Percentage of bits set (0.0 to 100.0)
+  - `estimated_fpr`: Estimated actual false positive rate based on saturation
+  - `bits_set`: Number of bits set
+  - `over_capacity`: Whether more than `capacity` items have been inserted
+  - `write_version`: Number of writes made to the filter so far
+  - `hash`: The hash algorithm, `:sip13`, `:xxh3` or `:wyhash`
+  - `configured`: The parameters the filter was created with, `capacity` and
+    `false_positive_rate`
+  - `derived`: The parameters chosen for them: `num_bits` and `num_hashes`,
+    with `fpr_at_capacity`, the false positive rate expected once `capacity`
+    items are in the filter, and `fpr_at_inserted_count`, the rate expected
+    with `inserted_count` items in it
+
+  The expected rates follow from the size and hash count alone, so comparing
+  `fpr_at_capacity` with the configured rate shows what rounding the size and
+  hash count cost.
+
+  `inserted_count`, `bits_set` and the values derived from them are read as of
+  the same write, even while other processes are adding items, and
+  `write_version` identifies that write. Two calls returning the same
+  `write_version` saw the same bits.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> stats = BloomFilterEx.stats(bloom)
+      iex> stats.capacity
+      100
+      iex> stats.saturation
+      0.0
+      iex> stats.configured
+      %{capacity: 100, false_positive_rate: 0.01}
+      iex> stats.derived.fpr_at_inserted_count
+      0.0
+
+  """
+  @spec stats(t()) :: map()
+  def stats(%__MODULE__{resource: resource}) do
+    {:ok,
+     %{
+       num_bits: size,
+       num_hashes: hash_count,
+       false_positive_rate: false_positive_rate,
+       inserted_count: inserted_count,
+       capacity: capacity,
+       over_capacity: over_capacity,
+       bits_set: bits_set,
+       write_version: write_version,
+       fpr_at_capacity: fpr_at_capacity,
+       fpr_at_inserted_count: fpr_at_inserted_count,
+       hash: hash
+     }} = Native.stats(resource)
+
+    fill_ratio = bits_set / size
+
+    # Estimated FPR based on saturation: X^k
+    # where X = fraction of bits set, k = hash_count
+    estimated_fpr = :math.pow(fill_ratio, hash_count)
+
+    %{
+      size: size,
+      hash_count: hash_count,
+      capacity: capacity,
+      false_positive_rate: false_positive_rate,
+      inserted_count: inserted_count,
+      saturation: fill_ratio * 100.0,
+      estimated_fpr: estimated_fpr,
+      bits_set: bits_set,
+      over_capacity: over_capacity,
+      write_version: write_version,
+      hash: hash,
+      configured: %{capacity: capacity, false_positive_rate: false_positive_rate},
+      derived: %{
+        num_bits: size,
+        num_hashes: hash_count,
+        fpr_at_capacity: fpr_at_capacity,
+        fpr_at_inserted_count: fpr_at_inserted_count
+      }
+    }
+  end
+
+  @doc """
+  Returns whether more items have been inserted than the filter's capacity.
+
+  The false positive rate only holds up to `capacity` items and degrades
+  quickly past it, so an over-capacity filter should be rebuilt larger.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(2, 0.01) |> BloomFilterEx.add_many(["a", "b"])
+      iex> BloomFilterEx.over_capacity?(bloom)
+      false
+      iex> bloom |> BloomFilterEx.add("c") |> BloomFilterEx.over_capacity?()
+      true
+
+  """
+  @spec over_capacity?(t()) :: boolean()
+  def over_capacity?(%__MODULE__{resource: resource}) do
+    Native.over_capacity(resource)
+  end
+
+  @doc """
+  Returns `false` if a native call panicked while changing the filter.
+
+  The filter keeps working after such a panic rather than failing every later
+  call, but a panic in the middle of `clear/1` may have removed only some of
+  the filter's items, so an unhealthy filter should be rebuilt from its source
+  data.
+
+  ## Examples
+
+      iex> BloomFilterEx.new(100, 0.01) |> BloomFilterEx.healthy?()
+      true
+
+  """
+  @spec healthy?(t()) :: boolean()
+  def healthy?(%__MODULE__{resource: resource}) do
+    Native.healthy(resource)
+  end
+
+  @doc """
+  Returns usage counters kept by the filter since it was created or since the
+  last `reset_counters/1`.
+
+  The counters are shared by every process using the filter:
+
+  - `:adds` - items passed to the add functions, including duplicates
+  - `:member_checks` - items looked up by the membership functions
+  - `:positive_results` - lookups that found the item (including false positives)
+  - `:negative_results` - lookups that did not find the item
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> BloomFilterEx.member_many(bloom, ["a", "b"])
+      [true, false]
+      iex> BloomFilterEx.counters(bloom)
+      %{adds: 1, member_checks: 2, positive_results: 1, negative_results: 1}
+
+  """
+  @spec counters(t()) :: %{
+          adds: non_neg_integer(),
+          member_checks: non_neg_integer(),
+          positive_results: non_neg_integer(),
+          negative_results: non_neg_integer()
+        }
+  def counters(%__MODULE__{resource: resource}) do
+    Native.counters(resource)
+  end
+
+  @doc """
+  Returns the counters like `counters/1` and resets them to zero, so periodic
+  reporters can export the events since their last report.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> BloomFilterEx.reset_counters(bloom).adds
+      1
+      iex> BloomFilterEx.counters(bloom).adds
+      0
+
+  """
+  @spec reset_counters(t()) :: map()
+  def reset_counters(%__MODULE__{resource: resource}) do
+    Native.reset_counters(resource)
+  end
+
+  @doc """
+  Estimates the number of distinct items in the filter from its bit density.
+
+  This estimates the number of unique items as `-m / k * ln(1 - X / m)`, where
+  `m` is the filter size, `k` the hash count and `X` the number of bits set.
+  Unlike `inserted_count`, it needs no history, so it also works for filters
+  built by `union/2`, whose `inserted_count` counts items shared by both inputs
+  twice. The estimate is accurate to within a few percent up to the filter's
+  capacity.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> b = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> a = BloomFilterEx.add_many(a, Enum.to_list(1..1000))
+      iex> b = BloomFilterEx.add_many(b, Enum.to_list(501..1500))
+      iex> {:ok, merged} = BloomFilterEx.union(a, b)
+      iex> merged.inserted_count > 1900
+      true
+      iex> BloomFilterEx.estimated_cardinality(merged) in 1425..1575
+      true
+
+  """
+  @spec estimated_cardinality(t()) :: non_neg_integer()
+  def estimated_cardinality(%__MODULE__{resource: resource}) do
+    {:ok, estimate} = Native.estimated_cardinality(resource)
+    estimate
+  end
+
+  @doc """
+  Estimates the number of distinct items in both `a` and `b` from their bit
+  densities.
+
+  The estimate is `|A| + |B| - |A ∪ B|`, each term estimated as in
+  `estimated_cardinality/1`, with the union's bits computed natively without
+  building a new filter. Its error is roughly the error of the three
+  estimates combined, so small overlaps between large filters are only
+  approximate. Both filters must be compatible in the same way as for
+  `union/2`, or `{:error, :incompatible_filters}` is returned.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> b = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> a = BloomFilterEx.add_many(a, Enum.to_list(1..1000))
+      iex> b = BloomFilterEx.add_many(b, Enum.to_list(501..1500))
+      iex> {:ok, overlap} = BloomFilterEx.estimate_overlap(a, b)
+      iex> overlap in 450..550
+      true
+
+  """
+  @spec estimate_overlap(t(), t()) :: {:ok, non_neg_integer()} | {:error, error()}
+  def estimate_overlap(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    Native.estimate_overlap(a, b)
+  end
+
+  @doc """
+  Returns the number of bytes of memory held by the filter's bit array.
+
+  This memory lives outside the process heap, but it is allocated through the
+  VM's allocators so it is included in `:erlang.memory(:system)`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(1000, 0.01)
+      iex> BloomFilterEx.byte_size(bloom) == div(bloom.size, 8)
+      true
+
+  """
+  @spec byte_size(t()) :: non_neg_integer()
+  def byte_size(%__MODULE__{resource: resource}) do
+    {:ok, size} = Native.byte_size(resource)
+    size
+  end
+
+  @doc """
+  Returns whether no bit is set in the filter.
+
+  This stops at the first set bit, so it is much cheaper than `bits_set/1` on
+  filters that are not empty, for example to check that a freshly
+  deserialized filter holds any items at all.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> BloomFilterEx.empty?(bloom)
+      true
+      iex> BloomFilterEx.empty?(BloomFilterEx.add(bloom, "item"))
+      false
+
+  """
+  @spec empty?(t()) :: boolean()
+  def empty?(%__MODULE__{resource: resource}) do
+    {:ok, empty} = Native.is_empty(resource)
+    empty
+  end
+
+  @doc """
+  Returns the exact number of bits set in the filter.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new_with_bits(1024, 4)
+      iex> BloomFilterEx.bits_set(bloom)
+      0
+      iex> BloomFilterEx.bits_set(BloomFilterEx.add(bloom, "item")) in 1..4
+      true
+
+  """
+  @spec bits_set(t()) :: non_neg_integer()
+  def bits_set(%__MODULE__{resource: resource}) do
+    {:ok, bits_set} = Native.bits_set(resource)
+    bits_set
+  end
+
+  @doc """
+  Returns the fraction of bits currently set in the filter, from 0.0 to 1.0.
+
+  This counts the set bits of the underlying bit array, so it reflects
+  duplicates and unions exactly. A well-sized filter at capacity is about half
+  full; values well above that mean the false positive rate has grown past the
+  configured target.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> BloomFilterEx.fill_ratio(bloom)
+      0.0
+      iex> bloom = BloomFilterEx.add(bloom, "item")
+      iex> BloomFilterEx.fill_ratio(bloom) > 0.0
+      true
+
+  """
+  @spec fill_ratio(t()) :: float()
+  def fill_ratio(%__MODULE__{resource: resource}) do
+    {:ok, fill_ratio} = Native.fill_ratio(resource)
+    fill_ratio
+  end
+
+  @doc """
+  Estimates the current false positive rate from the bits actually set.
+
+  Unlike the configured `false_positive_rate`, this reflects how full the
+  filter really is, so it grows past the target once the filter is over
+  capacity.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> BloomFilterEx.estimated_false_positive_rate(bloom)
+      0.0
+      iex> bloom = BloomFilterEx.add_many(bloom, Enum.to_list(1..1000))
+      iex> BloomFilterEx.estimated_false_positive_rate(bloom) > 0.01
+      true
+
+  """
+  @spec estimated_false_positive_rate(t()) :: float()
+  def estimated_false_positive_rate(%__MODULE__{resource: resource}) do
+    {:ok, rate} = Native.estimated_false_positive_rate(resource)
+    rate
+  end
+
+  @doc """
+  Serializes the Bloom filter into a binary.
+
+  The binary contains the full filter state (bit array, hash count, capacity,
+  false positive rate, inserted count and hasher seed) behind a small versioned
+  header, so it can be written to disk or sent to another node. A length field
+  and a CRC32 checksum let `deserialize/1` detect truncated or corrupted data.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add(bloom, "test")
+      iex> binary = BloomFilterEx.serialize(bloom)
+      iex> is_binary(binary)
+      true
+
+  """
+  @spec serialize(t()) :: binary()
+  def serialize(%__MODULE__{resource: resource}) do
+    {:ok, binary} = Native.serialize(resource)
+    binary
+  end
+
+  @doc """
+  Serializes the Bloom filter into a zstd-compressed binary.
+
+  Sparse filters are mostly zero bits and compress extremely well, which makes
+  this a better fit for snapshots shipped over the network. `level` is a zstd
+  compression level, from 1 (fastest) to 22 (smallest), and defaults to 3.
+  The compression algorithm is recorded in the header, so `deserialize/1` and
+  `load/1` decompress the binary transparently.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(10_000, 0.01) |> BloomFilterEx.add("test")
+      iex> compressed = BloomFilterEx.serialize_compressed(bloom)
+      iex> byte_size(compressed) < byte_size(BloomFilterEx.serialize(bloom))
+      true
+      iex> {:ok, restored} = BloomFilterEx.deserialize(compressed)
+      iex> BloomFilterEx.member?(restored, "test")
+      true
+
+  """
+  @spec serialize_compressed(t(), integer()) :: binary()
+  def serialize_compressed(%__MODULE__{resource: resource}, level \\ 3)
+      when is_integer(level) and level in 1..22 do
+    {:ok, binary} = Native.serialize_compressed(resource, level)
+    binary
+  end
+
+  @doc """
+  Serializes the Bloom filter lazily, as a stream of binaries of at most
+  `chunk_size` bytes.
+
+  The chunks concatenate to the same binary `serialize/1` returns, but only one
+  chunk is held in memory at a time, so very large filters can be written to a
+  file or socket without building a single huge binary. Bits added while the
+  stream is consumed may or may not be included.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(1000, 0.01) |> BloomFilterEx.add("test")
+      iex> chunks = bloom |> BloomFilterEx.serialize_stream(100) |> Enum.to_list()
+      iex> Enum.all?(chunks, &(byte_size(&1) <= 100))
+      true
+      iex> {:ok, restored} = chunks |> IO.iodata_to_binary() |> BloomFilterEx.deserialize()
+      iex> BloomFilterEx.member?(restored, "test")
+      true
+
+  """
+  @spec serialize_stream(t(), pos_integer()) :: Enumerable.t()
+  def serialize_stream(%__MODULE__{resource: resource}, chunk_size \\ 1_048_576)
+      when is_integer(chunk_size) and chunk_size > 0 do
+    Stream.resource(
+      fn ->
+        {:ok, stream} = Native.serialize_stream(resource, chunk_size)
+        stream
+      end,
+      fn stream ->
+        case Native.next_chunk(stream) do
+          {:ok, nil} -> {:halt, stream}
+          {:ok, chunk} -> {[chunk], stream}
+        end
+      end,
+      fn _stream -> :ok end
+    )
+  end
+
+  @doc """
+  Restores a Bloom filter from a binary produced by `serialize/1` or
+  `serialize_compressed/2`.
+
+  Binaries written by every earlier release are accepted, so snapshots keep
+  loading after an upgrade, while `serialize/1` always writes the latest
+  format. The formats are described in the README.
+
+  Returns `{:error, :unsupported_version}` if the binary was written by an
+  unknown format version, `{:error, :corrupt_data}` if it fails its length or
+  checksum check, or `{:error, :invalid_data}` if it is otherwise malformed.
+  Compressed binaries are only decompressed up to the size their header gives,
+  which counts against the budget set with `configure/1`, and are rejected as
+  `{:error, :corrupt_data}` if they expand past it.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> bloom = BloomFilterEx.add(bloom, "test")
+      iex> {:ok, restored} = bloom |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> restored.inserted_count
+      1
+      iex> BloomFilterEx.member?(restored, "test")
+      true
+
+      iex> BloomFilterEx.deserialize(<<1, 2, 3>>)
+      {:error, :invalid_data}
+
+      iex> BloomFilterEx.deserialize("BFEX" <> <<99>>)
+      {:error, :unsupported_version}
+
+      iex> binary = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.serialize()
+      iex> BloomFilterEx.deserialize(binary_part(binary, 0, byte_size(binary) - 1))
+      {:error, :corrupt_data}
+
+  """
+  @spec deserialize(binary()) :: {:ok, t()} | {:error, error()}
+  def deserialize(binary) when is_binary(binary) do
+    with {:ok, resource} <- Native.deserialize(binary) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Serializes the Bloom filter like `serialize/1`, as base64 text.
+
+  The result uses the standard alphabet with padding, so it can be stored in
+  JSON, environment variables or text columns. It is encoded as the filter is
+  written, without first building the raw binary.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("test")
+      iex> encoded = BloomFilterEx.serialize_base64(bloom)
+      iex> Base.decode64!(encoded) == BloomFilterEx.serialize(bloom)
+      true
+
+  """
+  @spec serialize_base64(t()) :: String.t()
+  def serialize_base64(%__MODULE__{resource: resource}) do
+    {:ok, encoded} = Native.serialize_base64(resource)
+    encoded
+  end
+
+  @doc """
+  Restores a Bloom filter from base64 text produced by `serialize_base64/1`.
+
+  Accepts the base64 encoding of anything `deserialize/1` accepts, and returns
+  the same errors. Text that is not valid padded base64 returns
+  `{:error, :invalid_data}`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("test")
+      iex> {:ok, restored} = bloom |> BloomFilterEx.serialize_base64() |> BloomFilterEx.deserialize_base64()
+      iex> BloomFilterEx.member?(restored, "test")
+      true
+
+      iex> BloomFilterEx.deserialize_base64("not base64!")
+      {:error, :invalid_data}
+
+  """
+  @spec deserialize_base64(String.t()) :: {:ok, t()} | {:error, error()}
+  def deserialize_base64(encoded) when is_binary(encoded) do
+    with {:ok, resource} <- Native.deserialize_base64(encoded) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Returns the filter's raw bit array as a binary.
+
+  The bits are stored as 64-bit words, each written little-endian, so bit `i`
+  of the filter is bit `rem(i, 8)` (counting from the least significant bit)
+  of byte `div(i, 8)`. The README's "Bit Layout" section describes how items
+  map to bits, and `hash_indexes/2` lists the bits of any one item. Unlike
+  `serialize/1`, no parameters are included; use `from_bits/3` with the same
+  hash count and seed to rebuild the filter. `from_bits/3` hashes with
+  SipHash-1-3, so filters created with another `:hash` don't rebuild that way.
+
+  The binary is a snapshot, which can be kept in ETS or `:persistent_term`,
+  or inspected and analysed in Elixir without the native filter. Large
+  binaries are reference counted, so storing one there doesn't copy it.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new_with_bits(128, 3)
+      iex> byte_size(BloomFilterEx.to_bits(bloom))
+      16
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> bits = BloomFilterEx.to_bits(bloom)
+      iex> set? = fn i -> Bitwise.band(:binary.at(bits, div(i, 8)), Bitwise.bsl(1, rem(i, 8))) != 0 end
+      iex> bloom |> BloomFilterEx.hash_indexes("a") |> Enum.all?(set?)
+      true
+      iex> Enum.sum(for <<bit::1 <- bits>>, do: bit) == length(Enum.uniq(BloomFilterEx.hash_indexes(bloom, "a")))
+      true
+
+  """
+  @spec to_bits(t()) :: binary()
+  def to_bits(%__MODULE__{resource: resource}) do
+    {:ok, bits} = Native.to_bits(resource)
+    bits
+  end
+
+  @doc """
+  Rebuilds a Bloom filter from a raw bit array in the layout of `to_bits/1`.
+
+  This allows importing filters built by another system using the same
+  hashing scheme, given their hash count and hasher `seed`. The bit array must
+  be made of whole 64-bit words, otherwise `{:error, :invalid_num_bits}` is
+  returned. The capacity and false positive rate are derived as in
+  `new_with_bits/2`, and `inserted_count` is estimated from the bits set.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new_with_seed(100, 0.01, 42) |> BloomFilterEx.add("a")
+      iex> bits = BloomFilterEx.to_bits(bloom)
+      iex> {:ok, rebuilt} = BloomFilterEx.from_bits(bits, bloom.hash_count, 42)
+      iex> BloomFilterEx.member?(rebuilt, "a")
+      true
+
+      iex> BloomFilterEx.from_bits(<<0::size(100)>>, 3, 42)
+      {:error, :invalid_num_bits}
+
+  """
+  @spec from_bits(bitstring(), pos_integer(), non_neg_integer()) ::
+          {:ok, t()} | {:error, error()}
+  def from_bits(bits, num_hashes, seed)
+      when is_bitstring(bits) and is_integer(num_hashes) and num_hashes > 0 and
+             is_integer(seed) and seed >= 0 and
+             seed < 0x1_0000_0000_0000_0000_0000_0000_0000_0000 do
+    if is_binary(bits) do
+      with {:ok, resource} <- Native.from_bits(bits, num_hashes, seed) do
+        {:ok, from_resource(resource)}
+      end
+    else
+      {:error, :invalid_num_bits}
+    end
+  end
+
+  @doc """
+  Writes the Bloom filter to the file at `path`, in the format of `serialize/1`.
+
+  The filter is written from native code, so large filters are never copied
+  onto the BEAM heap. The file is written to a temporary path and renamed into
+  place, so a crash mid-save leaves any previous file intact.
+
+  Returns `{:error, reason}` if the file cannot be written, using the same
+  atoms as `File.write/2` for common failures such as `:enoent` and `:eacces`.
+  """
+  @spec save(t(), Path.t()) :: :ok | {:error, error()}
+  def save(%__MODULE__{resource: resource}, path) do
+    with {:ok, {}} <- Native.save(resource, IO.chardata_to_string(path)), do: :ok
+  end
+
+  @doc """
+  Saves the Bloom filter to `path` like `save/2` every `interval_ms`
+  milliseconds, from a native thread, until `disable_autosave/1` is called.
+
+  Saves are skipped while the filter is unchanged. A save that fails, say
+  because the directory is missing, is retried on the next interval; call
+  `save/2` to see the error. Enabling autosave again replaces the previous
+  path and interval.
+
+  The thread holds a reference to the filter, so the filter is not garbage
+  collected while autosave is enabled.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("test")
+      iex> path = Path.join(System.tmp_dir!(), "autosave_doctest.bloom")
+      iex> BloomFilterEx.enable_autosave(bloom, path, 10)
+      :ok
+      iex> Process.sleep(100)
+      iex> BloomFilterEx.disable_autosave(bloom)
+      :ok
+      iex> {:ok, restored} = BloomFilterEx.load(path)
+      iex> BloomFilterEx.member?(restored, "test")
+      true
+
+  """
+  @spec enable_autosave(t(), Path.t(), pos_integer()) :: :ok
+  def enable_autosave(%__MODULE__{resource: resource}, path, interval_ms)
+      when is_integer(interval_ms) and interval_ms > 0 do
+    {:ok, {}} = Native.enable_autosave(resource, IO.chardata_to_string(path), interval_ms)
+    :ok
+  end
+
+  @doc """
+  Stops the autosave started by `enable_autosave/3`, waiting for a save in
+  progress to finish. Does nothing if autosave is not enabled.
+  """
+  @spec disable_autosave(t()) :: :ok
+  def disable_autosave(%__MODULE__{resource: resource}) do
+    {:ok, {}} = Native.disable_autosave(resource)
+    :ok
+  end
+
+  @doc """
+  Appends every item added to the filter from now on to the write-ahead log at
+  `path`, so inserts made since the last `save/2` can be recovered with
+  `replay_wal/2`.
+
+  The log is created if it does not exist and appended to if it does, in which
+  case it must have been written by a filter with the same seed, and a
+  partial record left at its end by a crash is cut off first. Each add
+  writes its items' hashes to the log before setting their bits, and fails
+  with the write's error if the log cannot be written. Only adds are logged;
+  unions, merges, applied diffs and `clear/1` are not. Enabling a log replaces any log
+  already enabled.
+
+  To start a fresh log after each snapshot, enable the new log before calling
+  `save/2`: every item missing from the new log is then in the snapshot, and
+  the old log can be deleted.
+
+  Returns `{:error, :incompatible_filters}` if the log belongs to a filter
+  with a different seed or hash algorithm, or `{:error, reason}` if it cannot
+  be opened.
+
+  ## Examples
+
+      iex> path = Path.join(System.tmp_dir!(), "wal_doctest.bloom")
+      iex> seed = 42
+      iex> File.rm(path)
+      iex> bloom = BloomFilterEx.new_with_seed(100, 0.01, seed)
+      iex> BloomFilterEx.enable_wal(bloom, path)
+      :ok
+      iex> bloom = BloomFilterEx.add(bloom, "test")
+      iex> recovered = BloomFilterEx.new_with_seed(100, 0.01, seed)
+      iex> {:ok, recovered} = BloomFilterEx.replay_wal(recovered, path)
+      iex> BloomFilterEx.member?(recovered, "test")
+      true
+
+  """
+  @spec enable_wal(t(), Path.t()) :: :ok | {:error, error()}
+  def enable_wal(%__MODULE__{resource: resource}, path) do
+    with {:ok, {}} <- Native.enable_wal(resource, IO.chardata_to_string(path)), do: :ok
+  end
+
+  @doc """
+  Stops appending to the write-ahead log enabled by `enable_wal/2`. Does nothing
+  if no log is enabled.
+  """
+  @spec disable_wal(t()) :: :ok
+  def disable_wal(%__MODULE__{resource: resource}) do
+    {:ok, {}} = Native.disable_wal(resource)
+    :ok
+  end
+
+  @doc """
+  Adds every item in the write-ahead log at `path` to the filter, which must
+  have the same seed and hash algorithm as the filter that wrote it.
+
+  Typically called on a filter from `load/1`, to recover the items added
+  since it was saved. Adding an item twice has no effect, so items already in
+  the snapshot are harmless. A record cut short by a crash is skipped. The
+  items are not appended to the filter's own log.
+
+  Returns `{:error, :incompatible_filters}` if the seeds or hash algorithms
+  differ, or `{:error, reason}` if the log cannot be read.
+  """
+  @spec replay_wal(t(), Path.t()) :: {:ok, t()} | {:error, error()}
+  def replay_wal(%__MODULE__{resource: resource} = bloom, path) do
+    with {:ok, count} <- Native.replay_wal(resource, IO.chardata_to_string(path)) do
+      {:ok, %{bloom | inserted_count: bloom.inserted_count + count}}
+    end
+  end
+
+  @doc """
+  Loads a Bloom filter from a file written by `save/2` or `serialize/1`.
+
+  Returns `{:error, reason}` if the file cannot be read, or the same errors as
+  `deserialize/1` if its contents are not a valid filter.
+  """
+  @spec load(Path.t()) :: {:ok, t()} | {:error, error()}
+  def load(path) do
+    with {:ok, resource} <- Native.load(IO.chardata_to_string(path)) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Creates a Bloom filter from the records of the file at `path`.
+
+  The file is read and inserted from native code on a dirty IO scheduler, which
+  is much faster than streaming it through `File.stream!/1` and `add/2` for
+  files with millions of lines. Records are separated by `delimiter`, a single
+  byte that defaults to a newline, and are added as raw bytes like
+  `add_iodata/2`, so look them up with `member_iodata?/2`. The delimiter is
+  not part of the record, but a `"\r"` before a newline is. Empty records are
+  skipped.
+
+  Returns `{:error, reason}` if the file cannot be read, using the same atoms
+  as `File.read/1`.
+
+  ## Options
+
+  - `:parallel` - when `true`, records are read in batches that are hashed and
+    inserted across a pool of native threads, as in `add_many/3`. Defaults to
+    `false`.
+  """
+  @spec new_from_file(Path.t(), pos_integer(), float(), binary(), keyword()) ::
+          {:ok, t()} | {:error, error()}
+  def new_from_file(path, capacity, false_positive_rate, delimiter \\ "\n", opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_binary(delimiter) and byte_size(delimiter) == 1 and
+             is_list(opts) do
+    path = IO.chardata_to_string(path)
+    <<delimiter>> = delimiter
+    parallel = Keyword.get(opts, :parallel, false)
+
+    with {:ok, resource} <-
+           Native.new_from_file(path, capacity, false_positive_rate, delimiter, parallel) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Copies the Bloom filter into a new, independent filter.
+
+  The copy is a consistent point-in-time snapshot: items added to either
+  filter afterwards are not visible in the other. This is useful because
+  `add/2` mutates the underlying native filter in place, which is shared by
+  every copy of the struct.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("before")
+      iex> snapshot = BloomFilterEx.clone(bloom)
+      iex> _bloom = BloomFilterEx.add(bloom, "after")
+      iex> BloomFilterEx.member?(snapshot, "before")
+      true
+      iex> BloomFilterEx.member?(snapshot, "after")
+      false
+
+  """
+  @spec clone(t()) :: t() | {:error, error()}
+  def clone(%__MODULE__{resource: resource}) do
+    resource |> Native.clone() |> created()
+  end
+
+  @doc """
+  Returns an immutable copy of the filter for read-only workloads.
+
+  Lookups on the returned `BloomFilterEx.Frozen` filter take no lock, and it
+  cannot be added to. Like `clone/1`, later changes to `bloom` are not seen by
+  the frozen copy, and the copy counts against the memory budget set with
+  `configure/1`. Its `inserted_count` is the count as of the bits copied.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> frozen = BloomFilterEx.freeze(bloom)
+      iex> frozen.inserted_count
+      1
+      iex> BloomFilterEx.Frozen.member?(frozen, "a")
+      true
+
+  """
+  @spec freeze(t()) ::
+          BloomFilterEx.Frozen.t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def freeze(%__MODULE__{resource: resource}) do
+    {:ok, stats} = Native.stats(resource)
+
+    case Native.freeze(resource) do
+      {:ok, {frozen, inserted_count}} ->
+        %BloomFilterEx.Frozen{
+          resource: frozen,
+          size: stats.num_bits,
+          hash_count: stats.num_hashes,
+          capacity: stats.capacity,
+          false_positive_rate: stats.false_positive_rate,
+          inserted_count: inserted_count
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Returns a copy-on-write branch of the filter, for speculative inserts that
+  must not reach `bloom`.
+
+  Branching copies nothing: the `BloomFilterEx.Branch` reads through to the
+  filter's bits, and copies each 4 KiB page of them the first time one of its
+  own inserts writes to it, so a branch of a large filter costs about as much
+  memory as the pages it changes. Unlike `clone/1`, pages the branch hasn't
+  written still see later changes to `bloom`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("base")
+      iex> branch = BloomFilterEx.branch(bloom) |> BloomFilterEx.Branch.add("what-if")
+      iex> {BloomFilterEx.Branch.member?(branch, "base"), BloomFilterEx.Branch.member?(branch, "what-if")}
+      {true, true}
+      iex> BloomFilterEx.member?(bloom, "what-if")
+      false
+
+  """
+  @spec branch(t()) :: BloomFilterEx.Branch.t()
+  def branch(%__MODULE__{resource: resource} = bloom) do
+    %BloomFilterEx.Branch{resource: Native.branch(resource), parent: bloom}
+  end
+
+  @doc """
+  Creates a filter backed by a memory-mapped file at `path`.
+
+  The filter is sized like `new/2`, and the file, which must not exist yet, is
+  created with room for its bit array. See `BloomFilterEx.Mmap` for how the
+  filter is used and reopened after a restart.
+
+  Returns `{:error, :eexist}` if `path` already exists, or another reason
+  using the same atoms as `File.open/2` if it cannot be created.
+  """
+  @spec new_mmap(Path.t(), pos_integer(), float()) ::
+          {:ok, BloomFilterEx.Mmap.t()} | {:error, error()}
+  def new_mmap(path, capacity, false_positive_rate)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    path = IO.chardata_to_string(path)
+
+    with {:ok, resource} <- Native.new_mmap(path, capacity, false_positive_rate) do
+      {:ok, BloomFilterEx.Mmap.from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Merges two Bloom filters into a new filter containing the items of both.
+
+  Both filters must share the same size, hash count, hasher seed and hash
+  algorithm, which is the case for filters created by `new_with_seed/3` with
+  the same arguments, or restored from the same `serialize/1` snapshot.
+  The `inserted_count` of the result is the sum of both inputs.
+
+  ## Examples
+
+      iex> template = BloomFilterEx.new(100, 0.01)
+      iex> {:ok, a} = template |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> {:ok, b} = template |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> {:ok, merged} = BloomFilterEx.union(BloomFilterEx.add(a, "a"), BloomFilterEx.add(b, "b"))
+      iex> BloomFilterEx.member?(merged, "a") and BloomFilterEx.member?(merged, "b")
+      true
+
+      iex> BloomFilterEx.union(BloomFilterEx.new(100, 0.01), BloomFilterEx.new(100, 0.01))
+      {:error, :incompatible_filters}
+
+  """
+  @spec union(t(), t()) :: {:ok, t()} | {:error, error()}
+  def union(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    with {:ok, resource} <- Native.union(a, b) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Merges a non-empty list of Bloom filters into a new filter containing the
+  items of all of them.
+
+  The result is the same as folding `union/2` over the list, but built in a
+  single native call that copies the bit array once, rather than once per
+  filter. Every filter must be compatible with the first in the same way as
+  for `union/2`, and the `inserted_count` of the result is the sum of all of
+  them.
+
+  ## Examples
+
+      iex> template = BloomFilterEx.new(100, 0.01)
+      iex> parts =
+      ...>   for item <- ["a", "b", "c"] do
+      ...>     {:ok, part} = template |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      ...>     BloomFilterEx.add(part, item)
+      ...>   end
+      iex> {:ok, merged} = BloomFilterEx.union_many(parts)
+      iex> Enum.all?(["a", "b", "c"], &BloomFilterEx.member?(merged, &1))
+      true
+      iex> merged.inserted_count
+      3
+
+  """
+  @spec union_many([t(), ...]) :: {:ok, t()} | {:error, error()}
+  def union_many([_ | _] = filters) do
+    with {:ok, resource} <- Native.union_many(Enum.map(filters, & &1.resource)) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Intersects two Bloom filters into a new filter approximating the items
+  present in both.
+
+  Both filters must be compatible in the same way as for `union/2`. The
+  result may report more false positives than a filter built directly from the
+  common items, and its `inserted_count` is the smaller of the two inputs.
+
+  ## Examples
+
+      iex> template = BloomFilterEx.new(100, 0.01)
+      iex> {:ok, a} = template |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> {:ok, b} = template |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> a = BloomFilterEx.add_many(a, ["shared", "only-a"])
+      iex> b = BloomFilterEx.add_many(b, ["shared", "only-b"])
+      iex> {:ok, common} = BloomFilterEx.intersection(a, b)
+      iex> BloomFilterEx.member?(common, "shared")
+      true
+      iex> common.inserted_count
+      2
+
+  """
+  @spec intersection(t(), t()) :: {:ok, t()} | {:error, error()}
+  def intersection(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    with {:ok, resource} <- Native.intersection(a, b) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Returns whether two filters have the same parameters and bit array.
+
+  Filters are equal when they share their size, hash count, hasher seed,
+  capacity and false positive rate, and have exactly the same bits set, so a
+  filter always equals its `serialize/1` round-trip, and replicas are equal
+  once merges have converged. Inserted counts are not compared, since replicas
+  can count the same items differently.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("a")
+      iex> {:ok, restored} = bloom |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> BloomFilterEx.equal?(bloom, restored)
+      true
+      iex> BloomFilterEx.equal?(bloom, BloomFilterEx.add(restored, "b"))
+      false
+
+  """
+  @spec equal?(t(), t()) :: boolean()
+  def equal?(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    {:ok, equal} = Native.equal(a, b)
+    equal
+  end
+
+  @doc """
+  Unions a filter serialized by `serialize/1` or `serialize_compressed/2` into
+  `bloom`, in place.
+
+  This is the merge step of gossip-style replication: nodes exchange
+  serialized filters and merge whatever they receive, without restoring it
+  into a filter first. The filters must be compatible, as for `union/2`.
+  Unlike `union/2`, the resulting `inserted_count` is the larger of the two
+  counts rather than their sum, so merging the same state again is a no-op.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(100, 0.01, 7) |> BloomFilterEx.add("a")
+      iex> b = BloomFilterEx.new_with_seed(100, 0.01, 7) |> BloomFilterEx.add("b")
+      iex> {:ok, a} = BloomFilterEx.merge_serialized(a, BloomFilterEx.serialize(b))
+      iex> BloomFilterEx.member?(a, "b")
+      true
+      iex> a.inserted_count
+      1
+
+  """
+  @spec merge_serialized(t(), binary()) :: {:ok, t()} | {:error, error()}
+  def merge_serialized(%__MODULE__{resource: resource} = bloom, binary) when is_binary(binary) do
+    with {:ok, count} <- Native.merge_serialized(resource, binary) do
+      {:ok, %{bloom | inserted_count: count}}
+    end
+  end
+
+  @doc """
+  Replaces the bits of `bloom` with those of `source`, in place.
+
+  `source` is either another Bloom filter or a binary from `serialize/1` or
+  `serialize_compressed/2`. The swap happens under the filter's write lock,
+  so concurrent lookups see either all of the old bits or all of the new ones,
+  and every process holding `bloom` sees the new bits without being handed a
+  new filter. This allows hot-swapping a filter rebuilt from the source of
+  truth, as long as it was built with the same size, hash count and seed, for
+  example with `new_with_seed/3`. Otherwise `{:error, :incompatible_filters}`
+  is returned and `bloom` is unchanged.
+
+  The inserted count is taken from `source`. Versions returned by
+  `snapshot_version/1` before the swap can no longer be diffed.
+
+  ## Examples
+
+      iex> live = BloomFilterEx.new_with_seed(100, 0.01, 7) |> BloomFilterEx.add("stale")
+      iex> rebuilt = BloomFilterEx.new_with_seed(100, 0.01, 7) |> BloomFilterEx.add("fresh")
+      iex> {:ok, swapped} = BloomFilterEx.replace(live, rebuilt)
+      iex> {BloomFilterEx.member?(live, "stale"), BloomFilterEx.member?(live, "fresh")}
+      {false, true}
+      iex> swapped.inserted_count
+      1
+
+  """
+  @spec replace(t(), t() | binary()) :: {:ok, t()} | {:error, error()}
+  def replace(%__MODULE__{resource: resource} = bloom, %__MODULE__{resource: source}) do
+    with {:ok, count} <- Native.replace(resource, source) do
+      {:ok, %{bloom | inserted_count: count}}
+    end
+  end
+
+  def replace(%__MODULE__{resource: resource} = bloom, binary) when is_binary(binary) do
+    with {:ok, count} <- Native.replace_serialized(resource, binary) do
+      {:ok, %{bloom | inserted_count: count}}
+    end
+  end
+
+  @doc """
+  Estimates the Jaccard similarity of the sets behind two Bloom filters.
+
+  The result is `|A ∩ B| / |A ∪ B|`, from 0.0 (disjoint) to 1.0 (identical),
+  estimated from the number of bits set in each filter and in their union.
+  Both filters must be compatible, as for `union/2`, otherwise
+  `{:error, :incompatible_filters}` is returned. The estimate is least accurate
+  for small sets and for filters filled past their capacity.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> b = BloomFilterEx.new_with_seed(10_000, 0.01, 1)
+      iex> a = BloomFilterEx.add_many(a, Enum.to_list(1..1000))
+      iex> b = BloomFilterEx.add_many(b, Enum.to_list(501..1500))
+      iex> {:ok, similarity} = BloomFilterEx.similarity(a, b)
+      iex> Float.round(similarity, 1)
+      0.3
+
+  """
+  @spec similarity(t(), t()) :: {:ok, float()} | {:error, error()}
+  def similarity(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    Native.similarity(a, b)
+  end
+
+  @doc """
+  Returns whether two Bloom filters can be combined with `union/2` and
+  `intersection/2`, that is whether they share the same size, hash count,
+  hasher seed and hash algorithm.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.new_with_seed(100, 0.01, 7)
+      iex> b = BloomFilterEx.new_with_seed(100, 0.01, 7) |> BloomFilterEx.add("b")
+      iex> BloomFilterEx.compatible?(a, b)
+      true
+      iex> BloomFilterEx.compatible?(a, BloomFilterEx.new_with_seed(100, 0.01, 8))
+      false
+
+  """
+  @spec compatible?(t(), t()) :: boolean()
+  def compatible?(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    {:ok, result} = Native.compatible(a, b)
+    result
+  end
+
+  @doc """
+  Returns a version number marking the filter's current state, for use with
+  `diff_since/2`.
+
+  To replicate a filter, take a version, send a full copy (for example with
+  `serialize/1`), and from then on send `diff_since/2` of the last version
+  taken instead of the whole filter. Change tracking starts with the first
+  call, and costs an extra 1/16 of the filter's size from then on.
+  """
+  @spec snapshot_version(t()) :: pos_integer()
+  def snapshot_version(%__MODULE__{resource: resource}) do
+    {:ok, version} = Native.snapshot_version(resource)
+    version
+  end
+
+  @doc """
+  Encodes the bits set since `version` as a compact binary for `apply_diff/2`.
+
+  Diffs only cover blocks of the bit array written since `version`, so they
+  are far smaller than the full filter when few items were added. Returns
+  `{:error, :unknown_version}` if `version` was not returned by
+  `snapshot_version/1` on this filter, or was taken before it was cleared.
+
+  ## Examples
+
+      iex> source = BloomFilterEx.new(1000, 0.01)
+      iex> version = BloomFilterEx.snapshot_version(source)
+      iex> {:ok, replica} = source |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+      iex> source = BloomFilterEx.add(source, "new")
+      iex> {:ok, diff} = BloomFilterEx.diff_since(source, version)
+      iex> byte_size(diff) < byte_size(BloomFilterEx.serialize(source))
+      true
+      iex> {:ok, replica} = BloomFilterEx.apply_diff(replica, diff)
+      iex> BloomFilterEx.member?(replica, "new")
+      true
+
+      iex> BloomFilterEx.diff_since(BloomFilterEx.new(100, 0.01), 1)
+      {:error, :unknown_version}
+
+  """
+  @spec diff_since(t(), pos_integer()) :: {:ok, binary()} | {:error, error()}
+  def diff_since(%__MODULE__{resource: resource}, version)
+      when is_integer(version) and version >= 0 do
+    Native.diff_since(resource, version)
+  end
+
+  @doc """
+  Sets the bits from a `diff_since/2` diff in the filter, in place.
+
+  The filter must be compatible with the one the diff was taken from, as for
+  `union/2`. Its `inserted_count` becomes the larger of its own and the
+  sender's.
+
+  The bits are not written to a log enabled with `enable_wal/2`, which only
+  holds the hashes of added items, so a replica that must not lose them in a
+  crash has to `save/2` a snapshot after applying diffs.
+  """
+  @spec apply_diff(t(), binary()) :: {:ok, t()} | {:error, error()}
+  def apply_diff(%__MODULE__{resource: resource} = bloom, diff) when is_binary(diff) do
+    with {:ok, count} <- Native.apply_diff(resource, diff) do
+      {:ok, %{bloom | inserted_count: count}}
+    end
+  end
+
+  @doc """
+  Registers the Bloom filter under `name` so other processes can find it with
+  `whereis/1`.
+
+  The registry lives in the native library and is shared by every process on
+  the node. A registered filter is kept alive until `unregister/1` is called.
+
+  Returns `{:error, :already_registered}` if the name is taken.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add("item")
+      iex> BloomFilterEx.register(bloom, "doctest-register")
+      :ok
+      iex> BloomFilterEx.register(bloom, "doctest-register")
+      {:error, :already_registered}
+      iex> "doctest-register" |> BloomFilterEx.whereis() |> BloomFilterEx.member?("item")
+      true
+      iex> BloomFilterEx.unregister("doctest-register")
+      :ok
+
+  """
+  @spec register(t(), String.t()) :: :ok | {:error, :already_registered}
+  def register(%__MODULE__{resource: resource}, name) when is_binary(name) do
+    if Native.register(name, resource), do: :ok, else: {:error, :already_registered}
+  end
+
+  @doc """
+  Returns the Bloom filter registered under `name`, or `nil` if there is none.
+
+  ## Examples
+
+      iex> BloomFilterEx.whereis("doctest-not-registered")
+      nil
+
+  """
+  @spec whereis(String.t()) :: t() | nil
+  def whereis(name) when is_binary(name) do
+    case Native.whereis(name) do
+      nil -> nil
+      resource -> from_resource(resource)
+    end
+  end
+
+  @doc """
+  Removes `name` from the registry. Unknown names are ignored.
+  """
+  @spec unregister(String.t()) :: :ok
+  def unregister(name) when is_binary(name) do
+    Native.unregister(name)
+    :ok
+  end
+
+  @doc """
+  Returns the names of all registered Bloom filters, in no particular order.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> :ok = BloomFilterEx.register(bloom, "doctest-registered")
+      iex> "doctest-registered" in BloomFilterEx.registered()
+      true
+      iex> BloomFilterEx.unregister("doctest-registered")
+      :ok
+
+  """
+  @spec registered() :: [String.t()]
+  def registered do
+    Native.list_registered()
+  end
+
+  @doc """
+  Sends `{:bloom_filter_released, tag}` to `pid` once the filter's native
+  resource is garbage collected.
+
+  The resource is released when no process holds the filter, or a copy of it,
+  any longer. Registered filters are held by the registry until they are
+  unregistered. A filter notifies at most one process, so calling this again
+  replaces the previous `pid` and `tag`. Filters derived from this one, such
+  as those returned by `clone/1` or `union/2`, don't inherit the notification.
+  """
+  @spec notify_on_release(t(), term(), pid()) :: :ok
+  def notify_on_release(%__MODULE__{resource: resource}, tag, pid \\ self()) when is_pid(pid) do
+    {:ok, {}} = Native.notify_on_release(resource, pid, tag)
+    :ok
+  end
+
+  @doc """
+  Sends `{:bloom_fill_alarm, ratio}` to `pid` soon after an insert takes the
+  fill ratio, the share of the filter's bits that are set, to `threshold` or
+  more.
+
+  The alarm fires once and is then cleared. Counting the bits takes a pass over
+  the whole filter, so inserts only count them as the expected fill ratio nears
+  the threshold, and the count runs on a thread of its own rather than holding
+  up the insert; the ratio sent may be a little past it. If the filter is
+  already past the threshold, the next insert sends the message. Calling this
+  again replaces the previous alarm.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01)
+      iex> :ok = BloomFilterEx.set_fill_alarm(bloom, 0.25)
+      iex> _bloom = Enum.reduce(1..100, bloom, &BloomFilterEx.add(&2, &1))
+      iex> receive do
+      ...>   {:bloom_fill_alarm, ratio} -> ratio >= 0.25
+      ...> after
+      ...>   1000 -> :timeout
+      ...> end
+      true
+
+  """
+  @spec set_fill_alarm(t(), float(), pid()) :: :ok | {:error, :invalid_value}
+  def set_fill_alarm(%__MODULE__{resource: resource}, threshold, pid \\ self())
+      when is_float(threshold) and is_pid(pid) do
+    case Native.set_fill_alarm(resource, threshold, pid) do
+      {:ok, {}} -> :ok
+      {:error, _reason} = error -> error
+    end
+  end
+
+  defp created({:ok, resource}), do: from_resource(resource)
+  defp created({:error, _reason} = error), do: error
+
+  defp from_resource(resource) do
+    {:ok, stats} = Native.stats(resource)
+
+    %__MODULE__{
+      resource: resource,
+      size: stats.num_bits,
+      hash_count: stats.num_hashes,
+      capacity: stats.capacity,
+      false_positive_rate: stats.false_positive_rate,
+      inserted_count: stats.inserted_count,
+      strict: stats.strict
+    }
+  end
+
+  # The add NIFs return whether the item was new, or how many items were new.
+  # Strict filters reject inserts past their capacity without changing, and
+  # inserts with a timeout give up if the lock isn't acquired in time.
+  defp inserted({:ok, true}, bloom), do: %{bloom | inserted_count: bloom.inserted_count + 1}
+  defp inserted({:ok, false}, bloom), do: bloom
+
+  defp inserted({:ok, count}, bloom) when is_integer(count),
+    do: %{bloom | inserted_count: bloom.inserted_count + count}
+
+  defp inserted({:error, :capacity_exceeded} = error, _bloom), do: error
+  defp inserted({:error, :lock_timeout} = error, _bloom), do: error
+  # Appending to the write-ahead log failed.
+  defp inserted({:error, _reason} = error, _bloom), do: error
+
+  # The NIFs hash raw binaries, so any term is reduced to its external format.
+  # `add/2` and `member?/2` do this natively and must produce the same bytes.
+  @doc false
+  def encode_item(item), do: :erlang.term_to_binary(item)
 end
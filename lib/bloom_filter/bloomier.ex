@@ -0,0 +1,94 @@
+defmodule BloomFilterEx.Bloomier do
+  @moduledoc """
+  An immutable approximate map from keys to small integer values, such as
+  shard IDs, in the style of a Bloomier filter.
+
+  The map is built once from the complete set of pairs and never stores the
+  keys. Each key hashes to a linear equation over a table of rows, and
+  building solves the equations so that the one for each key gives its value
+  together with a 16-bit fingerprint of the key, as in
+  `BloomFilterEx.Ribbon`. A lookup evaluates the key's equation and returns
+  the value if the fingerprint matches.
+
+  A key the map was built from always returns its value. Any other key
+  returns `:not_found`, except for about one in 65,536 that match a
+  fingerprint by chance and return an arbitrary value, like a Bloom filter's
+  false positives. The map takes about `1.05 * (16 + b)` bits per key, where
+  `b` is the number of bits in the largest value, so routing a million keys
+  to 1024 shards takes about 3.4 MB instead of a Bloom filter plus an Elixir
+  map of every key.
+
+  ## Examples
+
+      iex> {:ok, shards} = BloomFilterEx.Bloomier.build(%{"alice" => 3, "bob" => 12})
+      iex> BloomFilterEx.Bloomier.get(shards, "bob")
+      12
+      iex> BloomFilterEx.Bloomier.get_many(shards, ["alice", "bob"])
+      [3, 12]
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource]
+
+  @type t :: %__MODULE__{resource: reference()}
+
+  @doc """
+  Builds a map from a map or a list of `{key, value}` pairs.
+
+  Values must be integers from 0 to 65,535. If a key is repeated in a list,
+  the last value wins, as with `Map.new/1`.
+
+  Returns `{:error, :invalid_value}` for a value that doesn't fit in 16 bits.
+  Building can fail for an unlucky choice of hash seed, in which case it is
+  retried with a new seed and a slightly larger table, so
+  `{:error, :construction_failed}` is only returned in the unlikely event that
+  every attempt fails.
+  """
+  @spec build(Enumerable.t({term(), non_neg_integer()})) ::
+          {:ok, t()} | {:error, BloomFilterEx.error()}
+  def build(pairs) do
+    pairs =
+      Enum.map(Map.new(pairs), fn {key, value} when is_integer(value) and value >= 0 ->
+        {BloomFilterEx.encode_item(key), value}
+      end)
+
+    with {:ok, resource} <- Native.map_build(pairs) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+
+  @doc """
+  Returns the value for `key`, or `:not_found` if the key is not in the map.
+
+  Keys that were not in the map occasionally return a value instead; see the
+  module documentation.
+
+  ## Examples
+
+      iex> {:ok, shards} = BloomFilterEx.Bloomier.build([{"alice", 3}])
+      iex> BloomFilterEx.Bloomier.get(shards, "mallory")
+      :not_found
+
+  """
+  @spec get(t(), term()) :: non_neg_integer() | :not_found
+  def get(%__MODULE__{resource: resource}, key) do
+    case Native.map_get(resource, BloomFilterEx.encode_item(key)) do
+      nil -> :not_found
+      value -> value
+    end
+  end
+
+  @doc """
+  Returns the value for each key in a list, in a single native call.
+  """
+  @spec get_many(t(), [term()]) :: [non_neg_integer() | :not_found]
+  def get_many(%__MODULE__{resource: resource}, keys) when is_list(keys) do
+    resource
+    |> Native.map_get_many(Enum.map(keys, &BloomFilterEx.encode_item/1))
+    |> Enum.map(fn
+      nil -> :not_found
+      value -> value
+    end)
+  end
+end
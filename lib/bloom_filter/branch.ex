@@ -0,0 +1,76 @@
+defmodule BloomFilterEx.Branch do
+  @moduledoc """
+  A copy-on-write child of a `BloomFilterEx` filter.
+
+  Branches are created with `BloomFilterEx.branch/1`. A branch answers lookups
+  from its parent's bits until it inserts items of its own, which copy the
+  pages they write to into the branch. The parent is never changed, so a
+  branch suits speculative, what-if inserts against a large base filter, where
+  a `BloomFilterEx.clone/1` per request would copy all of it.
+
+  Pages the branch hasn't copied still read the parent's current bits, so
+  inserts into the parent after branching may show up in the branch. The
+  branch keeps its parent alive for as long as it is held.
+
+  ## Examples
+
+      iex> base = BloomFilterEx.new(100_000, 0.01) |> BloomFilterEx.add("a")
+      iex> branch = base |> BloomFilterEx.branch() |> BloomFilterEx.Branch.add("b")
+      iex> BloomFilterEx.Branch.member?(branch, "b")
+      true
+      iex> BloomFilterEx.member?(base, "b")
+      false
+      iex> BloomFilterEx.Branch.copied_bytes(branch) < BloomFilterEx.byte_size(base)
+      true
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :parent]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          parent: BloomFilterEx.t()
+        }
+
+  @doc """
+  Adds an item to the branch, leaving the parent unchanged.
+
+  Returns `{:error, :memory_budget_exceeded}` if copying a page would go over
+  the budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec add(t(), term()) :: t() | {:error, :memory_budget_exceeded}
+  def add(%__MODULE__{resource: resource} = branch, item) do
+    case Native.branch_add(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, _new?} -> branch
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the parent or has been added to the branch.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.branch_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Returns the parent's inserted count when the branch was made, plus the
+  number of new items added to the branch since.
+  """
+  @spec inserted_count(t()) :: non_neg_integer()
+  def inserted_count(%__MODULE__{resource: resource}) do
+    Native.branch_inserted_count(resource)
+  end
+
+  @doc """
+  Returns the number of bytes of the parent's bits the branch has copied.
+  """
+  @spec copied_bytes(t()) :: non_neg_integer()
+  def copied_bytes(%__MODULE__{resource: resource}) do
+    {:ok, bytes} = Native.branch_copied_bytes(resource)
+    bytes
+  end
+end
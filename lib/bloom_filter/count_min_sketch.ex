@@ -0,0 +1,90 @@
+defmodule BloomFilterEx.CountMinSketch do
+  @moduledoc """
+  A count-min sketch for approximate frequency counting.
+
+  A count-min sketch keeps `depth` rows of `width` counters. Incrementing an
+  item bumps one counter per row, and its estimated count is the smallest of
+  those counters. Estimates are never lower than the true count, and are
+  higher by at most `e / width * total` with probability `1 - e^-depth`.
+
+  Sketches with the same dimensions can be merged with `merge/2`, even when
+  they were built on different nodes.
+
+  ## Examples
+
+      iex> sketch = BloomFilterEx.CountMinSketch.new(1000, 5)
+      iex> sketch = BloomFilterEx.CountMinSketch.increment(sketch, "page-a", 3)
+      iex> sketch = BloomFilterEx.CountMinSketch.increment(sketch, "page-a")
+      iex> BloomFilterEx.CountMinSketch.estimate(sketch, "page-a")
+      4
+      iex> BloomFilterEx.CountMinSketch.estimate(sketch, "page-b")
+      0
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :width, :depth]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          width: pos_integer(),
+          depth: pos_integer()
+        }
+
+  @doc """
+  Creates a new count-min sketch with `depth` rows of `width` counters.
+
+  Returns `{:error, :memory_budget_exceeded}` if the counters would go over
+  the budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), pos_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(width, depth)
+      when is_integer(width) and width > 0 and is_integer(depth) and depth > 0 do
+    case Native.cms_new(width, depth) do
+      {:ok, resource} -> %__MODULE__{resource: resource, width: width, depth: depth}
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Increments the count of `item` by `count`.
+  """
+  @spec increment(t(), term(), non_neg_integer()) :: t()
+  def increment(%__MODULE__{resource: resource} = sketch, item, count \\ 1)
+      when is_integer(count) and count >= 0 do
+    {:ok, new_resource} = Native.cms_increment(resource, BloomFilterEx.encode_item(item), count)
+    %{sketch | resource: new_resource}
+  end
+
+  @doc """
+  Returns the estimated count of `item`.
+  """
+  @spec estimate(t(), term()) :: non_neg_integer()
+  def estimate(%__MODULE__{resource: resource}, item) do
+    {:ok, estimate} = Native.cms_estimate(resource, BloomFilterEx.encode_item(item))
+    estimate
+  end
+
+  @doc """
+  Merges two sketches into a new sketch whose counts are the sum of both.
+
+  Returns `{:error, :incompatible_filters}` if the sketches have different
+  dimensions.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.CountMinSketch.new(100, 4) |> BloomFilterEx.CountMinSketch.increment("x", 2)
+      iex> b = BloomFilterEx.CountMinSketch.new(100, 4) |> BloomFilterEx.CountMinSketch.increment("x", 5)
+      iex> {:ok, merged} = BloomFilterEx.CountMinSketch.merge(a, b)
+      iex> BloomFilterEx.CountMinSketch.estimate(merged, "x")
+      7
+
+  """
+  @spec merge(t(), t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def merge(%__MODULE__{resource: a} = sketch, %__MODULE__{resource: b}) do
+    with {:ok, resource} <- Native.cms_merge(a, b) do
+      {:ok, %{sketch | resource: resource}}
+    end
+  end
+end
@@ -0,0 +1,226 @@
+defmodule BloomFilterEx.Counting do
+  @moduledoc """
+  A counting Bloom filter, which supports removing items.
+
+  Each slot holds a small counter instead of a single bit. Adding an item
+  increments its counters and removing it decrements them again, so unlike
+  `BloomFilterEx` items can be deleted.
+
+  Counters saturate at 255 and are never decremented once saturated, so heavy
+  repetition of an item can only cause false positives, never false negatives.
+  Removing an item that was never added may cause false negatives for other
+  items, so only remove items you know were added.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(1000, 0.01)
+      iex> bloom = BloomFilterEx.Counting.add(bloom, "user@example.com")
+      iex> BloomFilterEx.Counting.member?(bloom, "user@example.com")
+      true
+      iex> bloom = BloomFilterEx.Counting.remove(bloom, "user@example.com")
+      iex> BloomFilterEx.Counting.member?(bloom, "user@example.com")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate, :inserted_count]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          inserted_count: non_neg_integer()
+        }
+
+  @doc """
+  Creates a new counting Bloom filter for the expected capacity and desired
+  false positive rate.
+
+  Returns `{:error, :memory_budget_exceeded}` if the counters would go over
+  the budget set with `BloomFilterEx.configure/1`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(1000, 0.01)
+      iex> bloom.inserted_count
+      0
+
+  """
+  @spec new(pos_integer(), float()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(capacity, false_positive_rate)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    case Native.counting_new(capacity, false_positive_rate) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          capacity: capacity,
+          false_positive_rate: false_positive_rate,
+          inserted_count: 0
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Creates a new counting Bloom filter like `new/2`, but with a fixed hasher
+  seed.
+
+  Counting filters created with the same capacity, false positive rate and
+  `seed` map items to the same counters, so they can be combined with
+  `subtract/2`. The seed must be a non-negative integer below `2^128`.
+  """
+  @spec new_with_seed(pos_integer(), float(), non_neg_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new_with_seed(capacity, false_positive_rate, seed)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_integer(seed) and seed >= 0 and
+             seed < 0x1_0000_0000_0000_0000_0000_0000_0000_0000 do
+    case Native.counting_new_with_seed(capacity, false_positive_rate, seed) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          capacity: capacity,
+          false_positive_rate: false_positive_rate,
+          inserted_count: 0
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item to the counting Bloom filter.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(100, 0.01)
+      iex> bloom = BloomFilterEx.Counting.add(bloom, "item")
+      iex> bloom.inserted_count
+      1
+
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{resource: resource} = bloom, item) do
+    {:ok, new_resource} = Native.counting_add(resource, BloomFilterEx.encode_item(item))
+    %{bloom | resource: new_resource, inserted_count: bloom.inserted_count + 1}
+  end
+
+  @doc """
+  Removes an item from the counting Bloom filter.
+
+  Items that are not (probably) present are ignored.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(100, 0.01)
+      iex> bloom = bloom |> BloomFilterEx.Counting.add("a") |> BloomFilterEx.Counting.add("a")
+      iex> bloom = BloomFilterEx.Counting.remove(bloom, "a")
+      iex> BloomFilterEx.Counting.member?(bloom, "a")
+      true
+      iex> bloom.inserted_count
+      1
+
+  """
+  @spec remove(t(), term()) :: t()
+  def remove(%__MODULE__{resource: resource} = bloom, item) do
+    case Native.counting_remove(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, true} -> %{bloom | inserted_count: bloom.inserted_count - 1}
+      {:ok, false} -> bloom
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the counting Bloom filter.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(100, 0.01)
+      iex> bloom = BloomFilterEx.Counting.add(bloom, "exists")
+      iex> BloomFilterEx.Counting.member?(bloom, "exists")
+      true
+      iex> BloomFilterEx.Counting.member?(bloom, "does-not-exist")
+      false
+
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.counting_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Returns the approximate number of times `item` was added, less the number of
+  times it was removed.
+
+  The estimate is the smallest of the item's counters. Other items sharing all
+  of those counters can make it higher than the true count, but never lower,
+  and it stops at 255 once the counters saturate. Items that were never added
+  usually return `0`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Counting.new(100, 0.01)
+      iex> bloom = Enum.reduce(1..3, bloom, fn _, bloom -> BloomFilterEx.Counting.add(bloom, "a") end)
+      iex> BloomFilterEx.Counting.count_estimate(bloom, "a")
+      3
+      iex> BloomFilterEx.Counting.count_estimate(bloom, "b")
+      0
+
+  """
+  @spec count_estimate(t(), term()) :: 0..255
+  def count_estimate(%__MODULE__{resource: resource}, item) do
+    {:ok, count} = Native.counting_count_estimate(resource, BloomFilterEx.encode_item(item))
+    count
+  end
+
+  @doc """
+  Returns the number of bytes of memory held by the filter's counters.
+
+  Each slot holds a one-byte counter, so a counting filter uses eight times
+  the memory of a `BloomFilterEx` with the same parameters.
+  """
+  @spec byte_size(t()) :: non_neg_integer()
+  def byte_size(%__MODULE__{resource: resource}) do
+    {:ok, size} = Native.counting_byte_size(resource)
+    size
+  end
+
+  @doc """
+  Returns a new filter with the counters of `b` subtracted from those of `a`.
+
+  This removes every item counted in `b` from `a` at once, for example to keep
+  a sliding window as a running total filter minus the bucket that just
+  expired, without re-adding the items still in the window. Counters stop at
+  zero, and saturated counters in `a` stay saturated. `b` should only hold
+  items that were also added to `a`, as with `remove/2`.
+
+  Both filters must have been created with `new_with_seed/3` using the same
+  parameters and seed, otherwise `{:error, :incompatible_filters}` is returned.
+
+  ## Examples
+
+      iex> total = BloomFilterEx.Counting.new_with_seed(100, 0.01, 7)
+      iex> expired = BloomFilterEx.Counting.new_with_seed(100, 0.01, 7)
+      iex> total = total |> BloomFilterEx.Counting.add("old") |> BloomFilterEx.Counting.add("new")
+      iex> expired = BloomFilterEx.Counting.add(expired, "old")
+      iex> {:ok, current} = BloomFilterEx.Counting.subtract(total, expired)
+      iex> {BloomFilterEx.Counting.member?(current, "old"), BloomFilterEx.Counting.member?(current, "new")}
+      {false, true}
+
+  """
+  @spec subtract(t(), t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def subtract(%__MODULE__{resource: a} = bloom, %__MODULE__{resource: b} = other) do
+    with {:ok, resource} <- Native.counting_subtract(a, b) do
+      count = max(bloom.inserted_count - other.inserted_count, 0)
+      {:ok, %{bloom | resource: resource, inserted_count: count}}
+    end
+  end
+end
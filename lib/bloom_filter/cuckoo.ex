@@ -0,0 +1,89 @@
+defmodule BloomFilterEx.Cuckoo do
+  @moduledoc """
+  A cuckoo filter, an alternative to Bloom filters that supports deletion.
+
+  A cuckoo filter stores a 16-bit fingerprint of each item in one of two
+  candidate buckets. It supports deleting items and uses less space than a
+  Bloom filter at low false positive rates. The false positive rate is fixed at
+  roughly 0.01% by the fingerprint size.
+
+  Unlike a Bloom filter, a cuckoo filter can become full: once it holds about
+  95% of its capacity, `add/2` may return `{:error, :filter_full}`.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.Cuckoo.new(1000)
+      iex> {:ok, filter} = BloomFilterEx.Cuckoo.add(filter, "user@example.com")
+      iex> BloomFilterEx.Cuckoo.member?(filter, "user@example.com")
+      true
+      iex> {:ok, filter} = BloomFilterEx.Cuckoo.delete(filter, "user@example.com")
+      iex> BloomFilterEx.Cuckoo.member?(filter, "user@example.com")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer()
+        }
+
+  @doc """
+  Creates a new cuckoo filter with room for at least `capacity` items.
+
+  Returns `{:error, :memory_budget_exceeded}` if the buckets would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def new(capacity) when is_integer(capacity) and capacity > 0 do
+    case Native.cuckoo_new(capacity) do
+      {:ok, resource} -> %__MODULE__{resource: resource, capacity: capacity}
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Adds an item to the cuckoo filter.
+
+  Returns `{:error, :filter_full}` if there is no room left for the item.
+  """
+  @spec add(t(), term()) :: {:ok, t()} | {:error, :filter_full}
+  def add(%__MODULE__{resource: resource} = filter, item) do
+    with {:ok, new_resource} <- Native.cuckoo_add(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, %{filter | resource: new_resource}}
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the cuckoo filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.cuckoo_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Deletes an item from the cuckoo filter.
+
+  Returns `{:error, :not_found}` if the item is not in the filter. Only delete
+  items that were added, as deleting a false positive removes another item.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.Cuckoo.new(100)
+      iex> BloomFilterEx.Cuckoo.delete(filter, "missing")
+      {:error, :not_found}
+
+  """
+  @spec delete(t(), term()) :: {:ok, t()} | {:error, :not_found}
+  def delete(%__MODULE__{resource: resource} = filter, item) do
+    case Native.cuckoo_delete(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, true} -> {:ok, filter}
+      {:ok, false} -> {:error, :not_found}
+    end
+  end
+end
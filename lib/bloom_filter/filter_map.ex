@@ -0,0 +1,120 @@
+defmodule BloomFilterEx.FilterMap do
+  @moduledoc """
+  Many small Bloom filters, one per tenant, behind a single native resource.
+
+  Keeping tens of thousands of tiny per-tenant filters as separate
+  `BloomFilterEx` structs means as many resources and handles to track. A
+  filter map holds them all natively, keyed by a tenant binary. A tenant's
+  filter is created by its first insert, sized for the map's `capacity` and
+  `false_positive_rate`, and freed by `drop_tenant/2`.
+
+  Like the other native filters, the map is shared: inserts through any copy
+  of the struct are seen by all of them.
+
+  ## Examples
+
+      iex> map = BloomFilterEx.FilterMap.new(100, 0.01)
+      iex> map = BloomFilterEx.FilterMap.add(map, "tenant-a", "user@example.com")
+      iex> BloomFilterEx.FilterMap.member?(map, "tenant-a", "user@example.com")
+      true
+      iex> BloomFilterEx.FilterMap.member?(map, "tenant-b", "user@example.com")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float()
+        }
+
+  @doc """
+  Creates an empty filter map whose tenants each get a filter for `capacity`
+  items at `false_positive_rate`.
+  """
+  @spec new(pos_integer(), float()) :: t()
+  def new(capacity, false_positive_rate)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    {:ok, resource} = Native.map_new(capacity, false_positive_rate)
+
+    %__MODULE__{
+      resource: resource,
+      capacity: capacity,
+      false_positive_rate: false_positive_rate
+    }
+  end
+
+  @doc """
+  Adds an item to the tenant's filter, creating the filter if needed.
+
+  Returns `{:error, :memory_budget_exceeded}` if creating the filter would go
+  over the budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec add(t(), binary(), term()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def add(%__MODULE__{resource: resource} = map, tenant, item) when is_binary(tenant) do
+    case Native.map_add(resource, tenant, BloomFilterEx.encode_item(item)) do
+      {:ok, _new?} -> map
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the tenant's filter. Tenants with no filter
+  have no items.
+  """
+  @spec member?(t(), binary(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, tenant, item) when is_binary(tenant) do
+    {:ok, result} = Native.map_member(resource, tenant, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Frees the tenant's filter, forgetting its items.
+
+  ## Examples
+
+      iex> map = BloomFilterEx.FilterMap.new(100, 0.01)
+      iex> map = BloomFilterEx.FilterMap.add(map, "tenant-a", "item")
+      iex> map = BloomFilterEx.FilterMap.drop_tenant(map, "tenant-a")
+      iex> BloomFilterEx.FilterMap.member?(map, "tenant-a", "item")
+      false
+
+  """
+  @spec drop_tenant(t(), binary()) :: t()
+  def drop_tenant(%__MODULE__{resource: resource} = map, tenant) when is_binary(tenant) do
+    {:ok, _existed?} = Native.map_drop_tenant(resource, tenant)
+    map
+  end
+
+  @doc """
+  Returns the number of tenants, the number of items inserted across all of
+  them, and the memory their bit arrays take.
+
+  ## Examples
+
+      iex> map = BloomFilterEx.FilterMap.new(100, 0.01)
+      iex> map = BloomFilterEx.FilterMap.add(map, "tenant-a", "x")
+      iex> map = BloomFilterEx.FilterMap.add(map, "tenant-b", "x")
+      iex> stats = BloomFilterEx.FilterMap.stats(map)
+      iex> {stats.tenants, stats.inserted_count}
+      {2, 2}
+
+  """
+  @spec stats(t()) :: %{
+          tenants: non_neg_integer(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          inserted_count: non_neg_integer(),
+          memory_bytes: non_neg_integer()
+        }
+  def stats(%__MODULE__{resource: resource}) do
+    {:ok, stats} = Native.map_stats(resource)
+    stats
+  end
+end
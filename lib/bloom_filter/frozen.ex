@@ -0,0 +1,62 @@
+defmodule BloomFilterEx.Frozen do
+  @moduledoc """
+  An immutable, read-only copy of a `BloomFilterEx` filter.
+
+  Frozen filters are created with `BloomFilterEx.freeze/1`. Their bits can
+  never change, so lookups skip the lock a regular filter takes on every
+  call, which suits filters that are built once (for example a blocklist
+  loaded at boot) and only ever read afterwards.
+
+  There are no functions to add items to a frozen filter, and passing one to
+  `BloomFilterEx.add/2` raises a `FunctionClauseError`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(1000, 0.01) |> BloomFilterEx.add("blocked")
+      iex> frozen = BloomFilterEx.freeze(bloom)
+      iex> BloomFilterEx.Frozen.member?(frozen, "blocked")
+      true
+      iex> BloomFilterEx.Frozen.member?(frozen, "fine")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :size, :hash_count, :capacity, :false_positive_rate, :inserted_count]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          size: pos_integer(),
+          hash_count: pos_integer(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          inserted_count: non_neg_integer()
+        }
+
+  @doc """
+  Checks if an item is possibly in the frozen filter.
+
+  Answers exactly like `BloomFilterEx.member?/2` on the filter it was frozen
+  from, at the time it was frozen.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    Native.frozen_member(resource, item)
+  end
+
+  @doc """
+  Checks a list of items against the frozen filter in a single native call.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.new(100, 0.01) |> BloomFilterEx.add_many(["a", "c"])
+      iex> frozen = BloomFilterEx.freeze(bloom)
+      iex> BloomFilterEx.Frozen.member_many(frozen, ["a", "b", "c"])
+      [true, false, true]
+
+  """
+  @spec member_many(t(), [term()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    Native.frozen_member_many(resource, Enum.map(items, &BloomFilterEx.encode_item/1))
+  end
+end
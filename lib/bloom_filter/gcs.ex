@@ -0,0 +1,96 @@
+defmodule BloomFilterEx.GCS do
+  @moduledoc """
+  An immutable Golomb-coded set, for distributing large static sets over the
+  network.
+
+  A Golomb-coded set is built once from the complete list of items, like the
+  BIP-158 block filters used by Bitcoin light clients. Each item is hashed to
+  a number below `n * 2^P`, where `n` is the number of items and `P` is
+  `ceil(log2(1 / false_positive_rate))`. The hashes are then sorted, and the
+  gaps between them are written with Golomb-Rice coding. That takes about
+  `P + 1.5` bits per item, less than a Bloom filter or `BloomFilterEx.Fuse`
+  needs at the same false positive rate, so `serialize/1` gives a compact
+  binary for clients to download and query with `deserialize/1`.
+
+  The set is not indexed, so each lookup decodes it from the start and takes
+  time linear in its size. `member_many/2` answers a whole batch in one pass.
+  The set can't be built from an existing `BloomFilterEx`, since a Bloom
+  filter doesn't retain its items.
+
+  ## Examples
+
+      iex> {:ok, gcs} = BloomFilterEx.GCS.build(["bad.example.com", "evil.example.com"], 0.001)
+      iex> BloomFilterEx.GCS.member?(gcs, "bad.example.com")
+      true
+      iex> BloomFilterEx.GCS.member_many(gcs, ["evil.example.com", "good.example.com"])
+      [true, false]
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource]
+
+  @type t :: %__MODULE__{resource: reference()}
+
+  @doc """
+  Builds a set containing `items` with at most the given false positive rate.
+  Duplicates are ignored.
+
+  The rate is rounded down to a power of two, and must be at least `2^-32`.
+  """
+  @spec build([term()], float()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def build(items, false_positive_rate) when is_list(items) and is_float(false_positive_rate) do
+    items = Enum.map(items, &BloomFilterEx.encode_item/1)
+
+    with {:ok, resource} <- Native.gcs_build(items, false_positive_rate) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the set.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    Native.gcs_member(resource, BloomFilterEx.encode_item(item))
+  end
+
+  @doc """
+  Checks a list of items, decoding the set only once.
+  """
+  @spec member_many(t(), [term()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    Native.gcs_member_many(resource, Enum.map(items, &BloomFilterEx.encode_item/1))
+  end
+
+  @doc """
+  Serializes the set into a binary.
+
+  The binary holds a version byte, `P`, the item count as a little-endian
+  64-bit integer and the 128-bit hasher seed, followed by the Golomb-Rice
+  coded gaps, most significant bit first.
+
+  ## Examples
+
+      iex> {:ok, gcs} = BloomFilterEx.GCS.build(["a", "b"], 0.01)
+      iex> {:ok, restored} = gcs |> BloomFilterEx.GCS.serialize() |> BloomFilterEx.GCS.deserialize()
+      iex> BloomFilterEx.GCS.member?(restored, "a")
+      true
+
+  """
+  @spec serialize(t()) :: binary()
+  def serialize(%__MODULE__{resource: resource}) do
+    {:ok, binary} = Native.gcs_serialize(resource)
+    binary
+  end
+
+  @doc """
+  Restores a set from a binary produced by `serialize/1`.
+  """
+  @spec deserialize(binary()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def deserialize(binary) when is_binary(binary) do
+    with {:ok, resource} <- Native.gcs_deserialize(binary) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+end
@@ -0,0 +1,99 @@
+defmodule BloomFilterEx.Guava do
+  @moduledoc """
+  A Bloom filter compatible with Google Guava's `BloomFilter`.
+
+  The filter hashes items with murmur3-128 and sets bits exactly like Guava,
+  so a filter written by Guava's `BloomFilter.writeTo/1` can be read with
+  `deserialize/1` and queried here, and `serialize/1` produces a binary that
+  Guava's `BloomFilter.readFrom/2` accepts. Both of Guava's hashing strategies
+  are supported when reading; new filters use `MURMUR128_MITZ_64`, Guava's
+  default.
+
+  Items must be binaries (or iodata) and are hashed as raw bytes, which matches
+  Guava filters built with `Funnels.byteArrayFunnel()` or
+  `Funnels.stringFunnel(StandardCharsets.UTF_8)`. Filters using other funnels
+  hash a different byte encoding and won't find the same items.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.Guava.new(1000, 0.01)
+      iex> filter = BloomFilterEx.Guava.add(filter, "user@example.com")
+      iex> BloomFilterEx.Guava.member?(filter, "user@example.com")
+      true
+      iex> {:ok, restored} = filter |> BloomFilterEx.Guava.serialize() |> BloomFilterEx.Guava.deserialize()
+      iex> BloomFilterEx.Guava.member?(restored, "user@example.com")
+      true
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource]
+
+  @type t :: %__MODULE__{resource: reference()}
+
+  @doc """
+  Creates an empty filter sized the way Guava's `BloomFilter.create/3` would.
+
+  Returns `{:error, :memory_budget_exceeded}` if the bits would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), float()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(capacity, false_positive_rate)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    case Native.guava_new(capacity, false_positive_rate) do
+      {:ok, resource} -> %__MODULE__{resource: resource}
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Adds an item, given as a binary or iodata.
+  """
+  @spec add(t(), iodata()) :: t()
+  def add(%__MODULE__{resource: resource} = filter, item)
+      when is_binary(item) or is_list(item) do
+    {:ok, _changed} = Native.guava_add(resource, item)
+    filter
+  end
+
+  @doc """
+  Checks if an item, given as a binary or iodata, is possibly in the filter.
+  """
+  @spec member?(t(), iodata()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) when is_binary(item) or is_list(item) do
+    {:ok, result} = Native.guava_member(resource, item)
+    result
+  end
+
+  @doc """
+  Serializes the filter in Guava's `BloomFilter.writeTo/1` format.
+  """
+  @spec serialize(t()) :: binary()
+  def serialize(%__MODULE__{resource: resource}) do
+    {:ok, binary} = Native.serialize_guava(resource)
+    binary
+  end
+
+  @doc """
+  Restores a filter from a binary written by Guava's `BloomFilter.writeTo/1`
+  or by `serialize/1`.
+
+  Returns `{:error, :unsupported_version}` for an unknown hashing strategy and
+  `{:error, :invalid_data}` if the binary is otherwise malformed.
+
+  ## Examples
+
+      iex> BloomFilterEx.Guava.deserialize(<<7, 3, 0, 0, 0, 0>>)
+      {:error, :unsupported_version}
+
+  """
+  @spec deserialize(binary()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def deserialize(binary) when is_binary(binary) do
+    with {:ok, resource} <- Native.deserialize_guava(binary) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+end
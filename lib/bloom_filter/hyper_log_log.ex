@@ -0,0 +1,80 @@
+defmodule BloomFilterEx.HyperLogLog do
+  @moduledoc """
+  A HyperLogLog for estimating the number of distinct items seen.
+
+  A HyperLogLog keeps `2^precision` one-byte registers, so a precision of 14
+  uses 16 KiB regardless of how many items are added. The standard error of
+  the estimate is about `1.04 / sqrt(2^precision)`, or 0.8% at precision 14.
+
+  HyperLogLogs with the same precision can be merged with `merge/2`, even
+  when they were built on different nodes.
+
+  ## Examples
+
+      iex> hll = BloomFilterEx.HyperLogLog.new()
+      iex> hll = BloomFilterEx.HyperLogLog.add(hll, "user-1")
+      iex> hll = BloomFilterEx.HyperLogLog.add(hll, "user-2")
+      iex> hll = BloomFilterEx.HyperLogLog.add(hll, "user-1")
+      iex> BloomFilterEx.HyperLogLog.cardinality(hll)
+      2
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :precision]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          precision: 4..18
+        }
+
+  @doc """
+  Creates a new HyperLogLog with `2^precision` registers.
+
+  `precision` must be between 4 and 18.
+  """
+  @spec new(4..18) :: t()
+  def new(precision \\ 14) when is_integer(precision) and precision in 4..18 do
+    {:ok, resource} = Native.hll_new(precision)
+    %__MODULE__{resource: resource, precision: precision}
+  end
+
+  @doc """
+  Adds `item` to the HyperLogLog.
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{resource: resource} = hll, item) do
+    {:ok, new_resource} = Native.hll_add(resource, BloomFilterEx.encode_item(item))
+    %{hll | resource: new_resource}
+  end
+
+  @doc """
+  Returns the estimated number of distinct items added.
+  """
+  @spec cardinality(t()) :: non_neg_integer()
+  def cardinality(%__MODULE__{resource: resource}) do
+    {:ok, cardinality} = Native.hll_cardinality(resource)
+    cardinality
+  end
+
+  @doc """
+  Merges two HyperLogLogs into a new one that estimates the size of the union.
+
+  Returns `{:error, :incompatible_filters}` if the precisions differ.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.HyperLogLog.new(12) |> BloomFilterEx.HyperLogLog.add("x")
+      iex> b = BloomFilterEx.HyperLogLog.new(12) |> BloomFilterEx.HyperLogLog.add("y")
+      iex> {:ok, merged} = BloomFilterEx.HyperLogLog.merge(a, b)
+      iex> BloomFilterEx.HyperLogLog.cardinality(merged)
+      2
+
+  """
+  @spec merge(t(), t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def merge(%__MODULE__{resource: a} = hll, %__MODULE__{resource: b}) do
+    with {:ok, resource} <- Native.hll_merge(a, b) do
+      {:ok, %{hll | resource: resource}}
+    end
+  end
+end
@@ -0,0 +1,141 @@
+defmodule BloomFilterEx.Mmap do
+  @moduledoc """
+  A Bloom filter whose bit array lives in a memory-mapped file.
+
+  Filters are created with `BloomFilterEx.new_mmap/3`. The bits are set
+  directly in the mapping, so the filter is held by the OS page cache rather
+  than allocated next to the VM, and pages that aren't being probed can be
+  evicted. This suits very large filters, tens of gigabytes, that would
+  otherwise need as much resident memory.
+
+  The file also persists the filter: after a restart, `open/1` maps it again
+  instantly, without the read and decode step of `BloomFilterEx.load/1`.
+  Changes reach the file whenever the OS writes dirty pages back, and `flush/1`
+  forces them to disk.
+
+  `open_shared_readonly/1` maps a file for lookups only. Every VM on the host
+  that maps the same file shares one copy of it in the page cache, instead of
+  each holding its own copy of the filter in memory.
+
+  Items are hashed exactly as `BloomFilterEx` hashes them, but there is no
+  struct field tracking the number of inserted items, since it lives in the
+  file. Use `inserted_count/1` instead.
+
+  A memory-mapped filter can have more than 2^32 bits. Those files are written
+  as version 2 of the file format, which maps each hash onto the bit array
+  with all 64 of its bits, since the scheme of smaller filters only reaches
+  their first 2^32 bits.
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :size, :hash_count, :capacity, :false_positive_rate]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          size: pos_integer(),
+          hash_count: pos_integer(),
+          capacity: pos_integer(),
+          false_positive_rate: float()
+        }
+
+  @doc """
+  Maps an existing filter file created by `BloomFilterEx.new_mmap/3`.
+
+  Returns `{:error, reason}` if the file cannot be opened, using the same atoms
+  as `File.open/2`, `{:error, :invalid_data}` if it is not a filter file and
+  `{:error, :corrupt_data}` if it has been truncated.
+  """
+  @spec open(Path.t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def open(path) do
+    with {:ok, resource} <- Native.open_mmap(IO.chardata_to_string(path)) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Maps an existing filter file for lookups only.
+
+  The file is opened without write access, so it can be a read-only file
+  shared between several VMs on the same host, each of which maps the same
+  pages. Bits set by a process that opened the file with `open/1` become
+  visible to every reader straight away.
+
+  Adding to a filter opened this way returns `{:error, :read_only}`. Returns
+  the same errors as `open/1` if the file cannot be mapped.
+  """
+  @spec open_shared_readonly(Path.t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def open_shared_readonly(path) do
+    with {:ok, resource} <- Native.open_shared_readonly(IO.chardata_to_string(path)) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  @doc """
+  Adds an item to the filter.
+
+  Returns `{:error, :read_only}` if the filter was opened with
+  `open_shared_readonly/1`.
+  """
+  @spec add(t(), term()) :: t() | {:error, :read_only}
+  def add(%__MODULE__{resource: resource} = filter, item) do
+    with {:ok, _new} <- Native.mmap_add(resource, item), do: filter
+  end
+
+  @doc """
+  Adds a list of items to the filter in a single native call.
+
+  Returns `{:error, :read_only}` if the filter was opened with
+  `open_shared_readonly/1`.
+  """
+  @spec add_many(t(), [term()]) :: t() | {:error, :read_only}
+  def add_many(%__MODULE__{resource: resource} = filter, items) when is_list(items) do
+    items = Enum.map(items, &BloomFilterEx.encode_item/1)
+    with {:ok, _added} <- Native.mmap_add_many(resource, items), do: filter
+  end
+
+  @doc """
+  Checks if an item is possibly in the filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    Native.mmap_member(resource, item)
+  end
+
+  @doc """
+  Checks a list of items against the filter in a single native call.
+  """
+  @spec member_many(t(), [term()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    Native.mmap_member_many(resource, Enum.map(items, &BloomFilterEx.encode_item/1))
+  end
+
+  @doc """
+  Returns the number of distinct items inserted, as stored in the file.
+  """
+  @spec inserted_count(t()) :: non_neg_integer()
+  def inserted_count(%__MODULE__{resource: resource}) do
+    Native.mmap_stats(resource).inserted_count
+  end
+
+  @doc """
+  Blocks until every change made so far has been written to the file.
+  """
+  @spec flush(t()) :: :ok | {:error, BloomFilterEx.error()}
+  def flush(%__MODULE__{resource: resource}) do
+    with {:ok, {}} <- Native.mmap_flush(resource), do: :ok
+  end
+
+  @doc false
+  def from_resource(resource) do
+    stats = Native.mmap_stats(resource)
+
+    %__MODULE__{
+      resource: resource,
+      size: stats.num_bits,
+      hash_count: stats.num_hashes,
+      capacity: stats.capacity,
+      false_positive_rate: stats.false_positive_rate
+    }
+  end
+end
@@ -19,9 +19,244 @@ defmodule BloomFilterEx.Native do
     ],
     nif_versions: ["2.17", "2.16"]
 
-  def new(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def configure(_opts), do: :erlang.nif_error(:nif_not_loaded)
+  def new(_capacity, _false_positive_rate, _strict), do: :erlang.nif_error(:nif_not_loaded)
+
+  def new_with_seed(_capacity, _false_positive_rate, _seed),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def new_with_bits(_num_bits, _num_hashes), do: :erlang.nif_error(:nif_not_loaded)
+  def new_with_opts(_opts), do: :erlang.nif_error(:nif_not_loaded)
+  def optimal_params(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
   def add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def add_term(_resource, _term), do: :erlang.nif_error(:nif_not_loaded)
+  def add_term_timeout(_resource, _term, _timeout), do: :erlang.nif_error(:nif_not_loaded)
+  def add_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def add_many_report(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def add_many_parallel(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def add_many_timeout(_resource, _items, _timeout), do: :erlang.nif_error(:nif_not_loaded)
+
+  def rebuild(_resource, _capacity, _false_positive_rate, _items),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def add_many_async(_resource, _items, _pid, _ref), do: :erlang.nif_error(:nif_not_loaded)
+  def add_if_absent(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
   def member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def member_term(_resource, _term), do: :erlang.nif_error(:nif_not_loaded)
+  def add_int(_resource, _int), do: :erlang.nif_error(:nif_not_loaded)
+  def member_int(_resource, _int), do: :erlang.nif_error(:nif_not_loaded)
+  def add_hashed(_resource, _hash1, _hash2), do: :erlang.nif_error(:nif_not_loaded)
+  def member_hashed(_resource, _hash1, _hash2), do: :erlang.nif_error(:nif_not_loaded)
+  def prepare(_item), do: :erlang.nif_error(:nif_not_loaded)
+  def add_prepared(_resource, _prepared), do: :erlang.nif_error(:nif_not_loaded)
+  def member_prepared(_resource, _prepared), do: :erlang.nif_error(:nif_not_loaded)
+  def hash_indexes(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def member_many_bitmask(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def member_count(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def contains_any(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def contains_all(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
   def clear(_resource), do: :erlang.nif_error(:nif_not_loaded)
   def stats(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def over_capacity(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def healthy(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def counters(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def reset_counters(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def estimated_cardinality(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def estimate_overlap(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def byte_size(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def is_empty(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def bits_set(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def fill_ratio(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def estimated_false_positive_rate(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def serialize(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def serialize_compressed(_resource, _level), do: :erlang.nif_error(:nif_not_loaded)
+  def serialize_stream(_resource, _chunk_size), do: :erlang.nif_error(:nif_not_loaded)
+  def next_chunk(_stream), do: :erlang.nif_error(:nif_not_loaded)
+  def deserialize(_binary), do: :erlang.nif_error(:nif_not_loaded)
+  def serialize_base64(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def deserialize_base64(_binary), do: :erlang.nif_error(:nif_not_loaded)
+  def to_bits(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def from_bits(_bits, _num_hashes, _seed), do: :erlang.nif_error(:nif_not_loaded)
+  def save(_resource, _path), do: :erlang.nif_error(:nif_not_loaded)
+  def load(_path), do: :erlang.nif_error(:nif_not_loaded)
+  def enable_autosave(_resource, _path, _interval_ms), do: :erlang.nif_error(:nif_not_loaded)
+  def disable_autosave(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def enable_wal(_resource, _path), do: :erlang.nif_error(:nif_not_loaded)
+  def disable_wal(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def replay_wal(_resource, _path), do: :erlang.nif_error(:nif_not_loaded)
+
+  def new_from_file(_path, _capacity, _false_positive_rate, _delimiter, _parallel),
+    do: :erlang.nif_error(:nif_not_loaded)
+  def clone(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def union(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def union_many(_resources), do: :erlang.nif_error(:nif_not_loaded)
+  def intersection(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def equal(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def merge_serialized(_resource, _binary), do: :erlang.nif_error(:nif_not_loaded)
+  def replace(_resource, _source), do: :erlang.nif_error(:nif_not_loaded)
+  def replace_serialized(_resource, _binary), do: :erlang.nif_error(:nif_not_loaded)
+  def similarity(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def compatible(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_version(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def diff_since(_resource, _version), do: :erlang.nif_error(:nif_not_loaded)
+  def apply_diff(_resource, _diff), do: :erlang.nif_error(:nif_not_loaded)
+
+  def register(_name, _resource), do: :erlang.nif_error(:nif_not_loaded)
+  def whereis(_name), do: :erlang.nif_error(:nif_not_loaded)
+  def unregister(_name), do: :erlang.nif_error(:nif_not_loaded)
+  def list_registered(), do: :erlang.nif_error(:nif_not_loaded)
+  def notify_on_release(_resource, _pid, _tag), do: :erlang.nif_error(:nif_not_loaded)
+  def set_fill_alarm(_resource, _threshold, _pid), do: :erlang.nif_error(:nif_not_loaded)
+
+  def counting_new(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_new_with_seed(_capacity, _false_positive_rate, _seed),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def counting_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_remove(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_count_estimate(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_byte_size(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def counting_subtract(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+  def scalable_new(_initial_capacity, _false_positive_rate, _growth_factor),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def scalable_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def scalable_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def scalable_num_layers(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def scalable_reserve(_resource, _additional), do: :erlang.nif_error(:nif_not_loaded)
+
+  def rotating_new(_capacity, _false_positive_rate, _generations, _interval_ms),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def rotating_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def rotating_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def rotating_rotate(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def cuckoo_new(_capacity), do: :erlang.nif_error(:nif_not_loaded)
+  def cuckoo_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def cuckoo_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def cuckoo_delete(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+
+  def fuse_build(_items), do: :erlang.nif_error(:nif_not_loaded)
+  def fuse_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def fuse_serialize(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def fuse_deserialize(_binary), do: :erlang.nif_error(:nif_not_loaded)
+
+  def gcs_build(_items, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def gcs_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def gcs_member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def gcs_serialize(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def gcs_deserialize(_binary), do: :erlang.nif_error(:nif_not_loaded)
+
+  def ribbon_build(_items, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def ribbon_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def ribbon_member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def ribbon_serialize(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def ribbon_deserialize(_binary), do: :erlang.nif_error(:nif_not_loaded)
+
+  def map_build(_pairs), do: :erlang.nif_error(:nif_not_loaded)
+  def map_get(_resource, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def map_get_many(_resource, _keys), do: :erlang.nif_error(:nif_not_loaded)
+
+  def cms_new(_width, _depth), do: :erlang.nif_error(:nif_not_loaded)
+  def cms_increment(_resource, _item, _count), do: :erlang.nif_error(:nif_not_loaded)
+  def cms_estimate(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def cms_merge(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+  def hll_new(_precision), do: :erlang.nif_error(:nif_not_loaded)
+  def hll_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def hll_cardinality(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def hll_merge(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+  def partitioned_new(_capacity, _false_positive_rate, _seed),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def partitioned_layout(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def partitioned_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def partitioned_add_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def partitioned_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def partitioned_member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def partitioned_to_bits(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def partitioned_from_bits(_bits, _num_hashes, _seed),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def qf_new(_capacity, _false_positive_rate, _seed), do: :erlang.nif_error(:nif_not_loaded)
+  def qf_layout(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def qf_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def qf_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def qf_delete(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def qf_merge(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+  def ts_new(_capacity, _false_positive_rate, _bucket_size, _retention),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def ts_add(_resource, _item, _timestamp), do: :erlang.nif_error(:nif_not_loaded)
+  def ts_member_in_range(_resource, _item, _from, _to), do: :erlang.nif_error(:nif_not_loaded)
+  def ts_buckets(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def ttl_new(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def ttl_add(_resource, _item, _ttl_buckets), do: :erlang.nif_error(:nif_not_loaded)
+  def ttl_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def ttl_advance(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def stable_new(_num_cells, _cell_bits, _fp_target), do: :erlang.nif_error(:nif_not_loaded)
+  def stable_layout(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def stable_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def stable_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+
+  def topk_new(_k, _width, _depth), do: :erlang.nif_error(:nif_not_loaded)
+  def topk_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def topk_list(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def sharded_new(_capacity, _false_positive_rate, _shards),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def sharded_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def sharded_add_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def sharded_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def sharded_inserted_count(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def freeze(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def frozen_member(_resource, _term), do: :erlang.nif_error(:nif_not_loaded)
+  def frozen_member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+
+  def branch(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def branch_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def branch_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def branch_inserted_count(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def branch_copied_bytes(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def guava_new(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def guava_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def guava_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def serialize_guava(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def deserialize_guava(_binary), do: :erlang.nif_error(:nif_not_loaded)
+
+  def redis_new(_capacity, _false_positive_rate, _expansion, _scaling),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def redis_add(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def redis_member(_resource, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def redis_scandump(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def redis_loadchunks(_chunks), do: :erlang.nif_error(:nif_not_loaded)
+
+  def new_mmap(_path, _capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def open_mmap(_path), do: :erlang.nif_error(:nif_not_loaded)
+  def open_shared_readonly(_path), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_add(_resource, _term), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_add_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_member(_resource, _term), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_member_many(_resource, _items), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_flush(_resource), do: :erlang.nif_error(:nif_not_loaded)
+  def mmap_stats(_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  def map_new(_capacity, _false_positive_rate), do: :erlang.nif_error(:nif_not_loaded)
+  def map_add(_resource, _tenant, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def map_member(_resource, _tenant, _item), do: :erlang.nif_error(:nif_not_loaded)
+  def map_drop_tenant(_resource, _tenant), do: :erlang.nif_error(:nif_not_loaded)
+  def map_stats(_resource), do: :erlang.nif_error(:nif_not_loaded)
 end
@@ -0,0 +1,201 @@
+defmodule BloomFilterEx.Partitioned do
+  @moduledoc """
+  A partitioned Bloom filter, where each hash function owns its own slice of
+  the bit array.
+
+  The bit array is split into `num_hashes` slices of `slice_bits` bits each,
+  and hash function `i` only sets bits in slice `i`. Every item therefore sets
+  exactly one bit per slice, which is the layout assumed by Almeida et al.'s
+  scalable Bloom filters and the implementations that follow that paper.
+
+  Sizing follows the paper too: `num_hashes` is `ceil(log2(1 / p))` and the
+  `-n ln(p) / ln(2)^2` bits are divided evenly between the slices, each
+  rounded up to whole 64-bit words.
+
+  Items must be binaries (or iodata) and are hashed as raw bytes, so that other
+  implementations can set the same bits and exchange filters through
+  `to_bits/1` and `from_bits/3`:
+
+    * An item is hashed with 128-bit murmur3 (`MurmurHash3_x64_128`) and the
+      filter's `:seed`, 0 by default. `h1` is the low 64 bits of the hash and
+      `h2` the high 64 bits, as `mmh3.hash64(item, seed, signed=False)` returns
+      them.
+    * Within slice `i`, counting from 0, the item sets bit
+      `rem(h1 + i * h2, slice_bits)`, with the sum taken modulo 2^64.
+    * Bit `j` of slice `i` is bit `i * slice_bits + j` of the array, and bit
+      `b` of the array is bit `rem(b, 8)` (least significant first) of byte
+      `div(b, 8)`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Partitioned.new(1000, 0.01)
+      iex> bloom = BloomFilterEx.Partitioned.add(bloom, "user@example.com")
+      iex> BloomFilterEx.Partitioned.member?(bloom, "user@example.com")
+      true
+      iex> BloomFilterEx.Partitioned.member?(bloom, "other@example.com")
+      false
+      iex> {bloom.num_hashes, bloom.slice_bits}
+      {7, 1408}
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [
+    :resource,
+    :capacity,
+    :false_positive_rate,
+    :num_hashes,
+    :slice_bits,
+    :inserted_count
+  ]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          num_hashes: pos_integer(),
+          slice_bits: pos_integer(),
+          inserted_count: non_neg_integer()
+        }
+
+  @doc """
+  Creates a new partitioned Bloom filter for the expected capacity and desired
+  false positive rate.
+
+  ## Options
+
+    * `:seed` - the murmur3 seed, a non-negative integer below `2^32`. Filters
+      with the same parameters and seed set the same bits for the same items.
+      Defaults to 0.
+
+  Returns `{:error, :memory_budget_exceeded}` if the bits would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), float(), keyword()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(opts) do
+    with {:ok, resource} <- Native.partitioned_new(capacity, false_positive_rate, seed(opts)) do
+      {:ok, layout} = Native.partitioned_layout(resource)
+
+      %__MODULE__{
+        resource: resource,
+        capacity: capacity,
+        false_positive_rate: false_positive_rate,
+        num_hashes: layout.num_hashes,
+        slice_bits: layout.slice_bits,
+        inserted_count: 0
+      }
+    end
+  end
+
+  @doc """
+  Adds an item, given as a binary or iodata, to the filter.
+  """
+  @spec add(t(), iodata()) :: t()
+  def add(%__MODULE__{resource: resource} = bloom, item) when is_binary(item) or is_list(item) do
+    case Native.partitioned_add(resource, item) do
+      {:ok, true} -> %{bloom | inserted_count: bloom.inserted_count + 1}
+      {:ok, false} -> bloom
+    end
+  end
+
+  @doc """
+  Adds a list of items, each a binary or iodata, in a single native call.
+  """
+  @spec add_many(t(), [iodata()]) :: t()
+  def add_many(%__MODULE__{resource: resource} = bloom, items) when is_list(items) do
+    {:ok, added} = Native.partitioned_add_many(resource, items)
+    %{bloom | inserted_count: bloom.inserted_count + added}
+  end
+
+  @doc """
+  Checks if an item, given as a binary or iodata, is possibly in the filter.
+  """
+  @spec member?(t(), iodata()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) when is_binary(item) or is_list(item) do
+    {:ok, result} = Native.partitioned_member(resource, item)
+    result
+  end
+
+  @doc """
+  Checks a list of items, each a binary or iodata, in a single native call.
+  """
+  @spec member_many(t(), [iodata()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    {:ok, results} = Native.partitioned_member_many(resource, items)
+    results
+  end
+
+  @doc """
+  Returns the filter's raw bit array, slice by slice, in the layout described
+  in the module documentation.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Partitioned.new(1000, 0.01)
+      iex> byte_size(BloomFilterEx.Partitioned.to_bits(bloom)) * 8 == bloom.num_hashes * bloom.slice_bits
+      true
+
+  """
+  @spec to_bits(t()) :: binary()
+  def to_bits(%__MODULE__{resource: resource}) do
+    {:ok, bits} = Native.partitioned_to_bits(resource)
+    bits
+  end
+
+  @doc """
+  Rebuilds a filter from a raw bit array of `num_hashes` equal slices, in the
+  layout of `to_bits/1`, such as one written by another implementation.
+
+  `slice_bits` is the size of the array divided by `num_hashes`, which must
+  divide it exactly, otherwise `{:error, :invalid_num_bits}` is returned. The
+  `:seed` option is as for `new/3`. The capacity and false positive rate are
+  those the slices would be sized for, and `inserted_count` is estimated from
+  the bits set.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Partitioned.new(1000, 0.01, seed: 7)
+      iex> bloom = BloomFilterEx.Partitioned.add(bloom, "a")
+      iex> bits = BloomFilterEx.Partitioned.to_bits(bloom)
+      iex> {:ok, rebuilt} = BloomFilterEx.Partitioned.from_bits(bits, bloom.num_hashes, seed: 7)
+      iex> {BloomFilterEx.Partitioned.member?(rebuilt, "a"), rebuilt.inserted_count}
+      {true, 1}
+
+      iex> BloomFilterEx.Partitioned.from_bits(<<0, 0, 0>>, 2)
+      {:error, :invalid_num_bits}
+
+  """
+  @spec from_bits(binary(), pos_integer(), keyword()) ::
+          {:ok, t()} | {:error, BloomFilterEx.error()}
+  def from_bits(bits, num_hashes, opts \\ [])
+      when is_binary(bits) and is_integer(num_hashes) and num_hashes > 0 and is_list(opts) do
+    with {:ok, {resource, inserted_count}} <-
+           Native.partitioned_from_bits(bits, num_hashes, seed(opts)) do
+      slice_bits = div(byte_size(bits) * 8, num_hashes)
+
+      {:ok,
+       %__MODULE__{
+         resource: resource,
+         capacity: max(round(slice_bits * :math.log(2)), 1),
+         false_positive_rate: :math.pow(2, -num_hashes),
+         num_hashes: num_hashes,
+         slice_bits: slice_bits,
+         inserted_count: inserted_count
+       }}
+    end
+  end
+
+  defp seed(opts) do
+    case Keyword.get(opts, :seed, 0) do
+      seed when is_integer(seed) and seed >= 0 and seed < 0x1_0000_0000 ->
+        seed
+
+      seed ->
+        raise ArgumentError, "expected :seed to be an integer below 2^32, got: #{inspect(seed)}"
+    end
+  end
+end
@@ -0,0 +1,148 @@
+defmodule BloomFilterEx.Quotient do
+  @moduledoc """
+  A quotient filter, an alternative to Bloom filters that supports deletion
+  and can be resized by merging.
+
+  Each item gets a fingerprint of `quotient_bits + remainder_bits` bits from
+  its hash. The top `quotient_bits` pick one of `2^quotient_bits` slots, and
+  the remaining bits are stored in that slot or, if it is taken, in the next
+  free slot after it. A lookup only scans a short run of adjacent slots, so it
+  touches far fewer cache lines than the scattered counters of a counting
+  Bloom filter.
+
+  Because the fingerprints are stored exactly, they can be deleted and copied
+  into a bigger table: `merge/2` builds a filter holding the items of two
+  filters, sized for both. Like a cuckoo filter, a quotient filter can become
+  full, once 95% of its slots are used.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.Quotient.new(1000, 0.01)
+      iex> {filter.quotient_bits, filter.remainder_bits}
+      {11, 6}
+      iex> {:ok, filter} = BloomFilterEx.Quotient.add(filter, "user@example.com")
+      iex> BloomFilterEx.Quotient.member?(filter, "user@example.com")
+      true
+      iex> {:ok, filter} = BloomFilterEx.Quotient.delete(filter, "user@example.com")
+      iex> BloomFilterEx.Quotient.member?(filter, "user@example.com")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :quotient_bits, :remainder_bits, :count]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          quotient_bits: pos_integer(),
+          remainder_bits: pos_integer(),
+          count: non_neg_integer()
+        }
+
+  @doc """
+  Creates a new quotient filter with room for at least `capacity` items at the
+  given false positive rate.
+
+  ## Options
+
+    * `:seed` - the hasher seed, a non-negative integer below `2^128`. Only
+      filters with the same seed can be merged. Defaults to a random seed.
+
+  Returns `{:error, :memory_budget_exceeded}` if the table would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), float(), keyword()) :: t() | {:error, BloomFilterEx.error()}
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(opts) do
+    with {:ok, resource} <-
+           Native.qf_new(capacity, false_positive_rate, Keyword.get(opts, :seed)) do
+      from_resource(resource)
+    end
+  end
+
+  @doc """
+  Adds an item to the filter.
+
+  Returns `{:error, :filter_full}` if there is no room left for the item.
+  """
+  @spec add(t(), term()) :: {:ok, t()} | {:error, :filter_full}
+  def add(%__MODULE__{resource: resource} = filter, item) do
+    case Native.qf_add(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, true} -> {:ok, %{filter | count: filter.count + 1}}
+      {:ok, false} -> {:ok, filter}
+      {:error, reason} -> {:error, reason}
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.qf_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Deletes an item from the filter.
+
+  Returns `{:error, :not_found}` if the item is not in the filter. Items with
+  the same fingerprint are only stored once, so only delete items that were
+  added, as deleting a false positive removes another item.
+  """
+  @spec delete(t(), term()) :: {:ok, t()} | {:error, :not_found}
+  def delete(%__MODULE__{resource: resource} = filter, item) do
+    case Native.qf_delete(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, true} -> {:ok, %{filter | count: filter.count - 1}}
+      {:ok, false} -> {:error, :not_found}
+    end
+  end
+
+  @doc """
+  Returns a new filter holding the items of both filters, which must have been
+  created with the same `:seed`.
+
+  The new filter has twice as many slots as the larger of the two, so merging
+  a full filter with an empty one created with the same seed resizes it. Each
+  doubling takes a bit from the remainders, which keeps the fingerprints the
+  same size, so the false positive rate stays the same for the same number of
+  items. Once there are no remainder bits left to take, `{:error, :filter_full}`
+  is returned.
+
+  ## Examples
+
+      iex> a = BloomFilterEx.Quotient.new(2, 0.001, seed: 7)
+      iex> {:ok, a} = BloomFilterEx.Quotient.add(a, "a")
+      iex> {:ok, a} = BloomFilterEx.Quotient.add(a, "b")
+      iex> {:ok, a} = BloomFilterEx.Quotient.add(a, "c")
+      iex> BloomFilterEx.Quotient.add(a, "d")
+      {:error, :filter_full}
+      iex> empty = BloomFilterEx.Quotient.new(2, 0.001, seed: 7)
+      iex> {:ok, merged} = BloomFilterEx.Quotient.merge(a, empty)
+      iex> {merged.quotient_bits, merged.count}
+      {3, 3}
+      iex> {:ok, merged} = BloomFilterEx.Quotient.add(merged, "d")
+      iex> Enum.map(["a", "b", "c", "d"], &BloomFilterEx.Quotient.member?(merged, &1))
+      [true, true, true, true]
+
+  """
+  @spec merge(t(), t()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def merge(%__MODULE__{resource: a}, %__MODULE__{resource: b}) do
+    with {:ok, resource} <- Native.qf_merge(a, b) do
+      {:ok, from_resource(resource)}
+    end
+  end
+
+  defp from_resource(resource) do
+    {:ok, layout} = Native.qf_layout(resource)
+
+    %__MODULE__{
+      resource: resource,
+      quotient_bits: layout.quotient_bits,
+      remainder_bits: layout.remainder_bits,
+      count: layout.len
+    }
+  end
+end
@@ -0,0 +1,133 @@
+defmodule BloomFilterEx.RedisBloom do
+  @moduledoc """
+  A Bloom filter compatible with RedisBloom's `BF.*` filters.
+
+  The filter hashes items with MurmurHash64A and sets bits exactly like
+  RedisBloom, including its chain of filters that scales as items are added,
+  so filters can be moved between Elixir and Redis without rebuilding them
+  from the source data. `scandump/1` returns the chunks `BF.SCANDUMP` would,
+  ready to pass to `BF.LOADCHUNK`, and `load_chunks/1` rebuilds a filter from
+  the chunks `BF.SCANDUMP` returns. Chains created by older RedisBloom
+  releases, which hash with 32-bit MurmurHash2, can be loaded too.
+
+  Items must be binaries (or iodata) and are hashed as raw bytes, the way
+  Redis receives them, so `"42"` here is the `42` of `BF.ADD key 42`.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.RedisBloom.new(1000, 0.01)
+      iex> filter = BloomFilterEx.RedisBloom.add(filter, "user@example.com")
+      iex> BloomFilterEx.RedisBloom.member?(filter, "user@example.com")
+      true
+      iex> {:ok, restored} = filter |> BloomFilterEx.RedisBloom.scandump() |> BloomFilterEx.RedisBloom.load_chunks()
+      iex> BloomFilterEx.RedisBloom.member?(restored, "user@example.com")
+      true
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource]
+
+  @type t :: %__MODULE__{resource: reference()}
+
+  @typedoc "An `{iterator, data}` pair, as `BF.SCANDUMP` returns and `BF.LOADCHUNK` takes it."
+  @type chunk :: {non_neg_integer(), binary()}
+
+  @doc """
+  Creates an empty filter the way `BF.RESERVE` would.
+
+  ## Options
+
+  - `:expansion` - how many times larger each new filter in the chain is than
+    the one before, as `BF.RESERVE`'s `EXPANSION` (default 2)
+  - `:scaling` - whether to grow the chain once the first filter holds
+    `capacity` items. With `false`, as `BF.RESERVE`'s `NONSCALING`, `add/2`
+    returns `{:error, :filter_full}` instead (default `true`)
+
+  Returns `{:error, :memory_budget_exceeded}` if the bits would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), float(), keyword()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(opts) do
+    expansion = Keyword.get(opts, :expansion, 2)
+    scaling = Keyword.get(opts, :scaling, true)
+
+    case Native.redis_new(capacity, false_positive_rate, expansion, scaling) do
+      {:ok, resource} -> %__MODULE__{resource: resource}
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Adds an item, given as a binary or iodata, like `BF.ADD`.
+
+  Returns `{:error, :filter_full}` if the filter doesn't scale and is full,
+  or `{:error, :memory_budget_exceeded}` if growing the chain would go over
+  the budget.
+  """
+  @spec add(t(), iodata()) ::
+          t() | {:error, :filter_full | :memory_budget_exceeded | :allocation_failed}
+  def add(%__MODULE__{resource: resource} = filter, item)
+      when is_binary(item) or is_list(item) do
+    case Native.redis_add(resource, item) do
+      {:ok, _new?} -> filter
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Checks if an item, given as a binary or iodata, is possibly in the filter,
+  like `BF.EXISTS`.
+  """
+  @spec member?(t(), iodata()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) when is_binary(item) or is_list(item) do
+    {:ok, result} = Native.redis_member(resource, item)
+    result
+  end
+
+  @doc """
+  Returns the chunks `BF.SCANDUMP` would return for the filter, in order and
+  without the final `{0, ""}`.
+
+  Loading them into Redis with `BF.LOADCHUNK key iterator data`, one chunk at
+  a time, recreates the filter there.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.RedisBloom.new(100, 0.01)
+      iex> [{1, _header}, {iterator, bits}] = BloomFilterEx.RedisBloom.scandump(filter)
+      iex> iterator == 1 + byte_size(bits)
+      true
+
+  """
+  @spec scandump(t()) :: [chunk()]
+  def scandump(%__MODULE__{resource: resource}) do
+    {:ok, chunks} = Native.redis_scandump(resource)
+    chunks
+  end
+
+  @doc """
+  Rebuilds a filter from the chunks returned by successive `BF.SCANDUMP`
+  calls, or by `scandump/1`.
+
+  The header chunk, at iterator 1, must come first. A final `{0, ""}` is
+  ignored. Returns `{:error, :invalid_data}` if a chunk is malformed or lies
+  outside the filter the header describes.
+
+  ## Examples
+
+      iex> BloomFilterEx.RedisBloom.load_chunks([{1, <<0, 0, 0>>}])
+      {:error, :invalid_data}
+
+  """
+  @spec load_chunks([chunk()]) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def load_chunks(chunks) when is_list(chunks) do
+    with {:ok, resource} <- Native.redis_loadchunks(chunks) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+end
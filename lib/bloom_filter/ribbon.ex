@@ -0,0 +1,98 @@
+defmodule BloomFilterEx.Ribbon do
+  @moduledoc """
+  An immutable ribbon filter, for large static sets such as daily blocklists.
+
+  A ribbon filter is built once from the complete list of items. Each item
+  hashes to a linear equation over a table of `r`-bit rows, where `r` is
+  `ceil(log2(1 / false_positive_rate))`, and building solves all of the
+  equations together. A lookup computes one equation and checks that it
+  holds, which it does for an absent item with probability `2^-r`.
+
+  The table takes about `1.05 * r` bits per item, against the `1.44 * r` a
+  Bloom filter needs for the same false positive rate, so a ribbon filter is
+  about 27% smaller. Unlike `BloomFilterEx.GCS`, lookups read a fixed number
+  of words instead of decoding the whole set. Like a GCS, it can't be built
+  from an existing `BloomFilterEx`, since a Bloom filter doesn't retain its
+  items.
+
+  ## Examples
+
+      iex> {:ok, ribbon} = BloomFilterEx.Ribbon.build(["bad.example.com", "evil.example.com"], 0.001)
+      iex> BloomFilterEx.Ribbon.member?(ribbon, "bad.example.com")
+      true
+      iex> BloomFilterEx.Ribbon.member_many(ribbon, ["evil.example.com", "good.example.com"])
+      [true, false]
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource]
+
+  @type t :: %__MODULE__{resource: reference()}
+
+  @doc """
+  Builds a filter containing `items` with at most the given false positive
+  rate. Duplicates are ignored.
+
+  The rate is rounded down to a power of two, and must be at least `2^-32`.
+  Building can fail for an unlucky choice of hash seed, in which case it is
+  retried with a new seed and a slightly larger table, so
+  `{:error, :construction_failed}` is only returned in the unlikely event that
+  every attempt fails.
+  """
+  @spec build([term()], float()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def build(items, false_positive_rate) when is_list(items) and is_float(false_positive_rate) do
+    items = Enum.map(items, &BloomFilterEx.encode_item/1)
+
+    with {:ok, resource} <- Native.ribbon_build(items, false_positive_rate) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in the filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    Native.ribbon_member(resource, BloomFilterEx.encode_item(item))
+  end
+
+  @doc """
+  Checks a list of items in a single native call.
+  """
+  @spec member_many(t(), [term()]) :: [boolean()]
+  def member_many(%__MODULE__{resource: resource}, items) when is_list(items) do
+    Native.ribbon_member_many(resource, Enum.map(items, &BloomFilterEx.encode_item/1))
+  end
+
+  @doc """
+  Serializes the filter into a binary.
+
+  The binary holds a version byte, `r`, the number of table rows as a
+  little-endian 64-bit integer and the 128-bit hasher seed, followed by the
+  table as little-endian 64-bit words, one column of row bits at a time.
+
+  ## Examples
+
+      iex> {:ok, ribbon} = BloomFilterEx.Ribbon.build(["a", "b"], 0.01)
+      iex> {:ok, restored} = ribbon |> BloomFilterEx.Ribbon.serialize() |> BloomFilterEx.Ribbon.deserialize()
+      iex> BloomFilterEx.Ribbon.member?(restored, "a")
+      true
+
+  """
+  @spec serialize(t()) :: binary()
+  def serialize(%__MODULE__{resource: resource}) do
+    {:ok, binary} = Native.ribbon_serialize(resource)
+    binary
+  end
+
+  @doc """
+  Restores a filter from a binary produced by `serialize/1`.
+  """
+  @spec deserialize(binary()) :: {:ok, t()} | {:error, BloomFilterEx.error()}
+  def deserialize(binary) when is_binary(binary) do
+    with {:ok, resource} <- Native.ribbon_deserialize(binary) do
+      {:ok, %__MODULE__{resource: resource}}
+    end
+  end
+end
@@ -0,0 +1,121 @@
+defmodule BloomFilterEx.Rotating do
+  @moduledoc """
+  A time-rotating (sliding window) Bloom filter.
+
+  A rotating filter keeps a fixed number of generations. Items are added to the
+  newest generation and looked up in all of them. Each rotation discards the
+  oldest generation and starts a new empty one, so an item is forgotten after
+  `generations` rotations. This gives "seen in the last N intervals" semantics.
+
+  Rotation happens automatically every `:interval_ms` milliseconds (checked
+  lazily on `add/2` and `member?/2`), or explicitly via `rotate/1`.
+
+  Each generation is sized for `capacity` items, and a lookup checks every
+  generation, so the overall false positive rate is up to `generations` times
+  the configured rate.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Rotating.new(1000, 0.01, generations: 2)
+      iex> bloom = BloomFilterEx.Rotating.add(bloom, "seen")
+      iex> bloom = BloomFilterEx.Rotating.rotate(bloom)
+      iex> BloomFilterEx.Rotating.member?(bloom, "seen")
+      true
+      iex> bloom = BloomFilterEx.Rotating.rotate(bloom)
+      iex> BloomFilterEx.Rotating.member?(bloom, "seen")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate, :generations, :interval_ms]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          generations: pos_integer(),
+          interval_ms: non_neg_integer() | nil
+        }
+
+  @doc """
+  Creates a new rotating Bloom filter.
+
+  ## Options
+
+  - `:generations`: Number of generations kept, defaults to `2`
+  - `:interval_ms`: Rotate automatically after this many milliseconds. When not
+    given, generations only rotate through `rotate/1`.
+
+  Returns `{:error, :memory_budget_exceeded}` if the generations would go over
+  the budget set with `BloomFilterEx.configure/1`. Rotating reuses the memory
+  of the generation it drops.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Rotating.new(1000, 0.01, generations: 4, interval_ms: 60_000)
+      iex> bloom.generations
+      4
+
+  """
+  @spec new(pos_integer(), float(), keyword()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    generations = Keyword.get(opts, :generations, 2)
+    interval_ms = Keyword.get(opts, :interval_ms)
+
+    case Native.rotating_new(capacity, false_positive_rate, generations, interval_ms || 0) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          capacity: capacity,
+          false_positive_rate: false_positive_rate,
+          generations: generations,
+          interval_ms: interval_ms
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item to the newest generation.
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{resource: resource} = bloom, item) do
+    {:ok, new_resource} = Native.rotating_add(resource, BloomFilterEx.encode_item(item))
+    %{bloom | resource: new_resource}
+  end
+
+  @doc """
+  Checks if an item is possibly in any live generation.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Rotating.new(100, 0.01)
+      iex> bloom = BloomFilterEx.Rotating.add(bloom, "exists")
+      iex> BloomFilterEx.Rotating.member?(bloom, "exists")
+      true
+      iex> BloomFilterEx.Rotating.member?(bloom, "does-not-exist")
+      false
+
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.rotating_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Discards the oldest generation and starts a new, empty one.
+  """
+  @spec rotate(t()) :: t()
+  def rotate(%__MODULE__{resource: resource} = bloom) do
+    {:ok, new_resource} = Native.rotating_rotate(resource)
+    %{bloom | resource: new_resource}
+  end
+end
@@ -0,0 +1,163 @@
+defmodule BloomFilterEx.Scalable do
+  @moduledoc """
+  A scalable Bloom filter, which grows as items are added.
+
+  A scalable filter starts as a single layer sized for `initial_capacity`
+  items. Whenever the newest layer has received that many distinct items, a
+  new layer `growth_factor` times larger is added, with a tighter false
+  positive rate so that the overall rate stays within the configured target.
+  This means the final cardinality does not need to be known up front.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Scalable.new(10, 0.01)
+      iex> bloom = Enum.reduce(1..100, bloom, &BloomFilterEx.Scalable.add(&2, &1))
+      iex> BloomFilterEx.Scalable.member?(bloom, 42)
+      true
+      iex> BloomFilterEx.Scalable.num_layers(bloom) > 1
+      true
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :initial_capacity, :false_positive_rate, :growth_factor, :inserted_count]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          initial_capacity: pos_integer(),
+          false_positive_rate: float(),
+          growth_factor: pos_integer(),
+          inserted_count: non_neg_integer()
+        }
+
+  @doc """
+  Creates a new scalable Bloom filter.
+
+  ## Parameters
+
+  - `initial_capacity`: Expected number of items in the first layer
+  - `false_positive_rate`: Desired overall false positive probability, between 0.0 and 1.0
+  - `growth_factor`: How many times larger each new layer is than the previous one (at least 2)
+
+  Returns `{:error, :memory_budget_exceeded}` if the first layer would go over
+  the budget set with `BloomFilterEx.configure/1`, `{:error, :allocation_failed}`
+  if its memory can't be allocated, or `{:error, :invalid_capacity}` if it
+  would need more than 2^32 bits.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Scalable.new(1000, 0.01, 4)
+      iex> bloom.growth_factor
+      4
+      iex> BloomFilterEx.Scalable.num_layers(bloom)
+      1
+
+  """
+  @spec new(pos_integer(), float(), pos_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def new(initial_capacity, false_positive_rate, growth_factor \\ 2)
+      when is_integer(initial_capacity) and initial_capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_integer(growth_factor) and growth_factor >= 2 do
+    case Native.scalable_new(initial_capacity, false_positive_rate, growth_factor) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          initial_capacity: initial_capacity,
+          false_positive_rate: false_positive_rate,
+          growth_factor: growth_factor,
+          inserted_count: 0
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item to the scalable Bloom filter, adding a new layer if needed.
+
+  A new layer is counted against the memory budget like the first, and the
+  same errors as `new/3` are returned if it can't be added.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Scalable.new(100, 0.01)
+      iex> bloom = BloomFilterEx.Scalable.add(bloom, "item")
+      iex> bloom.inserted_count
+      1
+
+  """
+  @spec add(t(), term()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def add(%__MODULE__{resource: resource} = bloom, item) do
+    case Native.scalable_add(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, new_resource} ->
+        %{bloom | resource: new_resource, inserted_count: bloom.inserted_count + 1}
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Checks if an item is possibly in any layer of the scalable Bloom filter.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Scalable.new(100, 0.01)
+      iex> bloom = BloomFilterEx.Scalable.add(bloom, "exists")
+      iex> BloomFilterEx.Scalable.member?(bloom, "exists")
+      true
+      iex> BloomFilterEx.Scalable.member?(bloom, "does-not-exist")
+      false
+
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.scalable_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Allocates the layers needed for `additional` more distinct items ahead of a
+  bulk load, so inserts during the load don't pause to allocate them.
+
+  Layers are added exactly as inserts would add them, with the same sizes and
+  false positive rates, and stay empty until the layers before them fill up.
+  Their memory is reserved together, so if the layers would go over the budget
+  set with `BloomFilterEx.configure/1`, none are added and
+  `{:error, :memory_budget_exceeded}` is returned. `{:error, :allocation_failed}`
+  is returned if their memory can't be allocated, and
+  `{:error, :invalid_capacity}` if a layer would need more than 2^32 bits.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Scalable.new(10, 0.01)
+      iex> bloom = BloomFilterEx.Scalable.reserve(bloom, 1000)
+      iex> layers = BloomFilterEx.Scalable.num_layers(bloom)
+      iex> bloom = Enum.reduce(1..1000, bloom, &BloomFilterEx.Scalable.add(&2, &1))
+      iex> BloomFilterEx.Scalable.num_layers(bloom) == layers
+      true
+
+  """
+  @spec reserve(t(), non_neg_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def reserve(%__MODULE__{resource: resource} = bloom, additional)
+      when is_integer(additional) and additional >= 0 do
+    case Native.scalable_reserve(resource, additional) do
+      {:ok, _added} -> bloom
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Returns the number of layers currently allocated, including any added ahead
+  of time by `reserve/2`.
+  """
+  @spec num_layers(t()) :: pos_integer()
+  def num_layers(%__MODULE__{resource: resource}) do
+    {:ok, num_layers} = Native.scalable_num_layers(resource)
+    num_layers
+  end
+end
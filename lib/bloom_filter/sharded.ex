@@ -0,0 +1,122 @@
+defmodule BloomFilterEx.Sharded do
+  @moduledoc """
+  A Bloom filter split into independently locked shards.
+
+  A plain `BloomFilterEx` has a single lock, so concurrent writers from many
+  processes take turns. A sharded filter routes each item by hash to one of
+  `shards` sub-filters, each with its own lock, so writers only contend when
+  their items land in the same shard. Lookups only lock the owning shard.
+
+  Each shard is sized for an equal share of `capacity` with the configured
+  false positive rate, so the filter as a whole keeps that rate.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Sharded.new(10_000, 0.01, 8)
+      iex> bloom = BloomFilterEx.Sharded.add(bloom, "user@example.com")
+      iex> BloomFilterEx.Sharded.member?(bloom, "user@example.com")
+      true
+      iex> BloomFilterEx.Sharded.member?(bloom, "not-added@example.com")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate, :shards, :inserted_count]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          shards: pos_integer(),
+          inserted_count: non_neg_integer()
+        }
+
+  @doc """
+  Creates a new sharded Bloom filter.
+
+  A good choice for `shards` is a small multiple of the number of schedulers,
+  for example `System.schedulers_online() * 2`. Returns
+  `{:error, :memory_budget_exceeded}` if the shards would go over the budget
+  set with `BloomFilterEx.configure/1`.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Sharded.new(1000, 0.01, 4)
+      iex> bloom.shards
+      4
+
+  """
+  @spec new(pos_integer(), float(), pos_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def new(capacity, false_positive_rate, shards)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_integer(shards) and shards > 0 do
+    case Native.sharded_new(capacity, false_positive_rate, shards) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          capacity: capacity,
+          false_positive_rate: false_positive_rate,
+          shards: shards,
+          inserted_count: 0
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item to the sharded Bloom filter.
+
+  As with `BloomFilterEx.add/2`, items that are (probably) already present are
+  not counted again.
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{resource: resource} = bloom, item) do
+    case Native.sharded_add(resource, BloomFilterEx.encode_item(item)) do
+      {:ok, true} -> %{bloom | inserted_count: bloom.inserted_count + 1}
+      {:ok, false} -> bloom
+    end
+  end
+
+  @doc """
+  Adds a list of items in a single native call, locking each shard once.
+
+  ## Examples
+
+      iex> bloom = BloomFilterEx.Sharded.new(1000, 0.01, 4)
+      iex> bloom = BloomFilterEx.Sharded.add_many(bloom, ["a", "b", "c", "a"])
+      iex> bloom.inserted_count
+      3
+
+  """
+  @spec add_many(t(), [term()]) :: t()
+  def add_many(%__MODULE__{resource: resource} = bloom, items) when is_list(items) do
+    items = Enum.map(items, &BloomFilterEx.encode_item/1)
+    {:ok, count} = Native.sharded_add_many(resource, items)
+    %{bloom | inserted_count: bloom.inserted_count + count}
+  end
+
+  @doc """
+  Checks if an item is possibly in the sharded Bloom filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.sharded_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Returns the number of distinct items added through any process.
+
+  The `inserted_count` field only reflects inserts made through this struct,
+  while the count returned here is kept by the shared native filter.
+  """
+  @spec inserted_count(t()) :: non_neg_integer()
+  def inserted_count(%__MODULE__{resource: resource}) do
+    Native.sharded_inserted_count(resource)
+  end
+end
@@ -0,0 +1,96 @@
+defmodule BloomFilterEx.Stable do
+  @moduledoc """
+  A stable Bloom filter, for deduplicating unbounded streams.
+
+  A plain Bloom filter fills up as items keep arriving, until every lookup
+  is a false positive. A stable Bloom filter (Deng and Rafiei, 2006) keeps
+  its false positive rate bounded instead by forgetting old items. Its cells
+  are small counters of `cell_bits` bits. Every insert decrements a fixed
+  number of cells, then sets the item's cells to the maximum value. A lookup
+  finds an item when all of its cells are non-zero.
+
+  The number of cells decremented per insert is chosen so that, once the
+  stream has run long enough for the filter to settle, the false positive
+  rate stays at `fp_target`. The price is false negatives: an item not seen
+  for a while may have been forgotten. More cells, or more bits per cell,
+  remember items for longer.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.Stable.new(100_000, 3, 0.01)
+      iex> {false, filter} = BloomFilterEx.Stable.add_if_absent(filter, "event-1")
+      iex> {true, _filter} = BloomFilterEx.Stable.add_if_absent(filter, "event-1")
+      iex> {filter.num_hashes, filter.decrements}
+      {7, 63}
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :num_cells, :cell_bits, :fp_target, :num_hashes, :decrements]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          num_cells: pos_integer(),
+          cell_bits: 1..8,
+          fp_target: float(),
+          num_hashes: pos_integer(),
+          decrements: pos_integer()
+        }
+
+  @doc """
+  Creates a new stable Bloom filter with `num_cells` cells of `cell_bits`
+  bits each, which settles at the `fp_target` false positive rate.
+
+  Each cell takes a byte of memory whatever `cell_bits` is, and `cell_bits`
+  must be from 1 to 8. `num_cells` must be larger than the number of hash
+  functions, `ceil(log2(1 / fp_target))`, or `{:error, :invalid_capacity}` is
+  returned. Returns `{:error, :memory_budget_exceeded}` if the cells would go
+  over the budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), 1..8, float()) ::
+          t() | {:error, :invalid_capacity | :memory_budget_exceeded | :allocation_failed}
+  def new(num_cells, cell_bits, fp_target)
+      when is_integer(num_cells) and num_cells > 0 and cell_bits in 1..8 and
+             is_float(fp_target) and fp_target > 0.0 and fp_target < 1.0 do
+    with {:ok, resource} <- Native.stable_new(num_cells, cell_bits, fp_target) do
+      {:ok, layout} = Native.stable_layout(resource)
+
+      %__MODULE__{
+        resource: resource,
+        num_cells: num_cells,
+        cell_bits: cell_bits,
+        fp_target: fp_target,
+        num_hashes: layout.num_hashes,
+        decrements: layout.decrements
+      }
+    end
+  end
+
+  @doc """
+  Adds an item to the filter.
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{} = filter, item) do
+    {_present, filter} = add_if_absent(filter, item)
+    filter
+  end
+
+  @doc """
+  Adds an item and returns whether it was already present, in a single
+  native call, which is how a stream is usually deduplicated.
+  """
+  @spec add_if_absent(t(), term()) :: {boolean(), t()}
+  def add_if_absent(%__MODULE__{resource: resource} = filter, item) do
+    {:ok, present} = Native.stable_add(resource, BloomFilterEx.encode_item(item))
+    {present, filter}
+  end
+
+  @doc """
+  Checks if an item is possibly in the filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.stable_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+end
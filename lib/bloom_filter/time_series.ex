@@ -0,0 +1,121 @@
+defmodule BloomFilterEx.TimeSeries do
+  @moduledoc """
+  A set of Bloom filters, one per time bucket, for answering "was this item
+  seen between `from` and `to`?".
+
+  Items are added with a timestamp, and go into the filter for the bucket
+  that timestamp falls in, which is created on demand. Timestamps are
+  non-negative integers in any unit, such as `System.os_time(:second)`, as
+  long as `:bucket_size` is in the same unit. `member_in_range?/4` checks
+  every bucket that overlaps the range, so ranges are only as precise as the
+  buckets: an item added at the start of a bucket is found by a range that
+  only covers the end of it.
+
+  Only the newest `:retention` buckets are kept. Once an item arrives for a
+  newer bucket, buckets that fall out of the window are dropped, and items
+  with timestamps in dropped buckets are ignored.
+
+  Each bucket's filter is sized for `capacity` items, and a range lookup
+  checks every bucket in the range, so its false positive rate is up to the
+  number of buckets checked times the configured rate.
+
+  ## Examples
+
+      iex> ts = BloomFilterEx.TimeSeries.new(1000, 0.01, bucket_size: 3600, retention: 24)
+      iex> ts = BloomFilterEx.TimeSeries.add(ts, "user-42", 7_200)
+      iex> BloomFilterEx.TimeSeries.member_in_range?(ts, "user-42", 7_000, 8_000)
+      true
+      iex> BloomFilterEx.TimeSeries.member_in_range?(ts, "user-42", 0, 3_599)
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate, :bucket_size, :retention]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float(),
+          bucket_size: pos_integer(),
+          retention: pos_integer()
+        }
+
+  @doc """
+  Creates a new time-bucketed filter set, with each bucket's filter sized for
+  `capacity` items at the given false positive rate.
+
+  ## Options
+
+  - `:bucket_size`: The length of each bucket, in the unit of the timestamps.
+    Defaults to `3600`, an hour of seconds.
+  - `:retention`: Number of buckets kept, counting back from the newest.
+    Defaults to `24`.
+
+  """
+  @spec new(pos_integer(), float(), keyword()) :: t()
+  def new(capacity, false_positive_rate, opts \\ [])
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 and is_list(opts) do
+    bucket_size = Keyword.get(opts, :bucket_size, 3600)
+    retention = Keyword.get(opts, :retention, 24)
+    {:ok, resource} = Native.ts_new(capacity, false_positive_rate, bucket_size, retention)
+
+    %__MODULE__{
+      resource: resource,
+      capacity: capacity,
+      false_positive_rate: false_positive_rate,
+      bucket_size: bucket_size,
+      retention: retention
+    }
+  end
+
+  @doc """
+  Adds an item seen at `timestamp`.
+
+  Items with timestamps in buckets that have already been dropped are
+  ignored. Returns `{:error, :memory_budget_exceeded}` if the item needs a
+  new bucket and its filter would go over the budget set with
+  `BloomFilterEx.configure/1`.
+  """
+  @spec add(t(), term(), non_neg_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed | :invalid_capacity}
+  def add(%__MODULE__{resource: resource} = ts, item, timestamp)
+      when is_integer(timestamp) and timestamp >= 0 do
+    case Native.ts_add(resource, BloomFilterEx.encode_item(item), timestamp) do
+      {:ok, _recorded} -> ts
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Checks if an item was possibly added with a timestamp in a bucket that
+  overlaps `from..to`, inclusive.
+  """
+  @spec member_in_range?(t(), term(), non_neg_integer(), non_neg_integer()) :: boolean()
+  def member_in_range?(%__MODULE__{resource: resource}, item, from, to)
+      when is_integer(from) and from >= 0 and is_integer(to) and to >= 0 do
+    {:ok, result} =
+      Native.ts_member_in_range(resource, BloomFilterEx.encode_item(item), from, to)
+
+    result
+  end
+
+  @doc """
+  Returns the start time of every bucket currently kept, oldest first.
+
+  ## Examples
+
+      iex> ts = BloomFilterEx.TimeSeries.new(100, 0.01, bucket_size: 10, retention: 2)
+      iex> ts = Enum.reduce([5, 15, 25], ts, &BloomFilterEx.TimeSeries.add(&2, "item", &1))
+      iex> BloomFilterEx.TimeSeries.buckets(ts)
+      [10, 20]
+
+  """
+  @spec buckets(t()) :: [non_neg_integer()]
+  def buckets(%__MODULE__{resource: resource}) do
+    {:ok, buckets} = Native.ts_buckets(resource)
+    buckets
+  end
+end
@@ -0,0 +1,66 @@
+defmodule BloomFilterEx.TopK do
+  @moduledoc """
+  Tracks the `k` most frequent items in a stream.
+
+  Every item is counted in a count-min sketch of `depth` rows of `width`
+  counters, and the `k` items with the highest estimated counts so far are kept
+  alongside it. An item joins the list once its estimate is higher than the
+  lowest one in the list, which drops out. As with
+  `BloomFilterEx.CountMinSketch`, counts may be overestimated but are never
+  underestimated.
+
+  ## Examples
+
+      iex> top = BloomFilterEx.TopK.new(2, 1000, 5)
+      iex> top = Enum.reduce(["a", "b", "a", "c", "a", "b"], top, &BloomFilterEx.TopK.add(&2, &1))
+      iex> BloomFilterEx.TopK.list(top)
+      [{"a", 3}, {"b", 2}]
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :k, :width, :depth]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          k: pos_integer(),
+          width: pos_integer(),
+          depth: pos_integer()
+        }
+
+  @doc """
+  Creates a tracker for the `k` most frequent items, counted in a sketch with
+  `depth` rows of `width` counters.
+
+  Returns `{:error, :memory_budget_exceeded}` if the counters would go over
+  the budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), pos_integer(), pos_integer()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(k, width, depth)
+      when is_integer(k) and k > 0 and is_integer(width) and width > 0 and is_integer(depth) and
+             depth > 0 do
+    case Native.topk_new(k, width, depth) do
+      {:ok, resource} -> %__MODULE__{resource: resource, k: k, width: width, depth: depth}
+      {:error, _reason} = error -> error
+    end
+  end
+
+  @doc """
+  Counts one occurrence of `item`.
+  """
+  @spec add(t(), term()) :: t()
+  def add(%__MODULE__{resource: resource} = top, item) do
+    {:ok, {}} = Native.topk_add(resource, BloomFilterEx.encode_item(item))
+    top
+  end
+
+  @doc """
+  Returns the tracked items with their estimated counts, most frequent first.
+  """
+  @spec list(t()) :: [{term(), pos_integer()}]
+  def list(%__MODULE__{resource: resource}) do
+    {:ok, items} = Native.topk_list(resource)
+    Enum.map(items, fn {item, count} -> {:erlang.binary_to_term(item), count} end)
+  end
+end
@@ -0,0 +1,97 @@
+defmodule BloomFilterEx.TTL do
+  @moduledoc """
+  A Bloom filter whose items expire individually, for "seen within the last
+  N intervals" checks.
+
+  Time is divided into buckets, and `advance/1` moves the filter on to the
+  next one, typically from a timer. Each item is added with its own
+  time-to-live in buckets and stops being a member once that many buckets
+  have passed. Adding an item again extends its time-to-live if the new one
+  is longer, and never shortens it.
+
+  Unlike `BloomFilterEx.Rotating`, which forgets whole generations at once,
+  each item lasts exactly as many buckets as it was given, so buckets can be
+  as short as the precision needed. Each slot stores the bucket it expires in
+  instead of a single bit, taking 32 bits, and `advance/1` doesn't touch the
+  slots, so it is cheap however large the filter is. Items whose slots are
+  kept alive by other items are false positives, as in any Bloom filter, and
+  the false positive rate applies to the items that haven't expired.
+
+  ## Examples
+
+      iex> filter = BloomFilterEx.TTL.new(1000, 0.01)
+      iex> filter = BloomFilterEx.TTL.add(filter, "10.0.0.1", 2)
+      iex> filter = BloomFilterEx.TTL.advance(filter)
+      iex> BloomFilterEx.TTL.member?(filter, "10.0.0.1")
+      true
+      iex> filter = BloomFilterEx.TTL.advance(filter)
+      iex> BloomFilterEx.TTL.member?(filter, "10.0.0.1")
+      false
+  """
+
+  alias BloomFilterEx.Native
+
+  defstruct [:resource, :capacity, :false_positive_rate]
+
+  @type t :: %__MODULE__{
+          resource: reference(),
+          capacity: pos_integer(),
+          false_positive_rate: float()
+        }
+
+  @doc """
+  Creates a new filter for the expected number of unexpired items and the
+  desired false positive rate.
+
+  Returns `{:error, :memory_budget_exceeded}` if the slots would go over the
+  budget set with `BloomFilterEx.configure/1`.
+  """
+  @spec new(pos_integer(), float()) ::
+          t() | {:error, :memory_budget_exceeded | :allocation_failed}
+  def new(capacity, false_positive_rate)
+      when is_integer(capacity) and capacity > 0 and
+             is_float(false_positive_rate) and false_positive_rate > 0.0 and
+             false_positive_rate < 1.0 do
+    case Native.ttl_new(capacity, false_positive_rate) do
+      {:ok, resource} ->
+        %__MODULE__{
+          resource: resource,
+          capacity: capacity,
+          false_positive_rate: false_positive_rate
+        }
+
+      {:error, _reason} = error ->
+        error
+    end
+  end
+
+  @doc """
+  Adds an item that stays a member for the next `ttl_buckets` calls to
+  `advance/1`.
+  """
+  @spec add(t(), term(), pos_integer()) :: t()
+  def add(%__MODULE__{resource: resource} = filter, item, ttl_buckets)
+      when is_integer(ttl_buckets) and ttl_buckets > 0 do
+    {:ok, {}} = Native.ttl_add(resource, BloomFilterEx.encode_item(item), ttl_buckets)
+    filter
+  end
+
+  @doc """
+  Checks if an unexpired item is possibly in the filter.
+  """
+  @spec member?(t(), term()) :: boolean()
+  def member?(%__MODULE__{resource: resource}, item) do
+    {:ok, result} = Native.ttl_member(resource, BloomFilterEx.encode_item(item))
+    result
+  end
+
+  @doc """
+  Moves the filter on to the next bucket, expiring every item whose
+  time-to-live runs out.
+  """
+  @spec advance(t()) :: t()
+  def advance(%__MODULE__{resource: resource} = filter) do
+    {:ok, _buckets} = Native.ttl_advance(resource)
+    filter
+  end
+end
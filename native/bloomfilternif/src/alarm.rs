@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::segmented::Filter;
+use crate::{popcount, BloomFilterResource};
+use rustler::{Encoder, LocalPid, OwnedEnv, ResourceArc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+rustler::atoms! {
+    bloom_fill_alarm,
+}
+
+struct Alarm {
+    threshold: f64,
+    pid: LocalPid,
+}
+
+// Counting the bits set takes a pass over the whole filter, far too slow for
+// every insert. Instead the bits are counted once the inserted count reaches
+// `next_check`, which is moved halfway to where the expected fill ratio would
+// cross the threshold each time it hasn't yet, so a filter filling steadily
+// is counted about log2 of its capacity times. The count runs on a thread of
+// its own, so inserts never wait for it.
+pub(crate) struct FillAlarm {
+    // usize::MAX while no alarm is set.
+    next_check: AtomicUsize,
+    // Set while a thread is counting.
+    counting: AtomicBool,
+    alarm: Mutex<Option<Alarm>>,
+}
+
+impl Default for FillAlarm {
+    fn default() -> Self {
+        FillAlarm {
+            next_check: AtomicUsize::new(usize::MAX),
+            counting: AtomicBool::new(false),
+            alarm: Mutex::new(None),
+        }
+    }
+}
+
+impl FillAlarm {
+    fn count(&self, filter: &Filter, inserted_count: usize) {
+        let Ok(mut alarm) = self.alarm.lock() else {
+            return;
+        };
+        let Some(Alarm { threshold, pid }) = *alarm else {
+            return;
+        };
+
+        let num_bits = filter.num_bits();
+        let ratio = popcount(filter) as f64 / num_bits as f64;
+        if ratio >= threshold {
+            *alarm = None;
+            self.next_check.store(usize::MAX, Ordering::Relaxed);
+            let _ =
+                OwnedEnv::new().send_and_clear(&pid, |env| (bloom_fill_alarm(), ratio).encode(env));
+            return;
+        }
+
+        // Each new item is expected to set a share k/m of the bits still unset.
+        let remaining =
+            num_bits as f64 / filter.num_hashes() as f64 * ((1.0 - ratio) / (1.0 - threshold)).ln();
+        let step = (remaining / 2.0).max(1.0) as usize;
+        self.next_check
+            .store(inserted_count.saturating_add(step), Ordering::Relaxed);
+    }
+}
+
+// Called after every insert. When a count is due, the bits are counted under
+// the read lock from a thread of its own, like `add_many_async`'s inserts, and
+// the message is sent from there: VM threads can't send from an environment
+// of their own.
+pub(crate) fn check_fill(resource: &ResourceArc<BloomFilterResource>) {
+    let fill_alarm = &resource.fill_alarm;
+    let inserted_count = resource.inserted_count.load(Ordering::Relaxed);
+    if inserted_count < fill_alarm.next_check.load(Ordering::Relaxed) {
+        return;
+    }
+    // Another insert has already started a count.
+    if fill_alarm.counting.swap(true, Ordering::Acquire) {
+        return;
+    }
+
+    let resource = resource.clone();
+    thread::spawn(move || loop {
+        let fill_alarm = &resource.fill_alarm;
+        let inserted_count = resource.inserted_count.load(Ordering::Relaxed);
+        fill_alarm.count(&resource.filter.read(), inserted_count);
+        fill_alarm.counting.store(false, Ordering::Release);
+
+        // Inserts that came due during the count skipped theirs, so count again.
+        let inserted_count = resource.inserted_count.load(Ordering::Relaxed);
+        if inserted_count < fill_alarm.next_check.load(Ordering::Relaxed)
+            || fill_alarm.counting.swap(true, Ordering::Acquire)
+        {
+            return;
+        }
+    });
+}
+
+// Sends `{:bloom_fill_alarm, ratio}` to `pid` soon after an insert takes the
+// share of bits set to `threshold` or more, then disarms. The next insert
+// checks straight away, so a filter already past the threshold sends on it.
+// Calling this again replaces the previous alarm.
+#[rustler::nif]
+fn set_fill_alarm(
+    resource: ResourceArc<BloomFilterResource>,
+    threshold: f64,
+    pid: LocalPid,
+) -> Result<(), Error> {
+    if !(threshold > 0.0 && threshold < 1.0) {
+        return Err(Error::InvalidValue);
+    }
+    let mut alarm = resource.fill_alarm.alarm.lock()?;
+    *alarm = Some(Alarm { threshold, pid });
+    resource.fill_alarm.next_check.store(0, Ordering::Relaxed);
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::error::Error;
+use crate::file::save_to;
+use crate::BloomFilterResource;
+use rustler::ResourceArc;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// A thread saving a filter to a file on a schedule, stopped by sending on or
+// dropping `stop`.
+pub(crate) struct Autosave {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Autosave {
+    fn start(resource: ResourceArc<BloomFilterResource>, path: String, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            // Saves are skipped while the filter is unchanged. A failed save
+            // leaves `saved` as it was, so it is retried on the next tick.
+            let mut saved = None;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let version = resource.writes.current();
+                if saved != Some(version) && save_to(&resource, &path).is_ok() {
+                    saved = Some(version);
+                }
+            }
+        });
+        Autosave { stop, thread }
+    }
+
+    // Waits for a save in progress to finish.
+    fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+// Saves the filter to `path` like `save` every `interval_ms` milliseconds, from
+// a thread of its own, replacing any autosave already running for it. The
+// thread holds a reference to the filter, so it is not garbage collected until
+// `disable_autosave` is called.
+#[rustler::nif(schedule = "DirtyIo")]
+fn enable_autosave(
+    resource: ResourceArc<BloomFilterResource>,
+    path: String,
+    interval_ms: u64,
+) -> Result<(), Error> {
+    if interval_ms == 0 {
+        return Err(Error::InvalidOption);
+    }
+    let autosave = Autosave::start(resource.clone(), path, Duration::from_millis(interval_ms));
+    let previous = resource.autosave.lock()?.replace(autosave);
+    if let Some(previous) = previous {
+        previous.stop();
+    }
+    Ok(())
+}
+
+// Stops any autosave running for the filter, once a save in progress is done.
+#[rustler::nif(schedule = "DirtyIo")]
+fn disable_autosave(resource: ResourceArc<BloomFilterResource>) -> Result<(), Error> {
+    let autosave = resource.autosave.lock()?.take();
+    if let Some(autosave) = autosave {
+        autosave.stop();
+    }
+    Ok(())
+}
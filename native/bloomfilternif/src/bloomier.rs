@@ -0,0 +1,69 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::ribbon::{fingerprint, RibbonTable, MAX_RESULT_BITS};
+use rustler::{Resource, ResourceArc};
+
+// Bits of each result that check the key was one the map was built from.
+// An absent key passes the check with probability 2^-CHECK_BITS.
+const CHECK_BITS: u32 = 16;
+
+// Values get whatever bits are left of a result.
+const MAX_VALUE_BITS: u32 = MAX_RESULT_BITS - CHECK_BITS;
+
+// A static approximate map from keys to small values, in the style of a
+// bloomier filter. It is a ribbon table whose result for each key is a
+// fingerprint of the key above the key's value, so it takes about
+// 1.05 * (CHECK_BITS + value_bits) bits per key and never stores the keys.
+// Looking up a key the map was built from always returns its value; any other
+// key returns `None`, unless it matches the fingerprint by chance.
+pub struct BloomierResource {
+    table: RibbonTable,
+    value_bits: u32,
+}
+
+impl Resource for BloomierResource {}
+
+impl BloomierResource {
+    fn get(&self, key: &[u8]) -> Option<u32> {
+        let hash = self.table.hash(key);
+        let result = self.table.lookup(hash);
+        (result >> self.value_bits == fingerprint(hash, CHECK_BITS))
+            .then_some(result & ((1 << self.value_bits) - 1))
+    }
+}
+
+// Values must fit in MAX_VALUE_BITS bits, and the map uses as few bits as the
+// largest value needs. Of repeated keys, the first pair is kept.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn map_build(pairs: Vec<(Item, u32)>) -> Result<ResourceArc<BloomierResource>, Error> {
+    let mut pairs: Vec<(&[u8], u32)> = pairs
+        .iter()
+        .map(|(key, value)| (key.as_slice(), *value))
+        .collect();
+    pairs.sort_by_key(|&(key, _)| key);
+    pairs.dedup_by_key(|&mut (key, _)| key);
+
+    let max_value = pairs.iter().map(|&(_, value)| value).max().unwrap_or(0);
+    let value_bits = (u32::BITS - max_value.leading_zeros()).max(1);
+    if value_bits > MAX_VALUE_BITS {
+        return Err(Error::InvalidValue);
+    }
+
+    let keys: Vec<&[u8]> = pairs.iter().map(|&(key, _)| key).collect();
+    let table = RibbonTable::build(&keys, CHECK_BITS + value_bits, |index, hash| {
+        fingerprint(hash, CHECK_BITS) << value_bits | pairs[index].1
+    })?;
+    Ok(ResourceArc::new(BloomierResource { table, value_bits }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn map_get(resource: ResourceArc<BloomierResource>, key: Item) -> Option<u32> {
+    resource.get(key.as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn map_get_many(resource: ResourceArc<BloomierResource>, keys: Vec<Item>) -> Vec<Option<u32>> {
+    keys.iter()
+        .map(|key| resource.get(key.as_slice()))
+        .collect()
+}
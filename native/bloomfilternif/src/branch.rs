@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::Reservation;
+use crate::segmented::Filter;
+use crate::BloomFilterResource;
+use rustler::{Resource, ResourceArc};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+// Pages are 4 KiB, 32768 bits.
+const PAGE_WORDS: usize = 512;
+
+struct Page {
+    words: Box<[u64]>,
+    // The page's share of the memory budget.
+    _memory: Reservation,
+}
+
+// A copy-on-write child of a bloom filter. The branch reads through to its
+// parent's bits until it inserts, which copies the pages it writes to into
+// the branch, so branching costs nothing and a branch holds only the pages it
+// has changed. The parent is never written. Pages the branch hasn't copied
+// still see changes made to the parent after it was branched.
+pub struct BranchResource {
+    parent: ResourceArc<BloomFilterResource>,
+    pages: RwLock<HashMap<usize, Page>>,
+    inserted_count: AtomicUsize,
+}
+
+impl Resource for BranchResource {}
+
+impl BranchResource {
+    // The word of the bit array at `index` as the branch sees it.
+    fn word(&self, filter: &Filter, pages: &HashMap<usize, Page>, index: usize) -> u64 {
+        match pages.get(&(index / PAGE_WORDS)) {
+            Some(page) => page.words[index % PAGE_WORDS],
+            None => filter.word(index).load(Ordering::Relaxed),
+        }
+    }
+
+    fn indexes(filter: &Filter, item: &[u8]) -> Vec<u64> {
+        filter.bit_indexes(filter.source_hash(item))
+    }
+}
+
+// The last page is cut short at the end of the bit array.
+fn copy_page(filter: &Filter, page: usize) -> Result<Page, Error> {
+    let start = page * PAGE_WORDS;
+    let end = (start + PAGE_WORDS).min(filter.num_words());
+    let words: Box<[u64]> = (start..end)
+        .map(|index| filter.word(index).load(Ordering::Relaxed))
+        .collect();
+    Ok(Page {
+        _memory: Reservation::new(words.len() * 8)?,
+        words,
+    })
+}
+
+#[rustler::nif]
+fn branch(parent: ResourceArc<BloomFilterResource>) -> ResourceArc<BranchResource> {
+    ResourceArc::new(BranchResource {
+        inserted_count: AtomicUsize::new(parent.inserted_count.load(Ordering::Relaxed)),
+        parent,
+        pages: RwLock::new(HashMap::new()),
+    })
+}
+
+// Returns whether the item was new, like `add`.
+#[rustler::nif]
+fn branch_add(resource: ResourceArc<BranchResource>, item: Item) -> Result<bool, Error> {
+    let filter = resource.parent.filter.read();
+    let mut pages = resource.pages.write()?;
+
+    let mut new = false;
+    for index in BranchResource::indexes(&filter, item.as_slice()) {
+        let (word, bit) = (index as usize / 64, index % 64);
+        if resource.word(&filter, &pages, word) >> bit & 1 == 1 {
+            continue;
+        }
+        let page = match pages.entry(word / PAGE_WORDS) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let copy = copy_page(&filter, *entry.key())?;
+                entry.insert(copy)
+            }
+        };
+        page.words[word % PAGE_WORDS] |= 1 << bit;
+        new = true;
+    }
+    if new {
+        resource.inserted_count.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(new)
+}
+
+#[rustler::nif]
+fn branch_member(resource: ResourceArc<BranchResource>, item: Item) -> Result<bool, Error> {
+    let filter = resource.parent.filter.read();
+    let pages = resource.pages.read()?;
+    Ok(BranchResource::indexes(&filter, item.as_slice())
+        .into_iter()
+        .all(|index| resource.word(&filter, &pages, index as usize / 64) >> (index % 64) & 1 == 1))
+}
+
+// The parent's inserted count when the branch was made, plus the new items
+// the branch has inserted since.
+#[rustler::nif]
+fn branch_inserted_count(resource: ResourceArc<BranchResource>) -> usize {
+    resource.inserted_count.load(Ordering::Relaxed)
+}
+
+// The memory held by the pages the branch has copied.
+#[rustler::nif]
+fn branch_copied_bytes(resource: ResourceArc<BranchResource>) -> Result<usize, Error> {
+    let pages = resource.pages.read()?;
+    Ok(pages.values().map(|page| page.words.len() * 8).sum())
+}
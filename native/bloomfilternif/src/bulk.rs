@@ -0,0 +1,50 @@
+use crate::alarm::check_fill;
+use crate::error::Error;
+use crate::item::Item;
+use crate::BloomFilterResource;
+use rustler::types::atom;
+use rustler::{Atom, Encoder, Env, LocalPid, NifResult, OwnedEnv, ResourceArc, Term};
+use std::thread;
+
+// Like `add_many`, but inserts on a background thread and returns immediately.
+// When done, `{reference, :ok, new_items}` or `{reference, :error, reason}` is
+// sent to `pid`. The items are copied out of the caller's heap first, so the
+// caller is free to drop them straight away.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn add_many_async<'a>(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Term<'a>,
+    pid: LocalPid,
+    reference: Term<'a>,
+) -> NifResult<Atom> {
+    if !items.is_list() {
+        return Err(rustler::Error::BadArg);
+    }
+
+    let mut owned_env = OwnedEnv::new();
+    let saved = owned_env.save((reference, items));
+
+    thread::spawn(move || {
+        // The process may have exited by now, in which case there is no one
+        // to tell.
+        let _ = owned_env.send_and_clear(&pid, |env| {
+            let (reference, items): (Term, Term) =
+                saved.load(env).decode().expect("saved as a 2-tuple");
+            let result = items
+                .decode::<Vec<Item>>()
+                .map_err(|_| Error::InvalidData)
+                .and_then(|items| resource.insert_many(&items))
+                .inspect(|_| check_fill(&resource));
+            completion(env, reference, result)
+        });
+    });
+
+    Ok(atom::ok())
+}
+
+fn completion<'a>(env: Env<'a>, reference: Term<'a>, result: Result<usize, Error>) -> Term<'a> {
+    match result {
+        Ok(new_items) => (reference, atom::ok(), new_items).encode(env),
+        Err(error) => (reference, atom::error(), error).encode(env),
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::Error;
+use crate::hasher::double_hash_indexes;
+use crate::memory::{zeroed, Reservation};
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// Sketches hash with a fixed seed so that sketches built on different nodes
+// with the same dimensions can always be merged.
+const CMS_SEED: [u8; 16] = [0; 16];
+
+// A count-min sketch: `depth` rows of `width` counters. Each item increments
+// one counter per row, and its estimate is the minimum of those counters, which
+// never underestimates the true count.
+pub(crate) struct CountMinSketch {
+    counters: Vec<u64>,
+    width: usize,
+    depth: usize,
+    hasher: DefaultHasher,
+    _memory: Reservation,
+}
+
+impl CountMinSketch {
+    pub(crate) fn new(width: usize, depth: usize) -> Result<Self, Error> {
+        // Each row takes one of the item's double hashes.
+        u32::try_from(depth).map_err(|_| Error::InvalidDepth)?;
+        let len = width.checked_mul(depth).ok_or(Error::AllocationFailed)?;
+        let (counters, memory) = zeroed(len)?;
+        Ok(CountMinSketch {
+            counters,
+            width,
+            depth,
+            hasher: DefaultHasher::seeded(&CMS_SEED),
+            _memory: memory,
+        })
+    }
+
+    // One counter index per row, the row's column picked by double hashing.
+    fn indexes(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(item);
+        let width = self.width;
+        double_hash_indexes(hash, self.depth as u32, width)
+            .enumerate()
+            .map(move |(row, column)| row * width + column)
+    }
+
+    pub(crate) fn increment(&mut self, item: &[u8], count: u64) {
+        for index in self.indexes(item) {
+            self.counters[index] = self.counters[index].saturating_add(count);
+        }
+    }
+
+    pub(crate) fn estimate(&self, item: &[u8]) -> u64 {
+        self.indexes(item)
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+pub struct CountMinSketchResource {
+    sketch: RwLock<CountMinSketch>,
+}
+
+impl Resource for CountMinSketchResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn cms_new(width: usize, depth: usize) -> Result<ResourceArc<CountMinSketchResource>, Error> {
+    if width == 0 {
+        return Err(Error::InvalidWidth);
+    }
+    if depth == 0 {
+        return Err(Error::InvalidDepth);
+    }
+
+    Ok(ResourceArc::new(CountMinSketchResource {
+        sketch: RwLock::new(CountMinSketch::new(width, depth)?),
+    }))
+}
+
+#[rustler::nif]
+fn cms_increment(
+    resource: ResourceArc<CountMinSketchResource>,
+    item: Binary,
+    count: u64,
+) -> Result<ResourceArc<CountMinSketchResource>, Error> {
+    {
+        let mut sketch = resource.sketch.write()?;
+        sketch.increment(item.as_slice(), count);
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn cms_estimate(resource: ResourceArc<CountMinSketchResource>, item: Binary) -> Result<u64, Error> {
+    let sketch = resource.sketch.read()?;
+    Ok(sketch.estimate(item.as_slice()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn cms_merge(
+    a: ResourceArc<CountMinSketchResource>,
+    b: ResourceArc<CountMinSketchResource>,
+) -> Result<ResourceArc<CountMinSketchResource>, Error> {
+    // Copy one side first so both locks are never held at once.
+    let mut merged = {
+        let sketch = a.sketch.read()?;
+        let mut merged = CountMinSketch::new(sketch.width, sketch.depth)?;
+        merged.counters.copy_from_slice(&sketch.counters);
+        merged
+    };
+    {
+        let b_sketch = b.sketch.read()?;
+        if merged.width != b_sketch.width || merged.depth != b_sketch.depth {
+            return Err(Error::IncompatibleFilters);
+        }
+        for (counter, other) in merged.counters.iter_mut().zip(&b_sketch.counters) {
+            *counter = counter.saturating_add(*other);
+        }
+    }
+
+    Ok(ResourceArc::new(CountMinSketchResource {
+        sketch: RwLock::new(merged),
+    }))
+}
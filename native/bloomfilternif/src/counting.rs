@@ -0,0 +1,210 @@
+use crate::error::Error;
+use crate::hasher::double_hash_indexes;
+use crate::memory::{zeroed, Reservation};
+use crate::{random_seed, slot_size, validate_params};
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// A bloom filter with a small counter per slot instead of a single bit, so
+// items can be removed again. Counters saturate at `u8::MAX` and are never
+// decremented once saturated, which keeps removals from causing false negatives.
+struct CountingBloomFilter {
+    counters: Vec<u8>,
+    num_hashes: u32,
+    seed: u128,
+    hasher: DefaultHasher,
+    _memory: Reservation,
+}
+
+impl CountingBloomFilter {
+    fn new(capacity: usize, false_positive_rate: f64, seed: u128) -> Result<Self, Error> {
+        let (num_slots, num_hashes) = slot_size(capacity, false_positive_rate);
+        let (counters, memory) = zeroed(num_slots)?;
+
+        Ok(CountingBloomFilter {
+            counters,
+            num_hashes,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+            _memory: memory,
+        })
+    }
+
+    fn copy(&self) -> Result<Self, Error> {
+        let (mut counters, memory) = zeroed(self.counters.len())?;
+        counters.copy_from_slice(&self.counters);
+        Ok(CountingBloomFilter {
+            counters,
+            num_hashes: self.num_hashes,
+            seed: self.seed,
+            hasher: self.hasher.clone(),
+            _memory: memory,
+        })
+    }
+
+    fn slots(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(item);
+        double_hash_indexes(hash, self.num_hashes, self.counters.len())
+    }
+
+    fn insert(&mut self, item: &[u8]) {
+        for slot in self.slots(item) {
+            self.counters[slot] = self.counters[slot].saturating_add(1);
+        }
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.slots(item).all(|slot| self.counters[slot] > 0)
+    }
+
+    // Every occurrence of an item increments all of its counters, so the
+    // smallest one is an upper bound on how often it was added, as in a
+    // count-min sketch.
+    fn count_estimate(&self, item: &[u8]) -> u8 {
+        self.slots(item)
+            .map(|slot| self.counters[slot])
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn remove(&mut self, item: &[u8]) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        for slot in self.slots(item) {
+            if self.counters[slot] != u8::MAX {
+                self.counters[slot] -= 1;
+            }
+        }
+        true
+    }
+
+    // Filters with the same size, hash count and seed give every item the same
+    // slots, so their counters line up.
+    fn compatible(&self, other: &Self) -> bool {
+        self.counters.len() == other.counters.len()
+            && self.num_hashes == other.num_hashes
+            && self.seed == other.seed
+    }
+
+    // Saturated counters no longer know their true count, so they stay
+    // saturated rather than risk dropping to zero under items still present.
+    fn subtract(&mut self, other: &Self) {
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            if *counter != u8::MAX {
+                *counter = counter.saturating_sub(*other);
+            }
+        }
+    }
+}
+
+pub struct CountingBloomFilterResource {
+    filter: RwLock<CountingBloomFilter>,
+}
+
+impl Resource for CountingBloomFilterResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn counting_new(
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<CountingBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    Ok(ResourceArc::new(CountingBloomFilterResource {
+        filter: RwLock::new(CountingBloomFilter::new(
+            capacity,
+            false_positive_rate,
+            random_seed()?,
+        )?),
+    }))
+}
+
+// Counting filters created with the same seed and parameters map items to the
+// same slots, so they can be combined with `counting_subtract`.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn counting_new_with_seed(
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u128,
+) -> Result<ResourceArc<CountingBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    Ok(ResourceArc::new(CountingBloomFilterResource {
+        filter: RwLock::new(CountingBloomFilter::new(
+            capacity,
+            false_positive_rate,
+            seed,
+        )?),
+    }))
+}
+
+#[rustler::nif]
+fn counting_add(
+    resource: ResourceArc<CountingBloomFilterResource>,
+    item: Binary,
+) -> Result<ResourceArc<CountingBloomFilterResource>, Error> {
+    {
+        let mut filter = resource.filter.write()?;
+        filter.insert(item.as_slice());
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn counting_remove(
+    resource: ResourceArc<CountingBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(filter.remove(item.as_slice()))
+}
+
+#[rustler::nif]
+fn counting_member(
+    resource: ResourceArc<CountingBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif]
+fn counting_count_estimate(
+    resource: ResourceArc<CountingBloomFilterResource>,
+    item: Binary,
+) -> Result<u8, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.count_estimate(item.as_slice()))
+}
+
+#[rustler::nif]
+fn counting_byte_size(resource: ResourceArc<CountingBloomFilterResource>) -> Result<usize, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.counters.len())
+}
+
+// Returns a new filter holding `a`'s counters minus `b`'s, for dropping a
+// bucket of items from a filter that also counted them.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn counting_subtract(
+    a: ResourceArc<CountingBloomFilterResource>,
+    b: ResourceArc<CountingBloomFilterResource>,
+) -> Result<ResourceArc<CountingBloomFilterResource>, Error> {
+    // Copy one side first so both locks are never held at once.
+    let mut difference = a.filter.read()?.copy()?;
+    {
+        let b_filter = b.filter.read()?;
+        if !difference.compatible(&b_filter) {
+            return Err(Error::IncompatibleFilters);
+        }
+        difference.subtract(&b_filter);
+    }
+
+    Ok(ResourceArc::new(CountingBloomFilterResource {
+        filter: RwLock::new(difference),
+    }))
+}
@@ -0,0 +1,198 @@
+use crate::error::Error;
+use crate::memory::{zeroed, Reservation};
+use crate::random_seed;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+// Cuckoo tables start failing inserts above roughly 95% occupancy.
+const MAX_LOAD_FACTOR: f64 = 0.95;
+
+// An empty slot. Fingerprints are never zero.
+const EMPTY: u16 = 0;
+
+type Bucket = [u16; BUCKET_SIZE];
+
+// A cuckoo filter with 16-bit fingerprints and 4-slot buckets, following
+// Fan et al., "Cuckoo Filter: Practically Better Than Bloom". Each item lives
+// in one of two candidate buckets, where either bucket index can be derived
+// from the other and the fingerprint, which is what makes deletion possible.
+struct CuckooFilter {
+    buckets: Vec<Bucket>,
+    hasher: DefaultHasher,
+    // State for picking which fingerprint to evict (xorshift64).
+    rng: u64,
+    // A fingerprint that could not be placed after `MAX_KICKS` relocations.
+    // Keeping it here instead of dropping it avoids false negatives.
+    victim: Option<(usize, u16)>,
+    _memory: Reservation,
+}
+
+impl CuckooFilter {
+    fn new(capacity: usize, seed: u128) -> Result<Self, Error> {
+        let num_buckets = ((capacity as f64 / BUCKET_SIZE as f64 / MAX_LOAD_FACTOR).ceil()
+            as usize)
+            .max(1)
+            .checked_next_power_of_two()
+            .ok_or(Error::InvalidCapacity)?;
+        // Zeroed buckets are all `EMPTY`.
+        let (buckets, memory) = zeroed(num_buckets)?;
+
+        Ok(CuckooFilter {
+            buckets,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+            rng: (seed as u64) | 1,
+            victim: None,
+            _memory: memory,
+        })
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    fn index_and_fingerprint(&self, item: &[u8]) -> (usize, u16) {
+        let hash = self.hasher.hash_one(item);
+        let fingerprint = ((hash >> 48) as u16).max(1);
+        (hash as usize & self.mask(), fingerprint)
+    }
+
+    fn alt_index(&self, index: usize, fingerprint: u16) -> usize {
+        // MurmurHash2 constant, so nearby fingerprints map to distant buckets.
+        let offset = (fingerprint as u64).wrapping_mul(0x5bd1_e995) as usize;
+        (index ^ offset) & self.mask()
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn try_place(&mut self, index: usize, fingerprint: u16) -> bool {
+        match self.buckets[index].iter_mut().find(|slot| **slot == EMPTY) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, item: &[u8]) -> Result<(), Error> {
+        if self.victim.is_some() {
+            return Err(Error::FilterFull);
+        }
+
+        let (i1, fingerprint) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fingerprint);
+        if self.try_place(i1, fingerprint) || self.try_place(i2, fingerprint) {
+            return Ok(());
+        }
+
+        let mut index = if self.next_random() & 1 == 0 { i1 } else { i2 };
+        let mut fingerprint = fingerprint;
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() as usize % BUCKET_SIZE;
+            std::mem::swap(&mut fingerprint, &mut self.buckets[index][slot]);
+            index = self.alt_index(index, fingerprint);
+            if self.try_place(index, fingerprint) {
+                return Ok(());
+            }
+        }
+
+        self.victim = Some((index, fingerprint));
+        Ok(())
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        let (i1, fingerprint) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        self.buckets[i1].contains(&fingerprint)
+            || self.buckets[i2].contains(&fingerprint)
+            || self.victim.is_some_and(|(index, victim)| {
+                victim == fingerprint && (index == i1 || index == i2)
+            })
+    }
+
+    fn delete(&mut self, item: &[u8]) -> bool {
+        let (i1, fingerprint) = self.index_and_fingerprint(item);
+        let i2 = self.alt_index(i1, fingerprint);
+
+        for index in [i1, i2] {
+            if let Some(slot) = self.buckets[index]
+                .iter_mut()
+                .find(|slot| **slot == fingerprint)
+            {
+                *slot = EMPTY;
+                self.reinsert_victim();
+                return true;
+            }
+        }
+        if self
+            .victim
+            .is_some_and(|(index, victim)| victim == fingerprint && (index == i1 || index == i2))
+        {
+            self.victim = None;
+            return true;
+        }
+        false
+    }
+
+    // A deletion frees a slot, which may make room for the stashed victim.
+    fn reinsert_victim(&mut self) {
+        if let Some((index, fingerprint)) = self.victim.take() {
+            let alt = self.alt_index(index, fingerprint);
+            if !self.try_place(index, fingerprint) && !self.try_place(alt, fingerprint) {
+                self.victim = Some((index, fingerprint));
+            }
+        }
+    }
+}
+
+pub struct CuckooFilterResource {
+    filter: RwLock<CuckooFilter>,
+}
+
+impl Resource for CuckooFilterResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn cuckoo_new(capacity: usize) -> Result<ResourceArc<CuckooFilterResource>, Error> {
+    if capacity == 0 {
+        return Err(Error::InvalidCapacity);
+    }
+
+    Ok(ResourceArc::new(CuckooFilterResource {
+        filter: RwLock::new(CuckooFilter::new(capacity, random_seed()?)?),
+    }))
+}
+
+#[rustler::nif]
+fn cuckoo_add(
+    resource: ResourceArc<CuckooFilterResource>,
+    item: Binary,
+) -> Result<ResourceArc<CuckooFilterResource>, Error> {
+    {
+        let mut filter = resource.filter.write()?;
+        filter.insert(item.as_slice())?;
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn cuckoo_member(resource: ResourceArc<CuckooFilterResource>, item: Binary) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif]
+fn cuckoo_delete(resource: ResourceArc<CuckooFilterResource>, item: Binary) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(filter.delete(item.as_slice()))
+}
@@ -0,0 +1,192 @@
+use crate::error::Error;
+use crate::hasher::HashAlgorithm;
+use crate::{hash_shape, BloomFilterResource};
+use rustler::{Binary, Env, OwnedBinary, ResourceArc};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Changes are tracked per block of this many words, so only blocks written
+// since a version need to be scanned and sent.
+const BLOCK_WORDS: usize = 16;
+
+const DIFF_FORMAT_VERSION: u8 = 2;
+
+// version + num_hashes + seed + num_bits + inserted_count + hash algorithm.
+// Version 1 diffs, from filters that all hashed with SipHash-1-3, have no hash
+// algorithm byte.
+const DIFF_HEADER_LEN: usize = 1 + 4 + 16 + 8 + 8 + 1;
+const SIP13_DIFF_FORMAT_VERSION: u8 = 1;
+const SIP13_DIFF_HEADER_LEN: usize = DIFF_HEADER_LEN - 1;
+
+// Each entry is a word index and the word's value, both little-endian u64s.
+const DIFF_ENTRY_LEN: usize = 16;
+
+// Records which blocks of the bit array were written in which version. It is
+// only allocated once `snapshot_version` is first called, so filters that are
+// never diffed pay nothing for it.
+pub struct ChangeLog {
+    // The version new writes are stamped with. Taking a snapshot bumps it.
+    version: AtomicU64,
+    // Diffs can't express cleared bits, so `clear` invalidates older versions.
+    cleared_at: AtomicU64,
+    block_versions: Vec<AtomicU64>,
+}
+
+impl ChangeLog {
+    pub fn new(num_words: usize) -> Self {
+        ChangeLog {
+            version: AtomicU64::new(1),
+            cleared_at: AtomicU64::new(0),
+            block_versions: (0..num_words.div_ceil(BLOCK_WORDS))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    // Must be called after the word is written. The release pairs with the acquire
+    // in `diff_since`, so a diff that sees the stamp also sees the bit.
+    pub fn record(&self, word_index: usize) {
+        let version = self.version.load(Ordering::SeqCst);
+        self.block_versions[word_index / BLOCK_WORDS].fetch_max(version, Ordering::Release);
+    }
+
+    pub fn clear(&self) {
+        self.cleared_at
+            .store(self.version.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    // Versions start at 1. Writes racing with a snapshot may be stamped with
+    // either side of it, which at worst sends a block twice.
+    fn snapshot(&self) -> u64 {
+        self.version.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn check_version(&self, version: u64) -> Result<(), Error> {
+        let issued = version != 0 && version < self.version.load(Ordering::SeqCst);
+        if !issued || version < self.cleared_at.load(Ordering::SeqCst) {
+            return Err(Error::UnknownVersion);
+        }
+        Ok(())
+    }
+}
+
+// Returns a version to later pass to `diff_since`. A replica that copies the
+// filter after calling this can be kept up to date with the diffs since it.
+#[rustler::nif]
+fn snapshot_version(resource: ResourceArc<BloomFilterResource>) -> Result<u64, Error> {
+    let filter = resource.filter.read();
+    let changes = resource
+        .changes
+        .get_or_init(|| ChangeLog::new(filter.num_words()));
+    Ok(changes.snapshot())
+}
+
+// Encodes every set bit in the blocks written since `version`, as the non-zero
+// words of those blocks. Applying it to a copy taken at `version` brings the
+// copy up to date.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn diff_since<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+    version: u64,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read();
+    let changes = resource.changes.get().ok_or(Error::UnknownVersion)?;
+    changes.check_version(version)?;
+
+    let mut entries = Vec::new();
+    for (block, block_version) in changes.block_versions.iter().enumerate() {
+        if block_version.load(Ordering::Acquire) < version {
+            continue;
+        }
+        let start = block * BLOCK_WORDS;
+        let end = (start + BLOCK_WORDS).min(filter.num_words());
+        for index in start..end {
+            let word = filter.word(index).load(Ordering::Relaxed);
+            if word != 0 {
+                entries.push((index as u64, word));
+            }
+        }
+    }
+
+    let (num_bits, num_hashes, seed, hash) = hash_shape(&resource, &filter);
+    let count = resource.inserted_count.load(Ordering::Relaxed);
+
+    let mut binary = OwnedBinary::new(DIFF_HEADER_LEN + entries.len() * DIFF_ENTRY_LEN)
+        .ok_or(Error::AllocationFailed)?;
+    let buf = binary.as_mut_slice();
+    buf[0] = DIFF_FORMAT_VERSION;
+    buf[1..5].copy_from_slice(&num_hashes.to_le_bytes());
+    buf[5..21].copy_from_slice(&seed.to_le_bytes());
+    buf[21..29].copy_from_slice(&(num_bits as u64).to_le_bytes());
+    buf[29..37].copy_from_slice(&(count as u64).to_le_bytes());
+    buf[37] = hash as u8;
+    for (entry, (index, word)) in buf[DIFF_HEADER_LEN..]
+        .chunks_exact_mut(DIFF_ENTRY_LEN)
+        .zip(entries)
+    {
+        entry[..8].copy_from_slice(&index.to_le_bytes());
+        entry[8..].copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(binary.release(env))
+}
+
+// ORs a diff from `diff_since` into the filter and returns the new inserted
+// count, the larger of this filter's and the sender's. The write-ahead log
+// only holds item hashes, so the merged words are not logged and only survive
+// a crash once a snapshot has been saved.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn apply_diff(resource: ResourceArc<BloomFilterResource>, diff: Binary) -> Result<usize, Error> {
+    let buf = diff.as_slice();
+    let header_len = match buf.first() {
+        Some(&DIFF_FORMAT_VERSION) => DIFF_HEADER_LEN,
+        Some(&SIP13_DIFF_FORMAT_VERSION) => SIP13_DIFF_HEADER_LEN,
+        Some(_) => return Err(Error::UnsupportedVersion),
+        None => return Err(Error::InvalidData),
+    };
+    if buf.len() < header_len || !(buf.len() - header_len).is_multiple_of(DIFF_ENTRY_LEN) {
+        return Err(Error::InvalidData);
+    }
+
+    let num_hashes = u32::from_le_bytes(buf[1..5].try_into().unwrap());
+    let seed = u128::from_le_bytes(buf[5..21].try_into().unwrap());
+    let num_bits = u64::from_le_bytes(buf[21..29].try_into().unwrap()) as usize;
+    let count = u64::from_le_bytes(buf[29..37].try_into().unwrap()) as usize;
+    let hash = match header_len {
+        DIFF_HEADER_LEN => HashAlgorithm::from_byte(buf[37])?,
+        _ => HashAlgorithm::Sip13,
+    };
+
+    let filter = resource.filter.read();
+    if hash_shape(&resource, &filter) != (num_bits, num_hashes, seed, hash) {
+        return Err(Error::IncompatibleFilters);
+    }
+
+    let entries: Vec<(usize, u64)> = buf[header_len..]
+        .chunks_exact(DIFF_ENTRY_LEN)
+        .map(|entry| {
+            let index = u64::from_le_bytes(entry[..8].try_into().unwrap()) as usize;
+            let word = u64::from_le_bytes(entry[8..].try_into().unwrap());
+            (index, word)
+        })
+        .collect();
+    // Validate every entry first, so a bad diff leaves the filter untouched.
+    if entries
+        .iter()
+        .any(|&(index, _)| index >= filter.num_words())
+    {
+        return Err(Error::InvalidData);
+    }
+
+    let _write = resource.writes.begin();
+    for (index, word) in entries {
+        filter.word(index).fetch_or(word, Ordering::Relaxed);
+        // Stamped like local writes, so diffs taken from this filter pass on
+        // the bits it received.
+        if let Some(changes) = resource.changes.get() {
+            changes.record(index);
+        }
+    }
+
+    resource.inserted_count.fetch_max(count, Ordering::Relaxed);
+    Ok(resource.inserted_count.load(Ordering::Relaxed))
+}
@@ -0,0 +1,164 @@
+use rustler::{Encoder, Env, Term};
+use std::any::Any;
+use std::io;
+use std::panic::{self, UnwindSafe};
+use std::sync::PoisonError;
+
+mod atoms {
+    rustler::atoms! {
+        invalid_capacity,
+        invalid_false_positive_rate,
+        invalid_growth_factor,
+        invalid_generations,
+        invalid_shards,
+        invalid_width,
+        invalid_depth,
+        invalid_k,
+        invalid_precision,
+        invalid_num_bits,
+        invalid_num_hashes,
+        invalid_compression_level,
+        invalid_chunk_size,
+        invalid_option,
+        invalid_value,
+        invalid_ttl,
+        invalid_cell_bits,
+        invalid_bucket_size,
+        incompatible_filters,
+        filter_full,
+        capacity_exceeded,
+        read_only,
+        construction_failed,
+        lock_poisoned,
+        lock_timeout,
+        memory_budget_exceeded,
+        allocation_failed,
+        seed_unavailable,
+        unsupported_version,
+        unknown_version,
+        invalid_data,
+        corrupt_data,
+        enoent,
+        eacces,
+        eexist,
+        enospc,
+        io_error,
+        panic,
+    }
+}
+
+// Errors returned to Elixir as `{:error, atom}` so callers can match on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidCapacity,
+    InvalidFalsePositiveRate,
+    InvalidGrowthFactor,
+    InvalidGenerations,
+    InvalidShards,
+    InvalidWidth,
+    InvalidDepth,
+    InvalidK,
+    InvalidPrecision,
+    InvalidNumBits,
+    InvalidNumHashes,
+    InvalidCompressionLevel,
+    InvalidChunkSize,
+    InvalidOption,
+    InvalidValue,
+    InvalidTtl,
+    InvalidCellBits,
+    InvalidBucketSize,
+    IncompatibleFilters,
+    FilterFull,
+    CapacityExceeded,
+    ReadOnly,
+    ConstructionFailed,
+    LockPoisoned,
+    LockTimeout,
+    MemoryBudgetExceeded,
+    AllocationFailed,
+    SeedUnavailable,
+    UnsupportedVersion,
+    UnknownVersion,
+    InvalidData,
+    CorruptData,
+    Io(io::ErrorKind),
+    // Encoded as `{:panic, message}`.
+    Panic(String),
+}
+
+impl Encoder for Error {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let atom = match self {
+            Error::InvalidCapacity => atoms::invalid_capacity(),
+            Error::InvalidFalsePositiveRate => atoms::invalid_false_positive_rate(),
+            Error::InvalidGrowthFactor => atoms::invalid_growth_factor(),
+            Error::InvalidGenerations => atoms::invalid_generations(),
+            Error::InvalidShards => atoms::invalid_shards(),
+            Error::InvalidWidth => atoms::invalid_width(),
+            Error::InvalidDepth => atoms::invalid_depth(),
+            Error::InvalidK => atoms::invalid_k(),
+            Error::InvalidPrecision => atoms::invalid_precision(),
+            Error::InvalidNumBits => atoms::invalid_num_bits(),
+            Error::InvalidNumHashes => atoms::invalid_num_hashes(),
+            Error::InvalidCompressionLevel => atoms::invalid_compression_level(),
+            Error::InvalidChunkSize => atoms::invalid_chunk_size(),
+            Error::InvalidOption => atoms::invalid_option(),
+            Error::InvalidValue => atoms::invalid_value(),
+            Error::InvalidTtl => atoms::invalid_ttl(),
+            Error::InvalidCellBits => atoms::invalid_cell_bits(),
+            Error::InvalidBucketSize => atoms::invalid_bucket_size(),
+            Error::IncompatibleFilters => atoms::incompatible_filters(),
+            Error::FilterFull => atoms::filter_full(),
+            Error::CapacityExceeded => atoms::capacity_exceeded(),
+            Error::ReadOnly => atoms::read_only(),
+            Error::ConstructionFailed => atoms::construction_failed(),
+            Error::LockPoisoned => atoms::lock_poisoned(),
+            Error::LockTimeout => atoms::lock_timeout(),
+            Error::MemoryBudgetExceeded => atoms::memory_budget_exceeded(),
+            Error::AllocationFailed => atoms::allocation_failed(),
+            Error::SeedUnavailable => atoms::seed_unavailable(),
+            Error::UnsupportedVersion => atoms::unsupported_version(),
+            Error::UnknownVersion => atoms::unknown_version(),
+            Error::InvalidData => atoms::invalid_data(),
+            Error::CorruptData => atoms::corrupt_data(),
+            // The common file errors use the same atoms as `File`.
+            Error::Io(io::ErrorKind::NotFound) => atoms::enoent(),
+            Error::Io(io::ErrorKind::PermissionDenied) => atoms::eacces(),
+            Error::Io(io::ErrorKind::AlreadyExists) => atoms::eexist(),
+            Error::Io(io::ErrorKind::StorageFull) => atoms::enospc(),
+            Error::Io(_) => atoms::io_error(),
+            Error::Panic(message) => return (atoms::panic(), message.as_str()).encode(env),
+        };
+        atom.encode(env)
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Error::LockPoisoned
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error.kind())
+    }
+}
+
+// Runs `f`, turning a panic into an error instead of an exception raised in
+// the calling process. For NIFs that decode untrusted input or build filters
+// from caller-chosen parameters, where a panic is most likely.
+pub fn catch_panic<T>(f: impl FnOnce() -> Result<T, Error> + UnwindSafe) -> Result<T, Error> {
+    panic::catch_unwind(f).unwrap_or_else(|payload| Err(Error::Panic(panic_message(payload))))
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::{catch_panic, Error};
+use crate::hasher::HashAlgorithm;
+use crate::segmented::Filter;
+use crate::{random_seed, validate_params, BloomFilterResource};
+use rustler::ResourceArc;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Large enough that reading a big file takes few system calls.
+const READ_BUFFER_LEN: usize = 1024 * 1024;
+
+// Records read before each parallel insert in `new_from_file`.
+const PARALLEL_BATCH_LEN: usize = 64 * 1024;
+
+// Distinguishes temporary files when several saves to the same path overlap.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Writes the serialized filter (the same bytes `serialize` returns) to `path`.
+// The data goes to a temporary file next to `path` first and is renamed over
+// it once fully synced, so readers never see a partially written filter.
+#[rustler::nif(schedule = "DirtyIo")]
+fn save(resource: ResourceArc<BloomFilterResource>, path: String) -> Result<(), Error> {
+    Ok(save_to(&resource, &path)?)
+}
+
+pub(crate) fn save_to(resource: &BloomFilterResource, path: &str) -> io::Result<()> {
+    let temp_path = format!(
+        "{}.tmp.{}.{}",
+        path,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let result = {
+        let filter = resource.filter.read();
+        write_file(&temp_path, resource, &filter)
+    }; // Lock is dropped here
+
+    let result = result.and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_file(path: &str, resource: &BloomFilterResource, filter: &Filter) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    resource.encode_to(filter, &mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn load(path: String) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let buf = fs::read(path)?;
+    let resource = catch_panic(|| BloomFilterResource::decode(&buf))?;
+    Ok(ResourceArc::new(resource))
+}
+
+// Builds a filter sized for `capacity` and `false_positive_rate` from the
+// records of the file at `path`, separated by `delimiter`. Records are inserted
+// as raw bytes without the delimiter, the way the `add` NIF inserts binaries.
+// Empty records are skipped, so blank lines and a trailing delimiter add
+// nothing. With `parallel`, records are read in batches that are hashed and
+// inserted across rayon's thread pool while the file stays on this thread.
+#[rustler::nif(schedule = "DirtyIo")]
+fn new_from_file(
+    path: String,
+    capacity: usize,
+    false_positive_rate: f64,
+    delimiter: u8,
+    parallel: bool,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    let seed = random_seed()?;
+
+    let mut reader = BufReader::with_capacity(READ_BUFFER_LEN, File::open(path)?);
+    let resource = catch_panic(|| {
+        BloomFilterResource::new(
+            capacity,
+            false_positive_rate,
+            seed,
+            HashAlgorithm::default(),
+        )
+    })?;
+
+    if parallel {
+        let mut batch = Vec::with_capacity(PARALLEL_BATCH_LEN);
+        while let Some(record) = read_record(&mut reader, delimiter, Vec::new())? {
+            batch.push(record);
+            if batch.len() == PARALLEL_BATCH_LEN {
+                insert_batch(&resource, &mut batch)?;
+            }
+        }
+        insert_batch(&resource, &mut batch)?;
+    } else {
+        let mut record = Vec::new();
+        while let Some(read) = read_record(&mut reader, delimiter, record)? {
+            resource.insert(&read)?;
+            record = read;
+        }
+    }
+    Ok(ResourceArc::new(resource))
+}
+
+// Reads the next non-empty record into `buf`, reusing its allocation, or
+// returns `None` at the end of the file.
+fn read_record(
+    reader: &mut impl BufRead,
+    delimiter: u8,
+    mut buf: Vec<u8>,
+) -> io::Result<Option<Vec<u8>>> {
+    loop {
+        buf.clear();
+        if reader.read_until(delimiter, &mut buf)? == 0 {
+            return Ok(None);
+        }
+        if buf.last() == Some(&delimiter) {
+            buf.pop();
+        }
+        if !buf.is_empty() {
+            return Ok(Some(buf));
+        }
+    }
+}
+
+fn insert_batch(resource: &BloomFilterResource, batch: &mut Vec<Vec<u8>>) -> Result<(), Error> {
+    let items: Vec<&[u8]> = batch.iter().map(Vec::as_slice).collect();
+    resource.insert_many_parallel(&items)?;
+    batch.clear();
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::Reservation;
+use crate::{optimal_words, random_seed, validate_params};
+use fastbloom::AtomicBloomFilter;
+use rustler::{Binary, NifMap, Resource, ResourceArc};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+struct Tenant {
+    filter: AtomicBloomFilter,
+    inserted_count: AtomicUsize,
+    _memory: Reservation,
+}
+
+// Many small bloom filters, one per tenant, behind a single resource. A
+// tenant's filter is created by its first insert, with the capacity and false
+// positive rate the map was created with. Filters set bits atomically, so
+// inserts for tenants that already exist share the read lock, and only
+// creating or dropping a tenant takes the write lock.
+pub struct FilterMapResource {
+    tenants: RwLock<HashMap<Vec<u8>, Tenant>>,
+    capacity: usize,
+    false_positive_rate: f64,
+    // Shared by every tenant's filter.
+    seed: u128,
+}
+
+impl Resource for FilterMapResource {}
+
+impl FilterMapResource {
+    fn new_tenant(&self) -> Result<Tenant, Error> {
+        let (words, num_hashes, memory) = optimal_words(self.capacity, self.false_positive_rate)?;
+        Ok(Tenant {
+            filter: AtomicBloomFilter::from_vec(words)
+                .seed(&self.seed)
+                .hashes(num_hashes),
+            inserted_count: AtomicUsize::new(0),
+            _memory: memory,
+        })
+    }
+}
+
+// Returns whether the item was new, like `add`.
+fn insert(tenant: &Tenant, item: &[u8]) -> bool {
+    let previously_contained = tenant.filter.insert(item);
+    if !previously_contained {
+        tenant.inserted_count.fetch_add(1, Ordering::Relaxed);
+    }
+    !previously_contained
+}
+
+// `capacity` and `false_positive_rate` apply to each tenant's filter.
+#[rustler::nif]
+fn map_new(
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<FilterMapResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    Ok(ResourceArc::new(FilterMapResource {
+        tenants: RwLock::new(HashMap::new()),
+        capacity,
+        false_positive_rate,
+        seed: random_seed()?,
+    }))
+}
+
+#[rustler::nif]
+fn map_add(
+    resource: ResourceArc<FilterMapResource>,
+    tenant: Binary,
+    item: Item,
+) -> Result<bool, Error> {
+    if let Some(existing) = resource.tenants.read()?.get(tenant.as_slice()) {
+        return Ok(insert(existing, item.as_slice()));
+    }
+
+    // Another insert may have created the tenant since the read lock was let go.
+    let mut tenants = resource.tenants.write()?;
+    let created = match tenants.entry(tenant.as_slice().to_vec()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(resource.new_tenant()?),
+    };
+    Ok(insert(created, item.as_slice()))
+}
+
+// Unknown tenants have no items.
+#[rustler::nif]
+fn map_member(
+    resource: ResourceArc<FilterMapResource>,
+    tenant: Binary,
+    item: Item,
+) -> Result<bool, Error> {
+    let tenants = resource.tenants.read()?;
+    Ok(tenants
+        .get(tenant.as_slice())
+        .is_some_and(|existing| existing.filter.contains(item.as_slice())))
+}
+
+// Frees the tenant's filter. Returns whether the tenant existed.
+#[rustler::nif]
+fn map_drop_tenant(
+    resource: ResourceArc<FilterMapResource>,
+    tenant: Binary,
+) -> Result<bool, Error> {
+    Ok(resource
+        .tenants
+        .write()?
+        .remove(tenant.as_slice())
+        .is_some())
+}
+
+#[derive(NifMap)]
+struct MapStats {
+    tenants: usize,
+    capacity: usize,
+    false_positive_rate: f64,
+    inserted_count: usize,
+    memory_bytes: usize,
+}
+
+// `inserted_count` is summed over the tenants, and `memory_bytes` is the size
+// of their bit arrays.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn map_stats(resource: ResourceArc<FilterMapResource>) -> Result<MapStats, Error> {
+    let tenants = resource.tenants.read()?;
+    Ok(MapStats {
+        tenants: tenants.len(),
+        capacity: resource.capacity,
+        false_positive_rate: resource.false_positive_rate,
+        inserted_count: tenants
+            .values()
+            .map(|tenant| tenant.inserted_count.load(Ordering::Relaxed))
+            .sum(),
+        memory_bytes: tenants
+            .values()
+            .map(|tenant| tenant.filter.as_slice().len() * 8)
+            .sum(),
+    })
+}
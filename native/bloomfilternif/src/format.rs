@@ -0,0 +1,334 @@
+use crate::error::Error;
+use crate::hasher::HashAlgorithm;
+use crate::memory::Reservation;
+use crate::segmented::Filter;
+use crate::BloomFilterResource;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+
+// The serialized form of a `BloomFilterResource`, as returned by `serialize`
+// and written by `save`. All multi-byte fields are little-endian.
+//
+// Version 5, written by this version:
+//
+//   offset  size  field
+//        0     4  magic, "BFEX"
+//        4     1  format version, 5
+//        5     4  num_hashes (u32)
+//        9     8  capacity (u64)
+//       17     8  false_positive_rate (f64)
+//       25     8  inserted_count (u64)
+//       33    16  seed (u128)
+//       49     8  num_bits (u64), a non-zero multiple of 64
+//       57     1  hash algorithm: 0 for SipHash-1-3, 1 for xxh3, 2 for wyhash
+//       58     8  payload_len (u64), num_bits / 8
+//       66     n  payload: the bit array as num_bits / 64 u64 words
+//     66+n     4  CRC32 of every byte before it
+//
+// `serialize_compressed` writes the magic, version 2 and a compression
+// algorithm byte (1 for zstd), followed by the compressed bytes of a version 5
+// filter.
+//
+// `deserialize` accepts every version written by earlier releases, all of
+// which hash with SipHash-1-3:
+//
+//   1  the version 5 fields from num_hashes to num_bits, then the payload, with
+//      no magic, hash algorithm, payload_len or checksum
+//   2  a version byte and algorithm byte, then a compressed version 3 or 4
+//      filter
+//   3  version 4 without the magic
+//   4  version 5 without the hash algorithm
+//
+// New versions must keep the magic and version at the start, and reading every
+// version listed here must keep working.
+pub(crate) const MAGIC: &[u8; 4] = b"BFEX";
+pub(crate) const FORMAT_VERSION: u8 = 5;
+const SIP13_FORMAT_VERSION: u8 = 4;
+const UNFRAMED_FORMAT_VERSION: u8 = 3;
+const COMPRESSED_FORMAT_VERSION: u8 = 2;
+const LEGACY_FORMAT_VERSION: u8 = 1;
+const COMPRESSION_ZSTD: u8 = 1;
+
+// num_hashes + capacity + false_positive_rate + inserted_count + seed + num_bits
+const PARAMS_LEN: usize = 4 + 8 + 8 + 8 + 16 + 8;
+
+// magic + version + params + hash algorithm + payload_len
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 1 + PARAMS_LEN + 1 + 8;
+pub(crate) const CHECKSUM_LEN: usize = 4;
+
+// Words are converted to bytes in chunks of this size while being written.
+const WRITE_CHUNK_LEN: usize = 64 * 1024;
+
+impl BloomFilterResource {
+    pub(crate) fn encode_header(&self, filter: &Filter) -> [u8; HEADER_LEN] {
+        let count = self.inserted_count.load(Ordering::Relaxed);
+        let payload_len = filter.num_words() * 8;
+
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4] = FORMAT_VERSION;
+        header[5..9].copy_from_slice(&filter.num_hashes().to_le_bytes());
+        header[9..17].copy_from_slice(&(self.capacity as u64).to_le_bytes());
+        header[17..25].copy_from_slice(&self.false_positive_rate.to_le_bytes());
+        header[25..33].copy_from_slice(&(count as u64).to_le_bytes());
+        header[33..49].copy_from_slice(&self.seed.to_le_bytes());
+        header[49..57].copy_from_slice(&(filter.num_bits() as u64).to_le_bytes());
+        header[57] = self.hash as u8;
+        header[58..66].copy_from_slice(&(payload_len as u64).to_le_bytes());
+        header
+    }
+
+    pub(crate) fn encoded_len(filter: &Filter) -> usize {
+        HEADER_LEN + filter.num_words() * 8 + CHECKSUM_LEN
+    }
+
+    // Writes the header, the bit array and the checksum to `out`.
+    pub(crate) fn encode_to(&self, filter: &Filter, mut out: impl Write) -> std::io::Result<()> {
+        let mut checksum = crc32fast::Hasher::new();
+
+        let header = self.encode_header(filter);
+        checksum.update(&header);
+        out.write_all(&header)?;
+
+        let mut chunk = Vec::with_capacity(WRITE_CHUNK_LEN);
+        let mut words = filter.words().peekable();
+        while words.peek().is_some() {
+            chunk.clear();
+            chunk.extend(
+                words
+                    .by_ref()
+                    .take(WRITE_CHUNK_LEN / 8)
+                    .flat_map(|word| word.load(Ordering::Relaxed).to_le_bytes()),
+            );
+            checksum.update(&chunk);
+            out.write_all(&chunk)?;
+        }
+
+        out.write_all(&checksum.finalize().to_le_bytes())
+    }
+
+    // Writes a compressed filter for `serialize_compressed`.
+    pub(crate) fn encode_compressed(
+        &self,
+        filter: &Filter,
+        level: i32,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut out = MAGIC.to_vec();
+        out.extend([COMPRESSED_FORMAT_VERSION, COMPRESSION_ZSTD]);
+
+        let mut encoder = zstd::Encoder::new(out, level)?;
+        self.encode_to(filter, &mut encoder)?;
+        encoder.finish()
+    }
+
+    pub(crate) fn decode(buf: &[u8]) -> Result<Self, Error> {
+        let buf = decompressed(buf)?;
+        let encoded = Encoded::parse(&buf)?;
+        let memory = Reservation::new(encoded.num_bits / 8)?;
+
+        let filter = Filter::from_words(
+            encoded.words(),
+            encoded.hash,
+            encoded.seed,
+            encoded.num_hashes,
+        )?;
+
+        Ok(Self::from_filter(
+            filter,
+            memory,
+            encoded.capacity,
+            encoded.false_positive_rate,
+            encoded.seed,
+            encoded.hash,
+            encoded.inserted_count,
+        ))
+    }
+}
+
+// Unwraps a compressed filter, or returns an uncompressed one as-is.
+pub(crate) fn decompressed(buf: &[u8]) -> Result<Decompressed<'_>, Error> {
+    match buf.strip_prefix(MAGIC).unwrap_or(buf) {
+        [COMPRESSED_FORMAT_VERSION, COMPRESSION_ZSTD, compressed @ ..] => decompress(compressed),
+        [COMPRESSED_FORMAT_VERSION, ..] => Err(Error::UnsupportedVersion),
+        _ => Ok(Decompressed {
+            buf: Cow::Borrowed(buf),
+            _memory: None,
+        }),
+    }
+}
+
+// An uncompressed serialized filter. A decompressed one holds its share of
+// the memory budget until it is dropped.
+pub(crate) struct Decompressed<'a> {
+    buf: Cow<'a, [u8]>,
+    _memory: Option<Reservation>,
+}
+
+impl Deref for Decompressed<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+// The compressed bytes are untrusted and may expand to far more than any
+// filter, so the header is read and checked first, the payload it describes is
+// counted against the memory budget, and nothing past the length it gives is
+// decompressed.
+fn decompress(compressed: &[u8]) -> Result<Decompressed<'static>, Error> {
+    let mut decoder = zstd::Decoder::with_buffer(compressed).map_err(|_| Error::CorruptData)?;
+
+    let mut buf = vec![0; MAGIC.len() + 1];
+    decoder
+        .read_exact(&mut buf)
+        .map_err(|_| Error::CorruptData)?;
+    let (magic_len, extra_len) = match (buf.strip_prefix(MAGIC), buf[0]) {
+        (Some([FORMAT_VERSION]), _) => (MAGIC.len(), 1),
+        (Some([SIP13_FORMAT_VERSION]), _) => (MAGIC.len(), 0),
+        (None, UNFRAMED_FORMAT_VERSION) => (0, 0),
+        _ => return Err(Error::UnsupportedVersion),
+    };
+
+    let header_len = framed_header_len(magic_len, extra_len);
+    let prefix_len = buf.len();
+    buf.resize(header_len, 0);
+    decoder
+        .read_exact(&mut buf[prefix_len..])
+        .map_err(|_| Error::CorruptData)?;
+    let params = &buf[magic_len + 1..];
+    let num_bits = u64::from_le_bytes(params[44..52].try_into().unwrap());
+    let payload_len = u64::from_le_bytes(buf[header_len - 8..].try_into().unwrap());
+    if payload_len.checked_mul(8) != Some(num_bits) {
+        return Err(Error::InvalidData);
+    }
+
+    let payload_len = usize::try_from(payload_len).map_err(|_| Error::InvalidData)?;
+    let memory = Reservation::new(payload_len)?;
+    // One byte more than the filter can hold shows whether the data runs past it.
+    let rest_len = payload_len + CHECKSUM_LEN + 1;
+    buf.try_reserve_exact(rest_len)
+        .map_err(|_| Error::AllocationFailed)?;
+    decoder
+        .take(rest_len as u64)
+        .read_to_end(&mut buf)
+        .map_err(|_| Error::CorruptData)?;
+    if buf.len() == header_len + rest_len {
+        return Err(Error::CorruptData);
+    }
+
+    Ok(Decompressed {
+        buf: Cow::Owned(buf),
+        _memory: Some(memory),
+    })
+}
+
+// The fields of an uncompressed serialized filter, validated but with the bit
+// array still in its encoded form.
+pub(crate) struct Encoded<'a> {
+    pub(crate) num_hashes: u32,
+    pub(crate) capacity: usize,
+    pub(crate) false_positive_rate: f64,
+    pub(crate) inserted_count: usize,
+    pub(crate) seed: u128,
+    pub(crate) num_bits: usize,
+    pub(crate) hash: HashAlgorithm,
+    payload: &'a [u8],
+}
+
+impl<'a> Encoded<'a> {
+    pub(crate) fn parse(buf: &'a [u8]) -> Result<Self, Error> {
+        let (magic_len, version) = match buf.strip_prefix(MAGIC) {
+            Some([version, ..]) => (MAGIC.len(), *version),
+            Some([]) => return Err(Error::InvalidData),
+            None => (0, *buf.first().ok_or(Error::InvalidData)?),
+        };
+
+        let (params, hash, payload) = match (magic_len > 0, version) {
+            (true, FORMAT_VERSION) => {
+                let (params, payload) = framed(buf, magic_len, 1)?;
+                let hash = HashAlgorithm::from_byte(params[PARAMS_LEN])?;
+                (params, hash, payload)
+            }
+            (true, SIP13_FORMAT_VERSION) | (false, UNFRAMED_FORMAT_VERSION) => {
+                let (params, payload) = framed(buf, magic_len, 0)?;
+                (params, HashAlgorithm::Sip13, payload)
+            }
+            (false, LEGACY_FORMAT_VERSION) => {
+                if buf.len() < 1 + PARAMS_LEN {
+                    return Err(Error::InvalidData);
+                }
+                let (header, payload) = buf.split_at(1 + PARAMS_LEN);
+                (&header[1..], HashAlgorithm::Sip13, payload)
+            }
+            _ => return Err(Error::UnsupportedVersion),
+        };
+
+        // Every version shares these fields, so the slices always have the right length.
+        // The sizes are u64 whatever the platform, so they are checked to fit a usize.
+        let size = |bytes: &[u8]| {
+            usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap()))
+                .map_err(|_| Error::InvalidData)
+        };
+        let encoded = Encoded {
+            num_hashes: u32::from_le_bytes(params[0..4].try_into().unwrap()),
+            capacity: size(&params[4..12])?,
+            false_positive_rate: f64::from_le_bytes(params[12..20].try_into().unwrap()),
+            inserted_count: size(&params[20..28])?,
+            seed: u128::from_le_bytes(params[28..44].try_into().unwrap()),
+            num_bits: size(&params[44..52])?,
+            hash,
+            payload,
+        };
+
+        if encoded.num_bits == 0 || !encoded.num_bits.is_multiple_of(64) {
+            return Err(Error::InvalidData);
+        }
+        if payload.len() != encoded.num_bits / 8 {
+            return Err(Error::InvalidData);
+        }
+        if encoded.num_hashes == 0 {
+            return Err(Error::InvalidData);
+        }
+        Ok(encoded)
+    }
+
+    // The parameters that decide which bits an item sets, as `hash_shape`
+    // returns them.
+    pub(crate) fn hash_shape(&self) -> (usize, u32, u128, HashAlgorithm) {
+        (self.num_bits, self.num_hashes, self.seed, self.hash)
+    }
+
+    pub(crate) fn words(&self) -> impl ExactSizeIterator<Item = u64> + 'a {
+        self.payload
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+    }
+}
+
+// Splits a filter with a length field and checksum into the bytes after its
+// version, up to the length field, and its payload. `extra_len` bytes follow
+// the params.
+fn framed(buf: &[u8], magic_len: usize, extra_len: usize) -> Result<(&[u8], &[u8]), Error> {
+    let header_len = framed_header_len(magic_len, extra_len);
+    if buf.len() < header_len + CHECKSUM_LEN {
+        return Err(Error::CorruptData);
+    }
+    let (body, checksum) = buf.split_at(buf.len() - CHECKSUM_LEN);
+    if crc32fast::hash(body).to_le_bytes() != checksum {
+        return Err(Error::CorruptData);
+    }
+    let (header, payload) = body.split_at(header_len);
+    let payload_len = u64::from_le_bytes(header[header_len - 8..].try_into().unwrap());
+    if payload.len() as u64 != payload_len {
+        return Err(Error::CorruptData);
+    }
+    Ok((&header[magic_len + 1..header_len - 8], payload))
+}
+
+// The length of a framed filter's header, up to and including its length field.
+fn framed_header_len(magic_len: usize, extra_len: usize) -> usize {
+    magic_len + 1 + PARAMS_LEN + extra_len + 8
+}
@@ -0,0 +1,75 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::Reservation;
+use crate::segmented::Filter;
+use crate::BloomFilterResource;
+use rustler::{Resource, ResourceArc, Term};
+use std::sync::atomic::Ordering;
+
+// An immutable copy of a bloom filter. Nothing can change its bits after it is
+// built, so lookups read them directly without taking a lock, and there are no
+// NIFs to add items to it.
+pub struct FrozenBloomFilterResource {
+    filter: Filter,
+    _memory: Reservation,
+}
+
+impl Resource for FrozenBloomFilterResource {}
+
+// A copy of the bits of `resource` and its inserted count as of the same
+// write, like `consistent_counts`, falling back to the write lock if inserts
+// overlap every attempt.
+fn snapshot(resource: &BloomFilterResource) -> Result<(Filter, usize), Error> {
+    {
+        let filter = resource.filter.read();
+        let read = || {
+            resource
+                .copy_filter(&filter)
+                .map(|copy| (copy, resource.inserted_count.load(Ordering::Relaxed)))
+        };
+        if let Some((snapshot, _)) = resource.writes.read(read) {
+            return snapshot;
+        }
+    }
+    let filter = resource.filter.write();
+    Ok((
+        resource.copy_filter(&filter)?,
+        resource.inserted_count.load(Ordering::Relaxed),
+    ))
+}
+
+// Copies the bits, so the frozen filter is a snapshot and later inserts into
+// `resource` don't show up in it. It hashes with the same algorithm and seed
+// as `resource`, so it finds the same bits. Returns the inserted count the
+// bits were copied at alongside it.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn freeze(
+    resource: ResourceArc<BloomFilterResource>,
+) -> Result<(ResourceArc<FrozenBloomFilterResource>, usize), Error> {
+    let memory = Reservation::new(resource.filter.read().num_words() * 8)?;
+    let (filter, inserted_count) = snapshot(&resource)?;
+
+    let frozen = ResourceArc::new(FrozenBloomFilterResource {
+        filter,
+        _memory: memory,
+    });
+    Ok((frozen, inserted_count))
+}
+
+// Hashes the same bytes as `member_term`, so a frozen filter answers exactly
+// like the filter it was frozen from.
+#[rustler::nif]
+fn frozen_member(resource: ResourceArc<FrozenBloomFilterResource>, term: Term) -> bool {
+    resource.filter.contains(term.to_binary().as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn frozen_member_many(
+    resource: ResourceArc<FrozenBloomFilterResource>,
+    items: Vec<Item>,
+) -> Vec<bool> {
+    items
+        .iter()
+        .map(|item| resource.filter.contains(item.as_slice()))
+        .collect()
+}
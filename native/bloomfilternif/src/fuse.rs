@@ -0,0 +1,140 @@
+use crate::error::Error;
+use crate::random_seed;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use xorf::{BinaryFuse16, Filter};
+
+// Version byte written at the start of every serialized fuse filter.
+const FUSE_FORMAT_VERSION: u8 = 1;
+
+// version + hasher seed
+const FUSE_HEADER_LEN: usize = 1 + 16;
+
+// Fixed-width integers keep each fingerprint at exactly two bytes.
+fn bincode_config() -> impl bincode::config::Config {
+    bincode::config::standard().with_fixed_int_encoding()
+}
+
+// With fixed-width integers a `BinaryFuse16` is encoded as its hash seed, then
+// `segment_length`, `segment_length_mask` and `segment_count_length`, then the
+// fingerprint count and the fingerprints.
+const FUSE_FIELDS_LEN: usize = 8 + 4 + 4 + 4;
+const FUSE_FINGERPRINTS_OFFSET: usize = FUSE_FIELDS_LEN + 8;
+
+// Checks an encoded filter before decoding it. bincode allocates the
+// fingerprints from the untrusted count up front, so the count must match the
+// bytes actually present, and lookups index the fingerprints from the segment
+// fields, so those must describe the fingerprints' layout: power-of-two
+// segments, a whole number of them, and two segments of padding at the end.
+fn check_payload(payload: &[u8]) -> Result<(), Error> {
+    if payload.len() < FUSE_FINGERPRINTS_OFFSET {
+        return Err(Error::InvalidData);
+    }
+    let field = |offset: usize| u32::from_le_bytes(payload[offset..offset + 4].try_into().unwrap());
+    let segment_length = field(8) as u64;
+    let segment_length_mask = field(12) as u64;
+    let segment_count_length = field(16) as u64;
+    let count = u64::from_le_bytes(
+        payload[FUSE_FIELDS_LEN..FUSE_FINGERPRINTS_OFFSET]
+            .try_into()
+            .unwrap(),
+    );
+
+    let fingerprint_bytes = (payload.len() - FUSE_FINGERPRINTS_OFFSET) as u64;
+    let consistent = count.checked_mul(2) == Some(fingerprint_bytes)
+        && segment_length.is_power_of_two()
+        && segment_length_mask == segment_length - 1
+        && segment_count_length >= segment_length
+        && segment_count_length.is_multiple_of(segment_length)
+        && count == segment_count_length + 2 * segment_length
+        && count <= u32::MAX as u64;
+    if !consistent {
+        return Err(Error::InvalidData);
+    }
+    Ok(())
+}
+
+// An immutable binary fuse filter with 16-bit fingerprints (false positive rate
+// around 0.0015% at a little over 18 bits per item). It is built once from the full set
+// of items and never modified, so lookups need no locking.
+pub struct FuseFilterResource {
+    filter: BinaryFuse16,
+    seed: u128,
+    hasher: DefaultHasher,
+}
+
+impl Resource for FuseFilterResource {}
+
+impl FuseFilterResource {
+    fn new(filter: BinaryFuse16, seed: u128) -> Self {
+        FuseFilterResource {
+            filter,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+        }
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn fuse_build(items: Vec<Binary>) -> Result<ResourceArc<FuseFilterResource>, Error> {
+    let seed = random_seed()?;
+    let hasher = DefaultHasher::seeded(&seed.to_be_bytes());
+
+    // Construction fails on duplicate keys, so dedupe the hashes up front.
+    let mut keys: Vec<u64> = items
+        .iter()
+        .map(|item| hasher.hash_one(item.as_slice()))
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    let filter = BinaryFuse16::try_from(&keys).map_err(|_| Error::ConstructionFailed)?;
+    Ok(ResourceArc::new(FuseFilterResource::new(filter, seed)))
+}
+
+#[rustler::nif]
+fn fuse_member(resource: ResourceArc<FuseFilterResource>, item: Binary) -> bool {
+    let key = resource.hasher.hash_one(item.as_slice());
+    resource.filter.contains(&key)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn fuse_serialize<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<FuseFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let payload = bincode::encode_to_vec(&resource.filter, bincode_config())
+        .map_err(|_| Error::InvalidData)?;
+
+    let mut binary =
+        OwnedBinary::new(FUSE_HEADER_LEN + payload.len()).ok_or(Error::AllocationFailed)?;
+    let buf = binary.as_mut_slice();
+    buf[0] = FUSE_FORMAT_VERSION;
+    buf[1..FUSE_HEADER_LEN].copy_from_slice(&resource.seed.to_le_bytes());
+    buf[FUSE_HEADER_LEN..].copy_from_slice(&payload);
+
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn fuse_deserialize(binary: Binary) -> Result<ResourceArc<FuseFilterResource>, Error> {
+    let buf = binary.as_slice();
+    if buf.len() < FUSE_HEADER_LEN {
+        return Err(Error::InvalidData);
+    }
+    if buf[0] != FUSE_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion);
+    }
+
+    let seed = u128::from_le_bytes(buf[1..FUSE_HEADER_LEN].try_into().unwrap());
+    let payload = &buf[FUSE_HEADER_LEN..];
+    check_payload(payload)?;
+    let (filter, read) =
+        bincode::decode_from_slice(payload, bincode_config()).map_err(|_| Error::InvalidData)?;
+    if read != payload.len() {
+        return Err(Error::InvalidData);
+    }
+
+    Ok(ResourceArc::new(FuseFilterResource::new(filter, seed)))
+}
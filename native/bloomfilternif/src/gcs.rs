@@ -0,0 +1,272 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::random_seed;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+
+// Version byte written at the start of every serialized set.
+const GCS_FORMAT_VERSION: u8 = 1;
+
+// version + rice_bits + count + hasher seed
+const GCS_HEADER_LEN: usize = 1 + 1 + 8 + 16;
+
+// Every item is hashed into [0, count << rice_bits), so this keeps the range
+// within a u64 for up to 2^32 items.
+const MAX_RICE_BITS: u32 = 32;
+
+// A Golomb-coded set, as used for BIP-158 block filters: items are hashed
+// uniformly into [0, n * 2^P), the hashes are sorted, and the gaps between
+// them are written with Golomb-Rice coding using P remainder bits. That takes
+// about P + 1.5 bits per item for a false positive rate of 2^-P, less than a
+// bloom filter needs, at the cost of lookups that decode the set from the
+// start. It is built once from every item and never modified.
+pub struct GcsResource {
+    data: Vec<u8>,
+    count: u64,
+    rice_bits: u8,
+    seed: u128,
+    hasher: DefaultHasher,
+}
+
+impl Resource for GcsResource {}
+
+impl GcsResource {
+    fn new(data: Vec<u8>, count: u64, rice_bits: u8, seed: u128) -> Self {
+        GcsResource {
+            data,
+            count,
+            rice_bits,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+        }
+    }
+
+    // Maps the item's hash onto [0, count << rice_bits) by multiplying rather
+    // than with a modulo, as BIP-158 does.
+    fn hash_to_range(&self, item: &[u8]) -> u64 {
+        let range = self.count << self.rice_bits;
+        ((self.hasher.hash_one(item) as u128 * range as u128) >> 64) as u64
+    }
+
+    // Decodes the sorted hashes one by one, stopping early when `visit`
+    // returns false.
+    fn for_each_hash(&self, mut visit: impl FnMut(u64) -> bool) {
+        let mut reader = BitReader::new(&self.data);
+        let mut value = 0u64;
+        for _ in 0..self.count {
+            let Some(delta) = reader.read_rice(self.rice_bits) else {
+                return;
+            };
+            value = value.wrapping_add(delta);
+            if !visit(value) {
+                return;
+            }
+        }
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        if self.count == 0 {
+            return false;
+        }
+        let target = self.hash_to_range(item);
+        let mut found = false;
+        self.for_each_hash(|value| {
+            found = value == target;
+            value < target
+        });
+        found
+    }
+
+    // Sorts the targets so the whole batch is answered in a single pass.
+    fn contains_many(&self, items: &[Item]) -> Vec<bool> {
+        let mut results = vec![false; items.len()];
+        if self.count == 0 {
+            return results;
+        }
+
+        let mut targets: Vec<(u64, usize)> = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (self.hash_to_range(item.as_slice()), i))
+            .collect();
+        targets.sort_unstable();
+
+        let mut next = 0;
+        self.for_each_hash(|value| {
+            while next < targets.len() && targets[next].0 < value {
+                next += 1;
+            }
+            while next < targets.len() && targets[next].0 == value {
+                results[targets[next].1] = true;
+                next += 1;
+            }
+            next < targets.len()
+        });
+        results
+    }
+}
+
+// Bits are written most significant first, as in BIP-158.
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    // The quotient in unary, terminated by a zero, then the remainder.
+    fn write_rice(&mut self, value: u64, rice_bits: u8) {
+        for _ in 0..value >> rice_bits {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for i in (0..rice_bits).rev() {
+            self.write_bit(value >> i & 1 == 1);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = byte & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn read_rice(&mut self, rice_bits: u8) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut value = quotient;
+        for _ in 0..rice_bits {
+            value = value << 1 | self.read_bit()? as u64;
+        }
+        Some(value)
+    }
+}
+
+// The set gets P = ceil(log2(1 / false_positive_rate)) remainder bits, so its
+// false positive rate is at most the one requested.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn gcs_build(
+    items: Vec<Item>,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<GcsResource>, Error> {
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+    let rice_bits = (1.0 / false_positive_rate).log2().ceil().max(1.0) as u32;
+    if rice_bits > MAX_RICE_BITS {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+
+    // Duplicates would only add empty gaps, and `count` sizes the hash range.
+    let mut items: Vec<&[u8]> = items.iter().map(|item| item.as_slice()).collect();
+    items.sort_unstable();
+    items.dedup();
+    if items.len() as u64 > u64::MAX >> rice_bits {
+        return Err(Error::CapacityExceeded);
+    }
+
+    let gcs = GcsResource::new(
+        Vec::new(),
+        items.len() as u64,
+        rice_bits as u8,
+        random_seed()?,
+    );
+    let mut hashes: Vec<u64> = items.iter().map(|item| gcs.hash_to_range(item)).collect();
+    hashes.sort_unstable();
+
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        len: 0,
+    };
+    let mut previous = 0;
+    for hash in hashes {
+        writer.write_rice(hash - previous, gcs.rice_bits);
+        previous = hash;
+    }
+
+    Ok(ResourceArc::new(GcsResource {
+        data: writer.bytes,
+        ..gcs
+    }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn gcs_member(resource: ResourceArc<GcsResource>, item: Item) -> bool {
+    resource.contains(item.as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn gcs_member_many(resource: ResourceArc<GcsResource>, items: Vec<Item>) -> Vec<bool> {
+    resource.contains_many(&items)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn gcs_serialize<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GcsResource>,
+) -> Result<Binary<'a>, Error> {
+    let mut binary =
+        OwnedBinary::new(GCS_HEADER_LEN + resource.data.len()).ok_or(Error::AllocationFailed)?;
+    let buf = binary.as_mut_slice();
+    buf[0] = GCS_FORMAT_VERSION;
+    buf[1] = resource.rice_bits;
+    buf[2..10].copy_from_slice(&resource.count.to_le_bytes());
+    buf[10..GCS_HEADER_LEN].copy_from_slice(&resource.seed.to_le_bytes());
+    buf[GCS_HEADER_LEN..].copy_from_slice(&resource.data);
+
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn gcs_deserialize(binary: Binary) -> Result<ResourceArc<GcsResource>, Error> {
+    let buf = binary.as_slice();
+    if buf.len() < GCS_HEADER_LEN {
+        return Err(Error::InvalidData);
+    }
+    if buf[0] != GCS_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion);
+    }
+
+    let rice_bits = buf[1];
+    let count = u64::from_le_bytes(buf[2..10].try_into().unwrap());
+    let seed = u128::from_le_bytes(buf[10..GCS_HEADER_LEN].try_into().unwrap());
+    if rice_bits == 0 || rice_bits as u32 > MAX_RICE_BITS || count > u64::MAX >> rice_bits {
+        return Err(Error::InvalidData);
+    }
+    // Every item takes at least the terminating zero and the remainder bits.
+    let data = &buf[GCS_HEADER_LEN..];
+    if (data.len() as u64 * 8) / (rice_bits as u64 + 1) < count {
+        return Err(Error::InvalidData);
+    }
+
+    Ok(ResourceArc::new(GcsResource::new(
+        data.to_vec(),
+        count,
+        rice_bits,
+        seed,
+    )))
+}
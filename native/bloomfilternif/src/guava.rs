@@ -0,0 +1,191 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::{zeroed, Reservation};
+use crate::{collect_words, validate_params};
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::f64::consts::LN_2;
+use std::sync::RwLock;
+
+// Guava's `BloomFilterStrategies`, identified on the wire by their ordinal.
+#[derive(Clone, Copy)]
+enum Strategy {
+    Murmur128Mitz32 = 0,
+    Murmur128Mitz64 = 1,
+}
+
+// strategy + num_hashes + number of words
+const GUAVA_HEADER_LEN: usize = 1 + 1 + 4;
+
+// A bloom filter that hashes and lays out bits exactly like Guava's
+// `com.google.common.hash.BloomFilter`, so filters can be exchanged with JVM
+// services through Guava's `writeTo` / `readFrom` format. Items are hashed as
+// raw bytes, which matches Guava's `Funnels.byteArrayFunnel()` and
+// `Funnels.stringFunnel(UTF_8)`.
+struct GuavaBloomFilter {
+    words: Vec<u64>,
+    num_hashes: u8,
+    strategy: Strategy,
+    _memory: Reservation,
+}
+
+impl GuavaBloomFilter {
+    // Guava's sizing: m = -n ln(p) / ln(2)^2 rounded up to whole words, and
+    // k = m/n ln(2), computed from the unrounded m.
+    fn new(capacity: usize, false_positive_rate: f64) -> Result<Self, Error> {
+        let num_bits = (-(capacity as f64) * false_positive_rate.ln() / (LN_2 * LN_2)) as u64;
+        let num_hashes = ((num_bits as f64 / capacity as f64) * LN_2).round();
+        let (words, memory) = zeroed(num_bits.div_ceil(64).max(1) as usize)?;
+
+        Ok(GuavaBloomFilter {
+            words,
+            num_hashes: num_hashes.clamp(1.0, u8::MAX as f64) as u8,
+            strategy: Strategy::Murmur128Mitz64,
+            _memory: memory,
+        })
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.words.len() as u64 * 64
+    }
+
+    // Guava reads the 128-bit murmur3 hash as two little-endian longs.
+    fn bit_indexes(&self, item: &[u8]) -> Vec<u64> {
+        let hash = murmur3::murmur3_x64_128(&mut &item[..], 0).expect("reading from a slice");
+        let (hash1, hash2) = (hash as u64, (hash >> 64) as u64);
+        let num_bits = self.num_bits();
+
+        match self.strategy {
+            Strategy::Murmur128Mitz32 => {
+                let (low, high) = (hash1 as i32, (hash1 >> 32) as i32);
+                (1..=self.num_hashes as i32)
+                    .map(|i| {
+                        let combined = low.wrapping_add(i.wrapping_mul(high));
+                        let combined = if combined < 0 { !combined } else { combined };
+                        combined as u64 % num_bits
+                    })
+                    .collect()
+            }
+            Strategy::Murmur128Mitz64 => {
+                let mut combined = hash1;
+                (0..self.num_hashes)
+                    .map(|_| {
+                        let index = (combined & i64::MAX as u64) % num_bits;
+                        combined = combined.wrapping_add(hash2);
+                        index
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn insert(&mut self, item: &[u8]) -> bool {
+        let mut changed = false;
+        for index in self.bit_indexes(item) {
+            let (word, mask) = ((index / 64) as usize, 1 << (index % 64));
+            changed |= self.words[word] & mask == 0;
+            self.words[word] |= mask;
+        }
+        changed
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.bit_indexes(item)
+            .into_iter()
+            .all(|index| self.words[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    // Multi-byte fields are big-endian, as written by Java's `DataOutputStream`.
+    fn encode(&self, buf: &mut [u8]) {
+        buf[0] = self.strategy as u8;
+        buf[1] = self.num_hashes;
+        buf[2..GUAVA_HEADER_LEN].copy_from_slice(&(self.words.len() as u32).to_be_bytes());
+        for (bytes, word) in buf[GUAVA_HEADER_LEN..].chunks_exact_mut(8).zip(&self.words) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < GUAVA_HEADER_LEN {
+            return Err(Error::InvalidData);
+        }
+        let strategy = match buf[0] {
+            0 => Strategy::Murmur128Mitz32,
+            1 => Strategy::Murmur128Mitz64,
+            _ => return Err(Error::UnsupportedVersion),
+        };
+        let num_hashes = buf[1];
+        let num_words = u32::from_be_bytes(buf[2..GUAVA_HEADER_LEN].try_into().unwrap()) as usize;
+
+        let payload = &buf[GUAVA_HEADER_LEN..];
+        if num_hashes == 0 || num_words == 0 || payload.len() != num_words * 8 {
+            return Err(Error::InvalidData);
+        }
+
+        let memory = Reservation::new(payload.len())?;
+        let words = collect_words(
+            payload
+                .chunks_exact(8)
+                .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap())),
+        )?;
+        Ok(GuavaBloomFilter {
+            words,
+            num_hashes,
+            strategy,
+            _memory: memory,
+        })
+    }
+}
+
+pub struct GuavaBloomFilterResource {
+    filter: RwLock<GuavaBloomFilter>,
+}
+
+impl Resource for GuavaBloomFilterResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn guava_new(
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<GuavaBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    Ok(ResourceArc::new(GuavaBloomFilterResource {
+        filter: RwLock::new(GuavaBloomFilter::new(capacity, false_positive_rate)?),
+    }))
+}
+
+// Returns whether any bit changed, like Guava's `put`.
+#[rustler::nif]
+fn guava_add(resource: ResourceArc<GuavaBloomFilterResource>, item: Item) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(filter.insert(item.as_slice()))
+}
+
+#[rustler::nif]
+fn guava_member(
+    resource: ResourceArc<GuavaBloomFilterResource>,
+    item: Item,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn serialize_guava<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GuavaBloomFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read()?;
+
+    let mut binary = OwnedBinary::new(GUAVA_HEADER_LEN + filter.words.len() * 8)
+        .ok_or(Error::AllocationFailed)?;
+    filter.encode(binary.as_mut_slice());
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn deserialize_guava(binary: Binary) -> Result<ResourceArc<GuavaBloomFilterResource>, Error> {
+    Ok(ResourceArc::new(GuavaBloomFilterResource {
+        filter: RwLock::new(GuavaBloomFilter::decode(binary.as_slice())?),
+    }))
+}
@@ -0,0 +1,133 @@
+use crate::error::Error;
+use fastbloom::DefaultHasher;
+use rustler::Atom;
+use std::hash::{BuildHasher, Hasher};
+use wyhash::final3::{make_secret, wyhash};
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+mod atoms {
+    rustler::atoms! {
+        sip13,
+        xxh3,
+        wyhash,
+    }
+}
+
+// The hash a `BloomFilterResource` derives its bit indexes from, all seeded
+// with the filter's seed. SipHash-1-3, fastbloom's default, is keyed, so
+// without the seed nobody can pick items that land on chosen bits. xxh3 and
+// wyhash are faster, but their seeds don't make them safe against that.
+//
+// The discriminants are written to the serialized format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum HashAlgorithm {
+    #[default]
+    Sip13 = 0,
+    Xxh3 = 1,
+    Wyhash = 2,
+}
+
+impl HashAlgorithm {
+    pub(crate) fn from_atom(atom: Atom) -> Result<Self, Error> {
+        if atom == atoms::sip13() {
+            Ok(HashAlgorithm::Sip13)
+        } else if atom == atoms::xxh3() {
+            Ok(HashAlgorithm::Xxh3)
+        } else if atom == atoms::wyhash() {
+            Ok(HashAlgorithm::Wyhash)
+        } else {
+            Err(Error::InvalidOption)
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(HashAlgorithm::Sip13),
+            1 => Ok(HashAlgorithm::Xxh3),
+            2 => Ok(HashAlgorithm::Wyhash),
+            _ => Err(Error::InvalidData),
+        }
+    }
+
+    pub(crate) fn to_atom(self) -> Atom {
+        match self {
+            HashAlgorithm::Sip13 => atoms::sip13(),
+            HashAlgorithm::Xxh3 => atoms::xxh3(),
+            HashAlgorithm::Wyhash => atoms::wyhash(),
+        }
+    }
+}
+
+// Kirsch-Mitzenmacher double hashing for the filters that index their own
+// slots from one 64-bit hash: index_i = (h1 + i * h2) mod len, with h1 the
+// hash and h2 its halves swapped, made odd so it is never zero.
+pub(crate) fn double_hash_indexes(
+    hash: u64,
+    num_hashes: u32,
+    len: usize,
+) -> impl Iterator<Item = usize> {
+    let h1 = hash;
+    let h2 = hash.rotate_left(32) | 1;
+    (0..num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len as u64) as usize)
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum FilterHasher {
+    Sip13(DefaultHasher),
+    Xxh3 { seed: u64 },
+    Wyhash { seed: u64, secret: [u64; 4] },
+}
+
+impl FilterHasher {
+    // xxh3 and wyhash take 64-bit seeds, so the two halves of the seed are
+    // folded together for them.
+    pub(crate) fn new(algorithm: HashAlgorithm, seed: u128) -> Self {
+        let folded = (seed ^ seed >> 64) as u64;
+        match algorithm {
+            HashAlgorithm::Sip13 => FilterHasher::Sip13(DefaultHasher::seeded(&seed.to_be_bytes())),
+            HashAlgorithm::Xxh3 => FilterHasher::Xxh3 { seed: folded },
+            HashAlgorithm::Wyhash => FilterHasher::Wyhash {
+                seed: folded,
+                secret: make_secret(folded),
+            },
+        }
+    }
+}
+
+// xxh3 and wyhash hash each write in one shot, seeded with the hash of the
+// writes before it. Items are hashed in two writes, their length and their
+// bytes, so this avoids the buffering of their streaming hashers.
+pub(crate) enum FilterHasherState {
+    Sip13(<DefaultHasher as BuildHasher>::Hasher),
+    Xxh3(u64),
+    Wyhash(u64, [u64; 4]),
+}
+
+impl BuildHasher for FilterHasher {
+    type Hasher = FilterHasherState;
+
+    fn build_hasher(&self) -> FilterHasherState {
+        match self {
+            FilterHasher::Sip13(hasher) => FilterHasherState::Sip13(hasher.build_hasher()),
+            FilterHasher::Xxh3 { seed } => FilterHasherState::Xxh3(*seed),
+            FilterHasher::Wyhash { seed, secret } => FilterHasherState::Wyhash(*seed, *secret),
+        }
+    }
+}
+
+impl Hasher for FilterHasherState {
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            FilterHasherState::Sip13(state) => state.write(bytes),
+            FilterHasherState::Xxh3(hash) => *hash = xxh3_64_with_seed(bytes, *hash),
+            FilterHasherState::Wyhash(hash, secret) => *hash = wyhash(bytes, *hash, *secret),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match self {
+            FilterHasherState::Sip13(state) => state.finish(),
+            FilterHasherState::Xxh3(hash) | FilterHasherState::Wyhash(hash, _) => *hash,
+        }
+    }
+}
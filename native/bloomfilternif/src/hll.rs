@@ -0,0 +1,131 @@
+use crate::error::Error;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// Like the count-min sketch, HLLs hash with a fixed seed so that any two with
+// the same precision can be merged.
+const HLL_SEED: [u8; 16] = [0; 16];
+
+const MIN_PRECISION: u8 = 4;
+const MAX_PRECISION: u8 = 18;
+
+// A HyperLogLog cardinality estimator with 2^precision registers, following
+// Flajolet et al. with the linear counting correction for small cardinalities.
+// The standard error is about 1.04 / sqrt(2^precision).
+struct HyperLogLog {
+    registers: Vec<u8>,
+    precision: u8,
+    hasher: DefaultHasher,
+}
+
+impl HyperLogLog {
+    fn new(precision: u8) -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << precision],
+            precision,
+            hasher: DefaultHasher::seeded(&HLL_SEED),
+        }
+    }
+
+    fn insert(&mut self, item: &[u8]) {
+        let hash = self.hasher.hash_one(item);
+        let index = (hash >> (64 - self.precision)) as usize;
+        // The sentinel bit caps the rank at 64 - precision + 1.
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn cardinality(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&register| 2f64.powi(-(register as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|&&register| register == 0)
+            .count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+
+pub struct HllResource {
+    hll: RwLock<HyperLogLog>,
+}
+
+impl Resource for HllResource {}
+
+#[rustler::nif]
+fn hll_new(precision: u8) -> Result<ResourceArc<HllResource>, Error> {
+    if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+        return Err(Error::InvalidPrecision);
+    }
+
+    Ok(ResourceArc::new(HllResource {
+        hll: RwLock::new(HyperLogLog::new(precision)),
+    }))
+}
+
+#[rustler::nif]
+fn hll_add(
+    resource: ResourceArc<HllResource>,
+    item: Binary,
+) -> Result<ResourceArc<HllResource>, Error> {
+    {
+        let mut hll = resource.hll.write()?;
+        hll.insert(item.as_slice());
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn hll_cardinality(resource: ResourceArc<HllResource>) -> Result<u64, Error> {
+    let hll = resource.hll.read()?;
+    Ok(hll.cardinality().round() as u64)
+}
+
+#[rustler::nif]
+fn hll_merge(
+    a: ResourceArc<HllResource>,
+    b: ResourceArc<HllResource>,
+) -> Result<ResourceArc<HllResource>, Error> {
+    // Copy one side first so both locks are never held at once.
+    let mut merged = {
+        let hll = a.hll.read()?;
+        let mut merged = HyperLogLog::new(hll.precision);
+        merged.registers.copy_from_slice(&hll.registers);
+        merged
+    };
+    {
+        let b_hll = b.hll.read()?;
+        if merged.precision != b_hll.precision {
+            return Err(Error::IncompatibleFilters);
+        }
+        for (register, other) in merged.registers.iter_mut().zip(&b_hll.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    Ok(ResourceArc::new(HllResource {
+        hll: RwLock::new(merged),
+    }))
+}
@@ -0,0 +1,18 @@
+use rustler::{Binary, Decoder, NifResult, Term};
+
+// An item to hash, decoded from either a binary or any iodata. Binaries are
+// borrowed as-is; iolists are flattened once by the VM into a temporary binary
+// owned by the NIF call, so callers never need `IO.iodata_to_binary/1`.
+pub struct Item<'a>(Binary<'a>);
+
+impl Item<'_> {
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl<'a> Decoder<'a> for Item<'a> {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Binary::from_iolist(term).map(Item)
+    }
+}
@@ -1,121 +1,1535 @@
-use fastbloom::BloomFilter;
-use rustler::{Env, Resource, ResourceArc};
-use std::sync::RwLock;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use base64::write::EncoderWriter;
+use rayon::prelude::*;
+use rustler::{Atom, Binary, Env, NifMap, OwnedBinary, Resource, ResourceArc, Term};
+use std::f64::consts::LN_2;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+mod alarm;
+mod autosave;
+mod bloomier;
+mod branch;
+mod bulk;
+mod cms;
+mod counting;
+mod cuckoo;
+mod delta;
+mod error;
+mod file;
+mod filter_map;
+mod format;
+mod frozen;
+mod fuse;
+mod gcs;
+mod guava;
+mod hasher;
+mod hll;
+mod item;
+mod lock;
+mod memory;
+mod metrics;
+mod mmap;
+mod options;
+mod partitioned;
+mod prepared;
+mod quotient;
+mod redisbloom;
+mod registry;
+mod release;
+mod ribbon;
+mod rotating;
+mod scalable;
+mod segmented;
+mod sharded;
+mod stable;
+mod stream;
+mod timeseries;
+mod topk;
+mod ttl;
+mod wal;
+mod writes;
+
+use alarm::{check_fill, FillAlarm};
+use autosave::Autosave;
+use bloomier::BloomierResource;
+use branch::BranchResource;
+use cms::CountMinSketchResource;
+use counting::CountingBloomFilterResource;
+use cuckoo::CuckooFilterResource;
+use delta::ChangeLog;
+use error::{catch_panic, Error};
+use filter_map::FilterMapResource;
+use format::{decompressed, Encoded};
+use frozen::FrozenBloomFilterResource;
+use fuse::FuseFilterResource;
+use gcs::GcsResource;
+use guava::GuavaBloomFilterResource;
+use hasher::HashAlgorithm;
+use hll::HllResource;
+use item::Item;
+use lock::FilterLock;
+use memory::Reservation;
+use metrics::Counters;
+use mmap::MmapBloomFilterResource;
+use partitioned::PartitionedBloomFilterResource;
+use prepared::PreparedKeyResource;
+use quotient::QuotientFilterResource;
+use redisbloom::RedisBloomResource;
+use release::ReleaseNotice;
+use ribbon::RibbonResource;
+use rotating::RotatingBloomFilterResource;
+use scalable::ScalableBloomFilterResource;
+use segmented::Filter;
+use sharded::ShardedBloomFilterResource;
+use stable::StableBloomFilterResource;
+use stream::SerializeStreamResource;
+use timeseries::TimeSeriesFilterResource;
+use topk::TopKResource;
+use ttl::TtlBloomFilterResource;
+use wal::WalLog;
+use writes::WriteVersion;
+
+// Allocate through enif_alloc so memory held by filters is accounted for by the
+// VM and shows up in `:erlang.memory/0`. Unit tests run outside the VM, where
+// the enif functions are not available.
+#[cfg(not(test))]
+#[global_allocator]
+static ALLOCATOR: rustler::EnifAllocator = rustler::EnifAllocator;
 
 // Hold the bloom filter in memory through Rust rather than Elixir.
+//
+// Setting bits is monotonic, so inserts set them atomically and share the read
+// lock with lookups. The write lock is only taken to reset or replace the bits.
 pub struct BloomFilterResource {
-    filter: RwLock<BloomFilter>,
+    filter: FilterLock,
     capacity: usize,
     false_positive_rate: f64,
-    inserted_count: RwLock<usize>,
+    // The hasher seed is kept so the filter can be rebuilt from its bits.
+    seed: u128,
+    // The hash the seed keys.
+    hash: HashAlgorithm,
+    // Updated atomically alongside the bits. Concurrent inserts of the same new
+    // item can each see it as new, so it may slightly overcount under contention.
+    inserted_count: AtomicUsize,
+    // Strict filters reject inserts once `inserted_count` reaches `capacity`.
+    strict: bool,
+    // Set up by the first `snapshot_version` call.
+    changes: OnceLock<ChangeLog>,
+    // Bumped by every change to the bits or `inserted_count`, so `stats` can
+    // read both consistently without blocking inserts.
+    writes: WriteVersion,
+    counters: Counters,
+    // Set by `notify_on_release`.
+    release_notice: Mutex<Option<ReleaseNotice>>,
+    // Set by `enable_autosave`.
+    autosave: Mutex<Option<Autosave>>,
+    // Set by `enable_wal`.
+    wal: WalLog,
+    // Set by `set_fill_alarm`.
+    fill_alarm: FillAlarm,
+    // The bit array's share of the memory budget, given back when the
+    // resource is dropped.
+    _memory: Reservation,
 }
 
-impl Resource for BloomFilterResource {}
+impl Resource for BloomFilterResource {
+    const IMPLEMENTS_DESTRUCTOR: bool = true;
+
+    fn destructor(self, _env: Env<'_>) {
+        let notice = self
+            .release_notice
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(notice) = notice {
+            notice.send();
+        }
+    }
+}
 
 impl BloomFilterResource {
-    fn new(capacity: usize, false_positive_rate: f64) -> Self {
-        let filter = BloomFilter::with_false_pos(false_positive_rate).expected_items(capacity);
+    // Sized as `with_false_pos(..).expected_items(..)` would, but allocated
+    // with `Filter::zeroed`.
+    fn new(
+        capacity: usize,
+        false_positive_rate: f64,
+        seed: u128,
+        hash: HashAlgorithm,
+    ) -> Result<Self, Error> {
+        let (num_words, num_hashes) = optimal_size(capacity, false_positive_rate)?;
+        let memory = Reservation::new(num_words * 8)?;
+        let filter = Filter::zeroed(num_words, hash, seed, num_hashes)?;
+
+        Ok(Self::from_filter(
+            filter,
+            memory,
+            capacity,
+            false_positive_rate,
+            seed,
+            hash,
+            0,
+        ))
+    }
 
+    // A copy of `filter`, this resource's bits, allocated with
+    // `Filter::from_words`.
+    fn copy_filter(&self, filter: &Filter) -> Result<Filter, Error> {
+        let words = (0..filter.num_words()).map(|index| filter.word(index).load(Ordering::Relaxed));
+        Filter::from_words(words, self.hash, self.seed, filter.num_hashes())
+    }
+
+    // `memory` is the reservation taken for the bit array before allocating
+    // it, which the resource keeps for as long as it lives.
+    fn from_filter(
+        filter: Filter,
+        memory: Reservation,
+        capacity: usize,
+        false_positive_rate: f64,
+        seed: u128,
+        hash: HashAlgorithm,
+        inserted_count: usize,
+    ) -> Self {
         BloomFilterResource {
-            filter: RwLock::new(filter),
+            filter: FilterLock::new(filter),
             capacity,
             false_positive_rate,
-            inserted_count: RwLock::new(0),
+            seed,
+            hash,
+            inserted_count: AtomicUsize::new(inserted_count),
+            strict: false,
+            changes: OnceLock::new(),
+            writes: WriteVersion::default(),
+            counters: Counters::default(),
+            release_notice: Mutex::new(None),
+            autosave: Mutex::new(None),
+            wal: WalLog::default(),
+            fill_alarm: FillAlarm::default(),
+            _memory: memory,
+        }
+    }
+
+    // Counts `additional` items before they are inserted. Strict filters refuse
+    // to go past their capacity; if the items turn out not to be new, the
+    // reservation is handed back with `release`.
+    fn reserve(&self, additional: usize) -> Result<(), Error> {
+        if !self.strict {
+            self.inserted_count.fetch_add(additional, Ordering::Relaxed);
+            return Ok(());
+        }
+        self.inserted_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                count
+                    .checked_add(additional)
+                    .filter(|&count| count <= self.capacity)
+            })
+            .map(|_| ())
+            .map_err(|_| Error::CapacityExceeded)
+    }
+
+    fn release(&self, unused: usize) {
+        self.inserted_count.fetch_sub(unused, Ordering::Relaxed);
+    }
+
+    // Sets the bits for `hash` and returns whether they were all set already.
+    fn set_hash(&self, filter: &Filter, hash: u64) -> bool {
+        let previously_contained = filter.insert_hash(hash);
+        if let (false, Some(changes)) = (previously_contained, self.changes.get()) {
+            for index in filter.bit_indexes(hash) {
+                changes.record(index as usize / 64);
+            }
         }
+        previously_contained
     }
+
+    fn over_capacity(&self) -> bool {
+        self.inserted_count.load(Ordering::Relaxed) > self.capacity
+    }
+
+    // The inserted count and the number of bits set, as of the same write,
+    // and the number of writes so far. Inserts keep going while the bits are
+    // counted unless they overlap every attempt, in which case the write
+    // lock waits them out.
+    fn consistent_counts(&self) -> (usize, usize, u64) {
+        {
+            let filter = self.filter.read();
+            let counts = || {
+                (
+                    self.inserted_count.load(Ordering::Relaxed),
+                    popcount(&filter),
+                )
+            };
+            if let Some(((inserted_count, bits_set), version)) = self.writes.read(counts) {
+                return (inserted_count, bits_set, version);
+            }
+        }
+        let filter = self.filter.write();
+        (
+            self.inserted_count.load(Ordering::Relaxed),
+            popcount(&filter),
+            self.writes.current(),
+        )
+    }
+}
+
+// The parameters that decide which bits an item sets.
+fn hash_shape(
+    resource: &BloomFilterResource,
+    filter: &Filter,
+) -> (usize, u32, u128, HashAlgorithm) {
+    (
+        filter.num_bits(),
+        filter.num_hashes(),
+        resource.seed,
+        resource.hash,
+    )
+}
+
+// Filters can only be combined bit-for-bit when they hash items identically.
+fn check_compatible(
+    a: &BloomFilterResource,
+    a_filter: &Filter,
+    b: &BloomFilterResource,
+    b_filter: &Filter,
+) -> Result<(), Error> {
+    if hash_shape(a, a_filter) != hash_shape(b, b_filter) {
+        return Err(Error::IncompatibleFilters);
+    }
+    Ok(())
+}
+
+fn validate_params(capacity: usize, false_positive_rate: f64) -> Result<(), Error> {
+    if capacity == 0 {
+        return Err(Error::InvalidCapacity);
+    }
+    if false_positive_rate <= 0.0 || false_positive_rate >= 1.0 {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+    Ok(())
+}
+
+fn random_seed() -> Result<u128, Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|_| Error::SeedUnavailable)?;
+    Ok(u128::from_le_bytes(bytes))
 }
 
 fn on_load(env: Env, _info: rustler::Term) -> bool {
     env.register::<BloomFilterResource>().is_ok()
+        && env.register::<CountingBloomFilterResource>().is_ok()
+        && env.register::<ScalableBloomFilterResource>().is_ok()
+        && env.register::<RotatingBloomFilterResource>().is_ok()
+        && env.register::<CuckooFilterResource>().is_ok()
+        && env.register::<FuseFilterResource>().is_ok()
+        && env.register::<CountMinSketchResource>().is_ok()
+        && env.register::<HllResource>().is_ok()
+        && env.register::<ShardedBloomFilterResource>().is_ok()
+        && env.register::<FrozenBloomFilterResource>().is_ok()
+        && env.register::<SerializeStreamResource>().is_ok()
+        && env.register::<GuavaBloomFilterResource>().is_ok()
+        && env.register::<RedisBloomResource>().is_ok()
+        && env.register::<MmapBloomFilterResource>().is_ok()
+        && env.register::<TopKResource>().is_ok()
+        && env.register::<TtlBloomFilterResource>().is_ok()
+        && env.register::<StableBloomFilterResource>().is_ok()
+        && env.register::<TimeSeriesFilterResource>().is_ok()
+        && env.register::<PartitionedBloomFilterResource>().is_ok()
+        && env.register::<QuotientFilterResource>().is_ok()
+        && env.register::<GcsResource>().is_ok()
+        && env.register::<RibbonResource>().is_ok()
+        && env.register::<BloomierResource>().is_ok()
+        && env.register::<PreparedKeyResource>().is_ok()
+        && env.register::<FilterMapResource>().is_ok()
+        && env.register::<BranchResource>().is_ok()
 }
 
-#[rustler::nif]
+// NIFs whose cost scales with the filter size or the input length run on dirty
+// schedulers, so large filters don't block the normal BEAM schedulers.
+#[rustler::nif(schedule = "DirtyCpu")]
 fn new(
     capacity: usize,
     false_positive_rate: f64,
-) -> Result<ResourceArc<BloomFilterResource>, String> {
-    if capacity == 0 {
-        return Err("Capacity must be greater than 0".to_string());
+    strict: bool,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    let seed = random_seed()?;
+
+    let resource = catch_panic(|| {
+        BloomFilterResource::new(
+            capacity,
+            false_positive_rate,
+            seed,
+            HashAlgorithm::default(),
+        )
+    })?;
+    Ok(ResourceArc::new(BloomFilterResource { strict, ..resource }))
+}
+
+// Filters created with the same seed and parameters hash items identically, so
+// they can be unioned even when built on different nodes.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn new_with_seed(
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u128,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    let resource = catch_panic(|| {
+        BloomFilterResource::new(
+            capacity,
+            false_positive_rate,
+            seed,
+            HashAlgorithm::default(),
+        )
+    })?;
+    Ok(ResourceArc::new(resource))
+}
+
+// The capacity and false positive rate of a filter sized by hand: the item
+// count at which it would be optimally loaded, m ln(2) / k, and the rate at
+// that load, 2^-k.
+fn derived_params(num_bits: usize, num_hashes: u32) -> (usize, f64) {
+    let capacity = ((num_bits as f64 * LN_2 / num_hashes as f64) as usize).max(1);
+    (capacity, 0.5f64.powi(num_hashes as i32))
+}
+
+// Builds a filter directly from its bit array size and hash count, with the
+// capacity and false positive rate given by `derived_params`.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn new_with_bits(
+    num_bits: usize,
+    num_hashes: u32,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    if num_bits == 0 {
+        return Err(Error::InvalidNumBits);
     }
-    if false_positive_rate <= 0.0 || false_positive_rate >= 1.0 {
-        return Err("False positive rate must be between 0.0 and 1.0".to_string());
+    if num_hashes == 0 {
+        return Err(Error::InvalidNumHashes);
     }
 
-    Ok(ResourceArc::new(BloomFilterResource::new(
+    let seed = random_seed()?;
+    let num_words = num_bits.div_ceil(64);
+    let memory = Reservation::new(num_words * 8)?;
+    let filter = Filter::zeroed(num_words, HashAlgorithm::default(), seed, num_hashes)?;
+    let (capacity, false_positive_rate) = derived_params(filter.num_bits(), num_hashes);
+
+    Ok(ResourceArc::new(BloomFilterResource::from_filter(
+        filter,
+        memory,
         capacity,
         false_positive_rate,
+        seed,
+        HashAlgorithm::default(),
+        0,
     )))
 }
 
+#[derive(NifMap)]
+struct Params {
+    num_bits: usize,
+    num_hashes: u32,
+    memory_bytes: usize,
+}
+
+// Repeats the sizing `with_false_pos(..).expected_items(..)` does in fastbloom
+// without allocating anything: the bit count it picks (at least 512, rounded
+// up to whole words) and the hash count that is optimal for that many bits.
 #[rustler::nif]
-fn add(
-    resource: ResourceArc<BloomFilterResource>,
-    item: String,
-) -> Result<ResourceArc<BloomFilterResource>, String> {
-    {
-        let mut filter = resource
-            .filter
-            .write()
-            .map_err(|e| format!("Lock error: {}", e))?;
-        let mut count = resource
-            .inserted_count
-            .write()
-            .map_err(|e| format!("Lock error: {}", e))?;
-
-        filter.insert(&item);
-        *count += 1;
-    } // Locks are dropped here
+fn optimal_params(capacity: usize, false_positive_rate: f64) -> Result<Params, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    let (num_words, num_hashes) = optimal_size(capacity, false_positive_rate)?;
 
-    Ok(resource)
+    Ok(Params {
+        num_bits: num_words * 64,
+        num_hashes,
+        memory_bytes: num_words * 8,
+    })
+}
+
+// fastbloom maps each hash onto the bit array by multiplying its upper 32 bits
+// by the number of bits and keeping the upper 32 bits of the product, which
+// never reaches past bit 2^32, so bigger filters would only use their first
+// 512 MiB. `Filter` splits bigger bit arrays into segments of this size; the
+// other filter types built on fastbloom refuse them.
+const MAX_NUM_BITS: usize = 1 << 32;
+
+// Collects the words of a bit array into memory allocated up front, so that a
+// filter too big for the allocator fails with `AllocationFailed` instead of
+// aborting the VM. fastbloom converts the words to atomics in place.
+fn collect_words(words: impl ExactSizeIterator<Item = u64>) -> Result<Vec<u64>, Error> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(words.len())
+        .map_err(|_| Error::AllocationFailed)?;
+    vec.extend(words);
+    Ok(vec)
+}
+
+fn zeroed_words(num_words: usize) -> Result<Vec<u64>, Error> {
+    collect_words((0..num_words).map(|_| 0))
+}
+
+// Like `optimal_size`, but refuses filters past `MAX_NUM_BITS`.
+fn bounded_size(capacity: usize, false_positive_rate: f64) -> Result<(usize, u32), Error> {
+    let (num_words, num_hashes) = optimal_size(capacity, false_positive_rate)?;
+    if num_words * 64 > MAX_NUM_BITS {
+        return Err(Error::InvalidCapacity);
+    }
+    Ok((num_words, num_hashes))
 }
 
+// The zeroed words and hash count of a filter sized as
+// `with_false_pos(..).expected_items(..)` would size it, with their share of
+// the budget, for the filter types that build fastbloom filters of their own.
+fn optimal_words(
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<(Vec<u64>, u32, Reservation), Error> {
+    let (num_words, num_hashes) = bounded_size(capacity, false_positive_rate)?;
+    let memory = Reservation::new(num_words * 8)?;
+    Ok((zeroed_words(num_words)?, num_hashes, memory))
+}
+
+// The number of 64-bit words and hashes fastbloom picks for a filter built with
+// `with_false_pos(false_positive_rate).expected_items(capacity)`.
+fn optimal_size(capacity: usize, false_positive_rate: f64) -> Result<(usize, u32), Error> {
+    let bits = 8.0 * (capacity as f64 * false_positive_rate.ln() / (-8.0 * LN_2 * LN_2)).ceil();
+    let num_words = (bits as usize).max(512).div_ceil(64);
+    let num_bits = num_words.checked_mul(64).ok_or(Error::InvalidCapacity)?;
+    let num_hashes = ((LN_2 * num_bits as f64 / capacity as f64) as u32).max(1);
+    Ok((num_words, num_hashes))
+}
+
+// Standard bloom filter sizing for the filters with one slot per bit, such as
+// counting and TTL filters: m = -n ln(p) / ln(2)^2 slots and k = m/n ln(2)
+// hashes, at least one of each.
+fn slot_size(capacity: usize, false_positive_rate: f64) -> (usize, u32) {
+    let num_slots = (-(capacity as f64) * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+    let num_slots = (num_slots as usize).max(1);
+    let num_hashes = ((num_slots as f64 / capacity as f64) * LN_2).round() as u32;
+    (num_slots, num_hashes.max(1))
+}
+
+impl BloomFilterResource {
+    // Returns whether the item was new. Items the filter (probably) already
+    // contains are not counted and don't use up a strict filter's capacity.
+    fn insert(&self, item: &[u8]) -> Result<bool, Error> {
+        let filter = self.filter.read();
+        self.insert_locked(&filter, filter.source_hash(item))
+    }
+
+    fn insert_hash(&self, hash: u64) -> Result<bool, Error> {
+        self.insert_locked(&self.filter.read(), hash)
+    }
+
+    // Inserts into `filter`, which the caller holds the read lock on.
+    fn insert_locked(&self, filter: &Filter, hash: u64) -> Result<bool, Error> {
+        self.counters.record_adds(1);
+        if filter.contains_hash(hash) {
+            return Ok(false);
+        }
+        let _write = self.writes.begin();
+        self.reserve(1)?;
+        if let Err(error) = self.wal.append(&[hash]) {
+            self.release(1);
+            return Err(error);
+        }
+
+        // Another insert may have set the same bits since the check above.
+        if self.set_hash(filter, hash) {
+            self.release(1);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn contains(&self, item: &[u8]) -> Result<bool, Error> {
+        let filter = self.filter.read();
+        Ok(self.counters.record_lookup(filter.contains(item)))
+    }
+}
+
+// Folds a precomputed hash pair into the single 64-bit hash the filter derives
+// its bit indexes from. Callers with one 64-bit hash can pass 0 as `hash2`.
+fn fold_hashes(hash1: u64, hash2: u64) -> u64 {
+    hash1 ^ hash2.rotate_left(32)
+}
+
+// The add NIFs return whether the item was new.
+#[rustler::nif]
+fn add(resource: ResourceArc<BloomFilterResource>, item: Item) -> Result<bool, Error> {
+    resource
+        .insert(item.as_slice())
+        .inspect(|_| check_fill(&resource))
+}
+
+// Hashes the term's external format, the same bytes `:erlang.term_to_binary/1`
+// returns, so terms added here are found by `member` given that binary.
 #[rustler::nif]
-fn member(resource: ResourceArc<BloomFilterResource>, item: String) -> Result<bool, String> {
+fn add_term(resource: ResourceArc<BloomFilterResource>, term: Term) -> Result<bool, Error> {
+    resource
+        .insert(term.to_binary().as_slice())
+        .inspect(|_| check_fill(&resource))
+}
+
+impl BloomFilterResource {
+    // Returns the number of new items.
+    fn insert_many(&self, items: &[Item]) -> Result<usize, Error> {
+        self.insert_many_locked(&self.filter.read(), items)
+    }
+
+    fn insert_many_locked(&self, filter: &Filter, items: &[Item]) -> Result<usize, Error> {
+        let hashes = items
+            .iter()
+            .map(|item| filter.source_hash(item.as_slice()))
+            .collect();
+        self.insert_hashes_locked(filter, hashes, false)
+    }
+
+    // Like `insert_many`, but hashes the items and sets their bits across
+    // rayon's thread pool. Bits are set atomically, so the threads share the
+    // read lock just as concurrent callers do.
+    fn insert_many_parallel(&self, items: &[&[u8]]) -> Result<usize, Error> {
+        let filter = self.filter.read();
+        let hashes = items
+            .par_iter()
+            .map(|item| filter.source_hash(item))
+            .collect();
+        self.insert_hashes_locked(&filter, hashes, true)
+    }
+
+    fn insert_hashes_locked(
+        &self,
+        filter: &Filter,
+        hashes: Vec<u64>,
+        parallel: bool,
+    ) -> Result<usize, Error> {
+        self.set_hashes_locked(filter, hashes, parallel, true)
+    }
+
+    // Like `insert_hashes_locked`, appending the hashes to the write-ahead log
+    // if `log` is set and one is enabled.
+    fn set_hashes_locked(
+        &self,
+        filter: &Filter,
+        hashes: Vec<u64>,
+        parallel: bool,
+        log: bool,
+    ) -> Result<usize, Error> {
+        self.counters.record_adds(hashes.len());
+        let _write = self.writes.begin();
+
+        // Strict filters take all of the items or none of them, so room for every
+        // item not yet in the filter is reserved up front.
+        let reserved = if self.strict {
+            let mut new_hashes: Vec<u64> = hashes
+                .iter()
+                .copied()
+                .filter(|&hash| !filter.contains_hash(hash))
+                .collect();
+            new_hashes.sort_unstable();
+            new_hashes.dedup();
+            self.reserve(new_hashes.len())?;
+            new_hashes.len()
+        } else {
+            0
+        };
+        if log {
+            if let Err(error) = self.wal.append(&hashes) {
+                self.release(reserved);
+                return Err(error);
+            }
+        }
+
+        let new_items = if parallel {
+            hashes
+                .into_par_iter()
+                .filter(|&hash| !self.set_hash(filter, hash))
+                .count()
+        } else {
+            hashes
+                .into_iter()
+                .filter(|&hash| !self.set_hash(filter, hash))
+                .count()
+        };
+        if self.strict {
+            self.release(reserved.saturating_sub(new_items));
+        } else {
+            self.inserted_count.fetch_add(new_items, Ordering::Relaxed);
+        }
+        Ok(new_items)
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn add_many(resource: ResourceArc<BloomFilterResource>, items: Vec<Item>) -> Result<usize, Error> {
+    resource
+        .insert_many(&items)
+        .inspect(|_| check_fill(&resource))
+}
+
+// Like `add_many`, but returns the number of new items alongside the number
+// already present. A repeat within `items` counts as already present.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn add_many_report(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<(usize, usize), Error> {
+    let new_items = resource.insert_many(&items)?;
+    check_fill(&resource);
+    Ok((new_items, items.len() - new_items))
+}
+
+// Binaries borrowed from the caller can't be shared with other threads, but
+// their bytes can.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn add_many_parallel(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<usize, Error> {
+    let items: Vec<&[u8]> = items.iter().map(Item::as_slice).collect();
+    resource
+        .insert_many_parallel(&items)
+        .inspect(|_| check_fill(&resource))
+}
+
+// Like `add_term` and `add_many`, but return `LockTimeout` instead of waiting
+// more than `timeout_ms` for an exclusive operation such as `clear` to finish.
+// Waiting sleeps the calling thread, so both run on a dirty IO scheduler
+// rather than holding up a normal or dirty CPU one for the whole timeout.
+#[rustler::nif(schedule = "DirtyIo")]
+fn add_term_timeout(
+    resource: ResourceArc<BloomFilterResource>,
+    term: Term,
+    timeout_ms: u64,
+) -> Result<bool, Error> {
+    let filter = resource
+        .filter
+        .try_read_for(Duration::from_millis(timeout_ms))
+        .ok_or(Error::LockTimeout)?;
+    resource
+        .insert_locked(&filter, filter.source_hash(term.to_binary().as_slice()))
+        .inspect(|_| check_fill(&resource))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn add_many_timeout(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+    timeout_ms: u64,
+) -> Result<usize, Error> {
     let filter = resource
         .filter
-        .read()
-        .map_err(|e| format!("Lock error: {}", e))?;
-    Ok(filter.contains(&item))
+        .try_read_for(Duration::from_millis(timeout_ms))
+        .ok_or(Error::LockTimeout)?;
+    resource
+        .insert_many_locked(&filter, &items)
+        .inspect(|_| check_fill(&resource))
+}
+
+// Builds a filter sized for `capacity` and `false_positive_rate` holding
+// `items`, for replacing a saturated filter from its source of truth in one
+// call. The seed and strict mode are kept from `resource`, which is unchanged.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn rebuild(
+    resource: ResourceArc<BloomFilterResource>,
+    capacity: usize,
+    false_positive_rate: f64,
+    items: Vec<Item>,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    let rebuilt = catch_panic(|| {
+        BloomFilterResource::new(capacity, false_positive_rate, resource.seed, resource.hash)
+    })?;
+    let rebuilt = BloomFilterResource {
+        strict: resource.strict,
+        ..rebuilt
+    };
+    rebuilt.insert_many(&items)?;
+    Ok(ResourceArc::new(rebuilt))
+}
+
+// Concurrent inserts under the read lock can both see an item as new, so this
+// takes the write lock to promise that only one caller finds it absent.
+#[rustler::nif]
+fn add_if_absent(resource: ResourceArc<BloomFilterResource>, item: Item) -> Result<bool, Error> {
+    resource.counters.record_adds(1);
+    let filter = resource.filter.write();
+
+    let hash = filter.source_hash(item.as_slice());
+    if filter.contains_hash(hash) {
+        return Ok(true);
+    }
+    let _write = resource.writes.begin();
+    resource.reserve(1)?;
+    if let Err(error) = resource.wal.append(&[hash]) {
+        resource.release(1);
+        return Err(error);
+    }
+    resource.set_hash(&filter, hash);
+    check_fill(&resource);
+    Ok(false)
+}
+
+// Pre-hashed items skip the filter's seeded hasher, so the same hashes can be
+// used to probe any number of filters. They never match unhashed items.
+#[rustler::nif]
+fn add_hashed(
+    resource: ResourceArc<BloomFilterResource>,
+    hash1: u64,
+    hash2: u64,
+) -> Result<bool, Error> {
+    resource
+        .insert_hash(fold_hashes(hash1, hash2))
+        .inspect(|_| check_fill(&resource))
+}
+
+#[rustler::nif]
+fn member(resource: ResourceArc<BloomFilterResource>, item: Item) -> Result<bool, Error> {
+    resource.contains(item.as_slice())
+}
+
+// Integers are hashed as their 8 little-endian bytes, the in-memory layout on
+// x86-64 and ARM64, so they match filters built by services that hash their
+// integer keys directly. Elixir passes negative integers as their two's
+// complement, so signed and unsigned keys hash the same bytes.
+#[rustler::nif]
+fn add_int(resource: ResourceArc<BloomFilterResource>, int: u64) -> Result<bool, Error> {
+    resource
+        .insert(&int.to_le_bytes())
+        .inspect(|_| check_fill(&resource))
+}
+
+#[rustler::nif]
+fn member_int(resource: ResourceArc<BloomFilterResource>, int: u64) -> Result<bool, Error> {
+    resource.contains(&int.to_le_bytes())
+}
+
+#[rustler::nif]
+fn member_hashed(
+    resource: ResourceArc<BloomFilterResource>,
+    hash1: u64,
+    hash2: u64,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read();
+    let found = filter.contains_hash(fold_hashes(hash1, hash2));
+    Ok(resource.counters.record_lookup(found))
+}
+
+// fastbloom doesn't expose the bit indexes an item maps to, so this mirrors its
+// derivation: a double hasher seeded from the source hash, with each hash mapped
+// onto the bit array by multiplying its upper 32 bits. Keep in sync with fastbloom.
+fn bit_indexes(num_bits: u64, num_hashes: u32, source_hash: u64) -> Vec<u64> {
+    let mut h1 = source_hash;
+    let h2 = source_hash
+        .wrapping_shr(32)
+        .wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+
+    (0..num_hashes)
+        .map(|_| {
+            h1 = h1.wrapping_add(h2).rotate_left(5);
+            ((h1 >> 32).wrapping_mul(num_bits)) >> 32
+        })
+        .collect()
+}
+
+// Returns the bit index probed by each hash function, in order, for debugging
+// false positives and checking other implementations against this one.
+#[rustler::nif]
+fn hash_indexes(resource: ResourceArc<BloomFilterResource>, item: Item) -> Result<Vec<u64>, Error> {
+    let filter = resource.filter.read();
+    let hash = filter.source_hash(item.as_slice());
+    Ok(filter.bit_indexes(hash))
 }
 
 #[rustler::nif]
+fn member_term(resource: ResourceArc<BloomFilterResource>, term: Term) -> Result<bool, Error> {
+    resource.contains(term.to_binary().as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn member_many(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<Vec<bool>, Error> {
+    let filter = resource.filter.read();
+    let results: Vec<bool> = items
+        .iter()
+        .map(|item| filter.contains(item.as_slice()))
+        .collect();
+
+    let positive = results.iter().filter(|&&found| found).count();
+    resource
+        .counters
+        .record_lookups(positive, results.len() - positive);
+    Ok(results)
+}
+
+// Like `member_many`, but packs the results into one bit per item, the first
+// item in the most significant bit of the first byte, so each result costs a
+// bit instead of a list cell and an atom. Trailing bits of the last byte are 0.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn member_many_bitmask<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<Binary<'a>, Error> {
+    let mut binary = OwnedBinary::new(items.len().div_ceil(8)).ok_or(Error::AllocationFailed)?;
+    let mask = binary.as_mut_slice();
+    mask.fill(0);
+
+    let filter = resource.filter.read();
+    let mut positive = 0;
+    for (i, item) in items.iter().enumerate() {
+        if filter.contains(item.as_slice()) {
+            mask[i / 8] |= 0x80 >> (i % 8);
+            positive += 1;
+        }
+    }
+
+    resource
+        .counters
+        .record_lookups(positive, items.len() - positive);
+    Ok(binary.release(env))
+}
+
+// Like `member_many`, but returns only how many of the items are members.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn member_count(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<usize, Error> {
+    let filter = resource.filter.read();
+    let positive = items
+        .iter()
+        .filter(|item| filter.contains(item.as_slice()))
+        .count();
+
+    resource
+        .counters
+        .record_lookups(positive, items.len() - positive);
+    Ok(positive)
+}
+
+// Like `member_many`, but stops hashing at the first member.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn contains_any(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read();
+    let checked = items
+        .iter()
+        .position(|item| filter.contains(item.as_slice()));
+
+    // Every item up to the first member was checked and found absent.
+    match checked {
+        Some(index) => resource.counters.record_lookups(1, index),
+        None => resource.counters.record_lookups(0, items.len()),
+    }
+    Ok(checked.is_some())
+}
+
+// Like `member_many`, but stops hashing at the first non-member.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn contains_all(
+    resource: ResourceArc<BloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read();
+    let missing = items
+        .iter()
+        .position(|item| !filter.contains(item.as_slice()));
+
+    // Every item up to the first non-member was checked and found present.
+    match missing {
+        Some(index) => resource.counters.record_lookups(index, 1),
+        None => resource.counters.record_lookups(items.len(), 0),
+    }
+    Ok(missing.is_none())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
 fn clear(
     resource: ResourceArc<BloomFilterResource>,
-) -> Result<ResourceArc<BloomFilterResource>, String> {
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
     {
-        let mut filter = resource
-            .filter
-            .write()
-            .map_err(|e| format!("Lock error: {}", e))?;
-        let mut count = resource
-            .inserted_count
-            .write()
-            .map_err(|e| format!("Lock error: {}", e))?;
-
-        *filter = BloomFilter::with_false_pos(resource.false_positive_rate)
-            .expected_items(resource.capacity);
-        *count = 0;
-    } // Locks are dropped here
+        let filter = resource.filter.write();
+        let _write = resource.writes.begin();
+
+        filter.clear();
+        resource.inserted_count.store(0, Ordering::Relaxed);
+        if let Some(changes) = resource.changes.get() {
+            changes.clear();
+        }
+    } // Lock is dropped here
 
     Ok(resource)
 }
 
+// Encoded as a map with atom keys, so fields can be added without breaking callers.
+#[derive(NifMap)]
+struct Stats {
+    num_bits: usize,
+    num_hashes: u32,
+    false_positive_rate: f64,
+    inserted_count: usize,
+    capacity: usize,
+    over_capacity: bool,
+    strict: bool,
+    bits_set: usize,
+    write_version: u64,
+    fpr_at_capacity: f64,
+    fpr_at_inserted_count: f64,
+    hash: Atom,
+}
+
+// The expected false positive rate of a filter with `num_bits` bits and
+// `num_hashes` hashes once `items` distinct items are in it: (1 - e^(-kn/m))^k.
+fn theoretical_fpr(num_bits: usize, num_hashes: u32, items: usize) -> f64 {
+    let k = num_hashes as f64;
+    let fill_ratio = 1.0 - (-k * items as f64 / num_bits as f64).exp();
+    fill_ratio.powi(num_hashes as i32)
+}
+
 #[rustler::nif]
-fn stats(resource: ResourceArc<BloomFilterResource>) -> Result<(usize, usize, f64, usize), String> {
-    let filter = resource
-        .filter
-        .read()
-        .map_err(|e| format!("Lock error: {}", e))?;
-    let count = resource
+fn stats(resource: ResourceArc<BloomFilterResource>) -> Result<Stats, Error> {
+    let (num_bits, num_hashes) = {
+        let filter = resource.filter.read();
+        (filter.num_bits(), filter.num_hashes())
+    };
+    let (inserted_count, bits_set, write_version) = resource.consistent_counts();
+
+    Ok(Stats {
+        num_bits,
+        num_hashes,
+        false_positive_rate: resource.false_positive_rate,
+        inserted_count,
+        capacity: resource.capacity,
+        over_capacity: inserted_count > resource.capacity,
+        strict: resource.strict,
+        bits_set,
+        write_version,
+        fpr_at_capacity: theoretical_fpr(num_bits, num_hashes, resource.capacity),
+        fpr_at_inserted_count: theoretical_fpr(num_bits, num_hashes, inserted_count),
+        hash: resource.hash.to_atom(),
+    })
+}
+
+// False if a call panicked while changing the filter. See `FilterLock`.
+#[rustler::nif]
+fn healthy(resource: ResourceArc<BloomFilterResource>) -> bool {
+    resource.filter.healthy()
+}
+
+// The false positive rate only holds up to `capacity` items; past that it
+// degrades quickly, so callers can check this to know when to rebuild.
+#[rustler::nif]
+fn over_capacity(resource: ResourceArc<BloomFilterResource>) -> bool {
+    resource.over_capacity()
+}
+
+fn popcount(filter: &Filter) -> usize {
+    filter.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+// Swamidass & Baldi's estimate of the number of distinct items in a filter with
+// `bits_set` of its `num_bits` bits set: n = -m/k ln(1 - X/m). A saturated
+// filter is treated as having one bit clear, rather than infinitely many items.
+fn estimated_items(num_bits: usize, num_hashes: u32, bits_set: usize) -> f64 {
+    let m = num_bits as f64;
+    let x = bits_set.min(num_bits - 1) as f64;
+    -m / num_hashes as f64 * (1.0 - x / m).ln()
+}
+
+// Unlike `inserted_count`, this also accounts for the items shared by filters
+// combined with `union`.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn estimated_cardinality(resource: ResourceArc<BloomFilterResource>) -> Result<u64, Error> {
+    let filter = resource.filter.read();
+    let estimate = estimated_items(filter.num_bits(), filter.num_hashes(), popcount(&filter));
+    Ok(estimate.round() as u64)
+}
+
+// Inclusion-exclusion over the cardinality estimates: |A ∩ B| = |A| + |B| -
+// |A ∪ B|, where the union's bits are the OR of both filters'. Clamped at 0,
+// since the estimates' errors can make it negative for disjoint filters.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn estimate_overlap(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<u64, Error> {
+    // Copy one side first so both locks are never held at once.
+    let (a_shape, a_words) = {
+        let filter = a.filter.read();
+        (hash_shape(&a, &filter), filter.iter().collect::<Vec<_>>())
+    };
+    let b_filter = b.filter.read();
+    if a_shape != hash_shape(&b, &b_filter) {
+        return Err(Error::IncompatibleFilters);
+    }
+
+    let (mut a_bits, mut b_bits, mut union_bits) = (0, 0, 0);
+    for (a_word, b_word) in a_words.into_iter().zip(b_filter.iter()) {
+        a_bits += a_word.count_ones() as usize;
+        b_bits += b_word.count_ones() as usize;
+        union_bits += (a_word | b_word).count_ones() as usize;
+    }
+    let (num_bits, num_hashes) = (b_filter.num_bits(), b_filter.num_hashes());
+    let overlap = estimated_items(num_bits, num_hashes, a_bits)
+        + estimated_items(num_bits, num_hashes, b_bits)
+        - estimated_items(num_bits, num_hashes, union_bits);
+    Ok(overlap.max(0.0).round() as u64)
+}
+
+// Bytes held by the bit array, which dominates the size of the filter.
+#[rustler::nif]
+fn byte_size(resource: ResourceArc<BloomFilterResource>) -> Result<usize, Error> {
+    let filter = resource.filter.read();
+    Ok(filter.num_words() * 8)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn fill_ratio(resource: ResourceArc<BloomFilterResource>) -> Result<f64, Error> {
+    let filter = resource.filter.read();
+    Ok(popcount(&filter) as f64 / filter.num_bits() as f64)
+}
+
+// Stops at the first set bit, so it's cheap on any filter that isn't empty.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn is_empty(resource: ResourceArc<BloomFilterResource>) -> Result<bool, Error> {
+    let filter = resource.filter.read();
+    let empty = filter.iter().all(|word| word == 0);
+    Ok(empty)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn bits_set(resource: ResourceArc<BloomFilterResource>) -> Result<usize, Error> {
+    let filter = resource.filter.read();
+    Ok(popcount(&filter))
+}
+
+// The chance that all k probed bits of a non-member are set is X^k, where X is
+// the fraction of bits set.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn estimated_false_positive_rate(resource: ResourceArc<BloomFilterResource>) -> Result<f64, Error> {
+    let filter = resource.filter.read();
+    let fill_ratio = popcount(&filter) as f64 / filter.num_bits() as f64;
+    Ok(fill_ratio.powi(filter.num_hashes() as i32))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn serialize<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read();
+
+    let mut binary = OwnedBinary::new(BloomFilterResource::encoded_len(&filter))
+        .ok_or(Error::AllocationFailed)?;
+    resource.encode_to(&filter, binary.as_mut_slice())?;
+
+    Ok(binary.release(env))
+}
+
+// Like `serialize`, but zstd-compressed at `level`. Sparse filters are mostly
+// zero words and shrink by orders of magnitude.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn serialize_compressed<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+    level: i32,
+) -> Result<Binary<'a>, Error> {
+    if !zstd::compression_level_range().contains(&level) {
+        return Err(Error::InvalidCompressionLevel);
+    }
+
+    let compressed = {
+        let filter = resource.filter.read();
+
+        resource.encode_compressed(&filter, level)?
+    }; // Lock is dropped here
+
+    let mut binary = OwnedBinary::new(compressed.len()).ok_or(Error::AllocationFailed)?;
+    binary.as_mut_slice().copy_from_slice(&compressed);
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn deserialize(binary: Binary) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let buf = binary.as_slice();
+    let resource = catch_panic(|| BloomFilterResource::decode(buf))?;
+    Ok(ResourceArc::new(resource))
+}
+
+// `serialize`, base64-encoded (standard alphabet, padded) as it is written, so
+// the raw binary is never built.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn serialize_base64<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read();
+
+    let encoded_len = base64::encoded_len(BloomFilterResource::encoded_len(&filter), true)
+        .ok_or(Error::AllocationFailed)?;
+    let mut binary = OwnedBinary::new(encoded_len).ok_or(Error::AllocationFailed)?;
+    {
+        let mut encoder = EncoderWriter::new(binary.as_mut_slice(), &BASE64_STANDARD);
+        resource.encode_to(&filter, &mut encoder)?;
+        encoder.finish()?;
+    }
+
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn deserialize_base64(binary: Binary) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let buf = BASE64_STANDARD
+        .decode(binary.as_slice())
+        .map_err(|_| Error::InvalidData)?;
+    let resource = catch_panic(|| BloomFilterResource::decode(&buf))?;
+    Ok(ResourceArc::new(resource))
+}
+
+// The raw bit array: each 64-bit word of the filter in order, little-endian.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn to_bits<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<BloomFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read();
+
+    let mut binary = OwnedBinary::new(filter.num_words() * 8).ok_or(Error::AllocationFailed)?;
+    for (bytes, word) in binary.as_mut_slice().chunks_exact_mut(8).zip(filter.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(binary.release(env))
+}
+
+// Rebuilds a filter from a bit array in the `to_bits` layout. The bit array is
+// made of whole 64-bit words, so its size must be a multiple of 8 bytes. The
+// inserted count is estimated from the bits set.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn from_bits(
+    bits: Binary,
+    num_hashes: u32,
+    seed: u128,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    if bits.is_empty() || !bits.len().is_multiple_of(8) {
+        return Err(Error::InvalidNumBits);
+    }
+    if num_hashes == 0 {
+        return Err(Error::InvalidNumHashes);
+    }
+
+    let memory = Reservation::new(bits.len())?;
+    let words = bits
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+    let filter = Filter::from_words(words, HashAlgorithm::default(), seed, num_hashes)?;
+    let (capacity, false_positive_rate) = derived_params(filter.num_bits(), num_hashes);
+    let inserted_count =
+        estimated_items(filter.num_bits(), num_hashes, popcount(&filter)).round() as usize;
+
+    Ok(ResourceArc::new(BloomFilterResource::from_filter(
+        filter,
+        memory,
+        capacity,
+        false_positive_rate,
+        seed,
+        HashAlgorithm::default(),
+        inserted_count,
+    )))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn clone(
+    resource: ResourceArc<BloomFilterResource>,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let filter = resource.filter.read();
+    let memory = Reservation::new(filter.num_words() * 8)?;
+
+    Ok(ResourceArc::new(BloomFilterResource {
+        strict: resource.strict,
+        ..BloomFilterResource::from_filter(
+            resource.copy_filter(&filter)?,
+            memory,
+            resource.capacity,
+            resource.false_positive_rate,
+            resource.seed,
+            resource.hash,
+            resource.inserted_count.load(Ordering::Relaxed),
+        )
+    }))
+}
+
+// Builds a new resource from the bits of `a` combined with the bits of `b`.
+fn combine(
+    a: &BloomFilterResource,
+    b: &BloomFilterResource,
+    merge: impl FnOnce(&Filter, &Filter),
+    merge_count: impl FnOnce(usize, usize) -> usize,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    // Copy one side first so both locks are never held at once.
+    let (filter, a_count, memory) = {
+        let filter = a.filter.read();
+        let reservation = Reservation::new(filter.num_words() * 8)?;
+        (
+            a.copy_filter(&filter)?,
+            a.inserted_count.load(Ordering::Relaxed),
+            reservation,
+        )
+    };
+    let b_count = {
+        let b_filter = b.filter.read();
+        check_compatible(a, &filter, b, &b_filter)?;
+        merge(&filter, &b_filter);
+        b.inserted_count.load(Ordering::Relaxed)
+    };
+
+    Ok(ResourceArc::new(BloomFilterResource {
+        strict: a.strict,
+        ..BloomFilterResource::from_filter(
+            filter,
+            memory,
+            a.capacity,
+            a.false_positive_rate,
+            a.seed,
+            a.hash,
+            merge_count(a_count, b_count),
+        )
+    }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn union(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    catch_panic(AssertUnwindSafe(|| {
+        combine(&a, &b, Filter::union, |a, b| a + b)
+    }))
+}
+
+// Like folding `union` over the filters, but the bits are copied only once.
+// The inserted counts are summed, as `union` does.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn union_many(
+    filters: Vec<ResourceArc<BloomFilterResource>>,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let (first, rest) = filters.split_first().ok_or(Error::InvalidData)?;
+    catch_panic(AssertUnwindSafe(|| {
+        let (filter, mut count, memory) = {
+            let filter = first.filter.read();
+            let reservation = Reservation::new(filter.num_words() * 8)?;
+            (
+                first.copy_filter(&filter)?,
+                first.inserted_count.load(Ordering::Relaxed),
+                reservation,
+            )
+        };
+        // One lock is held at a time, as in `combine`.
+        for other in rest {
+            let other_filter = other.filter.read();
+            check_compatible(first, &filter, other, &other_filter)?;
+            filter.union(&other_filter);
+            count += other.inserted_count.load(Ordering::Relaxed);
+        }
+
+        Ok(ResourceArc::new(BloomFilterResource {
+            strict: first.strict,
+            ..BloomFilterResource::from_filter(
+                filter,
+                memory,
+                first.capacity,
+                first.false_positive_rate,
+                first.seed,
+                first.hash,
+                count,
+            )
+        }))
+    }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn intersection(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    // The true overlap is unknown, but it can't exceed the smaller input.
+    catch_panic(AssertUnwindSafe(|| {
+        combine(&a, &b, Filter::intersect, usize::min)
+    }))
+}
+
+// Filters are equal when they hash items identically, were sized for the same
+// capacity and rate, and have exactly the same bits set. Inserted counts are
+// not compared: replicas whose bits converged through merges can still count
+// differently.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn equal(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<bool, Error> {
+    if std::ptr::eq(&*a, &*b) {
+        return Ok(true);
+    }
+
+    // Copy one side first so both locks are never held at once.
+    let (a_shape, a_words) = {
+        let filter = a.filter.read();
+        (hash_shape(&a, &filter), filter.iter().collect::<Vec<_>>())
+    };
+    let b_filter = b.filter.read();
+    Ok(a_shape == hash_shape(&b, &b_filter)
+        && a.capacity == b.capacity
+        && a.false_positive_rate == b.false_positive_rate
+        && b_filter.iter().eq(a_words))
+}
+
+// Unions a serialized filter into this one in place, without building a
+// resource for it first. The inserted count becomes the larger of the two,
+// rather than their sum as in `union`, so merging the same state twice (as
+// gossip protocols do) changes nothing. Returns the new inserted count.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn merge_serialized(
+    resource: ResourceArc<BloomFilterResource>,
+    binary: Binary,
+) -> Result<usize, Error> {
+    let buf = catch_panic(|| decompressed(binary.as_slice()))?;
+    let encoded = catch_panic(|| Encoded::parse(&buf))?;
+
+    let filter = resource.filter.read();
+    if hash_shape(&resource, &filter) != encoded.hash_shape() {
+        return Err(Error::IncompatibleFilters);
+    }
+
+    let _write = resource.writes.begin();
+    let changes = resource.changes.get();
+    for (index, (word, other)) in filter.words().zip(encoded.words()).enumerate() {
+        if other & !word.fetch_or(other, Ordering::Relaxed) != 0 {
+            if let Some(changes) = changes {
+                changes.record(index);
+            }
+        }
+    }
+
+    resource
         .inserted_count
-        .read()
-        .map_err(|e| format!("Lock error: {}", e))?;
-    Ok((
-        filter.num_bits(),
-        filter.num_hashes() as usize,
-        resource.false_positive_rate,
-        *count,
-    ))
+        .fetch_max(encoded.inserted_count, Ordering::Relaxed);
+    Ok(resource.inserted_count.load(Ordering::Relaxed))
+}
+
+impl BloomFilterResource {
+    // Overwrites the bits and inserted count under the write lock, so lookups
+    // see either every old bit or every new one. Bits may be cleared, which
+    // diffs can't express, so older versions are invalidated as by `clear`.
+    fn replace_with(
+        &self,
+        shape: (usize, u32, u128, HashAlgorithm),
+        words: impl Iterator<Item = u64>,
+        inserted_count: usize,
+    ) -> Result<usize, Error> {
+        let filter = self.filter.write();
+        if hash_shape(self, &filter) != shape {
+            return Err(Error::IncompatibleFilters);
+        }
+
+        let _write = self.writes.begin();
+        for (word, new) in filter.words().zip(words) {
+            word.store(new, Ordering::Relaxed);
+        }
+        self.inserted_count.store(inserted_count, Ordering::Relaxed);
+        if let Some(changes) = self.changes.get() {
+            changes.clear();
+        }
+        Ok(inserted_count)
+    }
+}
+
+// Swaps the bits of `source` into `resource` in place, so every handle to
+// `resource` sees the rebuilt filter. Returns the new inserted count.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn replace(
+    resource: ResourceArc<BloomFilterResource>,
+    source: ResourceArc<BloomFilterResource>,
+) -> Result<usize, Error> {
+    // Copy the source first so both locks are never held at once.
+    let (shape, words, inserted_count) = {
+        let filter = source.filter.read();
+        (
+            hash_shape(&source, &filter),
+            source.copy_filter(&filter)?,
+            source.inserted_count.load(Ordering::Relaxed),
+        )
+    };
+    resource.replace_with(shape, words.iter(), inserted_count)
+}
+
+// Like `replace`, but from a serialized filter, without building a resource
+// for it first.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn replace_serialized(
+    resource: ResourceArc<BloomFilterResource>,
+    binary: Binary,
+) -> Result<usize, Error> {
+    let buf = catch_panic(|| decompressed(binary.as_slice()))?;
+    let encoded = catch_panic(|| Encoded::parse(&buf))?;
+
+    resource.replace_with(
+        encoded.hash_shape(),
+        encoded.words(),
+        encoded.inserted_count,
+    )
+}
+
+// Estimates the Jaccard index |A ∩ B| / |A ∪ B| of the sets behind two
+// compatible filters, from the estimated sizes of A, B and their union.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn similarity(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<f64, Error> {
+    // Copy one side first so both locks are never held at once.
+    let (a_shape, a_words) = {
+        let filter = a.filter.read();
+        (hash_shape(&a, &filter), a.copy_filter(&filter)?)
+    };
+    let b_filter = b.filter.read();
+    if a_shape != hash_shape(&b, &b_filter) {
+        return Err(Error::IncompatibleFilters);
+    }
+
+    let (num_bits, num_hashes, _, _) = a_shape;
+    let (mut a_set, mut b_set, mut union_set) = (0, 0, 0);
+    for (a_word, b_word) in a_words.iter().zip(b_filter.iter()) {
+        a_set += a_word.count_ones() as usize;
+        b_set += b_word.count_ones() as usize;
+        union_set += (a_word | b_word).count_ones() as usize;
+    }
+
+    // Two empty sets are identical.
+    if union_set == 0 {
+        return Ok(1.0);
+    }
+    let a_items = estimated_items(num_bits, num_hashes, a_set);
+    let b_items = estimated_items(num_bits, num_hashes, b_set);
+    let union_items = estimated_items(num_bits, num_hashes, union_set);
+    let intersection_items = a_items + b_items - union_items;
+    Ok((intersection_items / union_items).clamp(0.0, 1.0))
+}
+
+#[rustler::nif]
+fn compatible(
+    a: ResourceArc<BloomFilterResource>,
+    b: ResourceArc<BloomFilterResource>,
+) -> Result<bool, Error> {
+    let a_shape = hash_shape(&a, &a.filter.read());
+    let b_shape = hash_shape(&b, &b.filter.read());
+    Ok(a_shape == b_shape)
 }
 
 rustler::init!("Elixir.BloomFilterEx.Native", load = on_load);
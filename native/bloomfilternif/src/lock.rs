@@ -0,0 +1,77 @@
+use crate::segmented::Filter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{
+    PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, TryLockResult,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The lock around a bloom filter's bits. Unlike a plain `RwLock`, it doesn't
+// stay poisoned after a panic while it was held for writing: any set of bits
+// is a usable filter, so the lock is recovered and the filter is only flagged
+// as unhealthy. An interrupted insert at worst leaves some of an item's bits
+// set, but an interrupted `clear` can leave items partly removed, which causes
+// false negatives until the filter is rebuilt.
+pub struct FilterLock {
+    lock: RwLock<Filter>,
+    healthy: AtomicBool,
+}
+
+impl FilterLock {
+    pub fn new(filter: Filter) -> Self {
+        FilterLock {
+            lock: RwLock::new(filter),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Filter> {
+        self.lock
+            .read()
+            .unwrap_or_else(|poisoned| self.recover(poisoned))
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, Filter> {
+        self.lock
+            .write()
+            .unwrap_or_else(|poisoned| self.recover(poisoned))
+    }
+
+    // Like `read`, but gives up with `None` once `timeout` has passed.
+    pub fn try_read_for(&self, timeout: Duration) -> Option<RwLockReadGuard<'_, Filter>> {
+        self.poll(timeout, || self.lock.try_read())
+    }
+
+    // False once a panic has been recovered from.
+    pub fn healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    // `RwLock` can't wait with a deadline, so this retries, backing off from
+    // microsecond to millisecond sleeps. The lock is only held for writing by
+    // whole-filter operations such as `clear`, so waits are rare.
+    fn poll<G>(&self, timeout: Duration, try_lock: impl Fn() -> TryLockResult<G>) -> Option<G> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Duration::from_micros(1);
+        loop {
+            match try_lock() {
+                Ok(guard) => return Some(guard),
+                Err(TryLockError::Poisoned(poisoned)) => return Some(self.recover(poisoned)),
+                Err(TryLockError::WouldBlock) => {}
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(Duration::from_millis(1));
+        }
+    }
+
+    fn recover<G>(&self, poisoned: PoisonError<G>) -> G {
+        self.healthy.store(false, Ordering::Relaxed);
+        self.lock.clear_poison();
+        poisoned.into_inner()
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::Error;
+use rustler::{Atom, Term};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod atoms {
+    rustler::atoms! {
+        memory_budget,
+        infinity,
+    }
+}
+
+// Bytes held by the bit arrays and counters of every live filter, and the
+// most they may hold, with 0 meaning no limit.
+static HELD: AtomicUsize = AtomicUsize::new(0);
+static BUDGET: AtomicUsize = AtomicUsize::new(0);
+
+// A share of the bytes counted against the budget, given back when dropped.
+// Each filter holds the share for the storage it sized from its parameters.
+pub(crate) struct Reservation(usize);
+
+impl Reservation {
+    // Fails without counting anything if `bytes` more would go over budget.
+    // Constructors take a reservation before allocating and hand it to the new
+    // resource, so concurrent constructors can never allocate past the budget
+    // between them, and nothing is counted twice.
+    pub(crate) fn new(bytes: usize) -> Result<Self, Error> {
+        HELD.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |held| {
+            let budget = BUDGET.load(Ordering::Relaxed);
+            held.checked_add(bytes)
+                .filter(|&held| budget == 0 || held <= budget)
+        })
+        .map_err(|_| Error::MemoryBudgetExceeded)?;
+        Ok(Reservation(bytes))
+    }
+}
+
+// `len` zeroed values and their share of the budget, for the filters that
+// size their own storage. Running out of memory is returned as an error
+// rather than aborting the VM.
+pub(crate) fn zeroed<T: Clone + Default>(len: usize) -> Result<(Vec<T>, Reservation), Error> {
+    let bytes = len
+        .checked_mul(size_of::<T>())
+        .ok_or(Error::AllocationFailed)?;
+    let memory = Reservation::new(bytes)?;
+    let mut values = Vec::new();
+    values
+        .try_reserve_exact(len)
+        .map_err(|_| Error::AllocationFailed)?;
+    values.resize(len, T::default());
+    Ok((values, memory))
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        HELD.fetch_sub(self.0, Ordering::Relaxed);
+    }
+}
+
+// Sets crate-wide options from a keyword list. `memory_budget` is the most
+// bytes the bit arrays and counters of all filters may hold together, or
+// `:infinity`.
+// Lowering it below what is already held only stops new allocations.
+#[rustler::nif]
+fn configure(opts: Vec<(Atom, Term)>) -> Result<(), Error> {
+    let mut budget = None;
+    for (key, value) in opts.into_iter().rev() {
+        if key == atoms::memory_budget() {
+            budget = match value.decode::<Atom>() {
+                Ok(atom) if atom == atoms::infinity() => Some(0),
+                _ => match value.decode::<usize>() {
+                    Ok(bytes) if bytes > 0 => Some(bytes),
+                    _ => return Err(Error::InvalidOption),
+                },
+            };
+        } else {
+            return Err(Error::InvalidOption);
+        }
+    }
+
+    if let Some(budget) = budget {
+        BUDGET.store(budget, Ordering::Relaxed);
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::BloomFilterResource;
+use rustler::{NifMap, ResourceArc};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Usage counters kept per filter, so callers can export them without wrapping
+// every call site. Batch NIFs add their totals once per call.
+#[derive(Default)]
+pub struct Counters {
+    adds: AtomicU64,
+    positive_results: AtomicU64,
+    negative_results: AtomicU64,
+}
+
+impl Counters {
+    // Counts items passed to the add NIFs, whether or not they were new.
+    pub fn record_adds(&self, items: usize) {
+        self.adds.fetch_add(items as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_lookups(&self, positive: usize, negative: usize) {
+        if positive > 0 {
+            self.positive_results
+                .fetch_add(positive as u64, Ordering::Relaxed);
+        }
+        if negative > 0 {
+            self.negative_results
+                .fetch_add(negative as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_lookup(&self, found: bool) -> bool {
+        self.record_lookups(found as usize, !found as usize);
+        found
+    }
+
+    fn snapshot(&self, take: impl Fn(&AtomicU64) -> u64) -> CounterValues {
+        let positive_results = take(&self.positive_results);
+        let negative_results = take(&self.negative_results);
+        CounterValues {
+            adds: take(&self.adds),
+            member_checks: positive_results + negative_results,
+            positive_results,
+            negative_results,
+        }
+    }
+}
+
+#[derive(NifMap)]
+struct CounterValues {
+    adds: u64,
+    member_checks: u64,
+    positive_results: u64,
+    negative_results: u64,
+}
+
+#[rustler::nif]
+fn counters(resource: ResourceArc<BloomFilterResource>) -> CounterValues {
+    resource
+        .counters
+        .snapshot(|counter| counter.load(Ordering::Relaxed))
+}
+
+// Returns the counters and sets them back to zero. Each counter is swapped
+// atomically, so no event is lost or counted twice between two resets.
+#[rustler::nif]
+fn reset_counters(resource: ResourceArc<BloomFilterResource>) -> CounterValues {
+    resource
+        .counters
+        .snapshot(|counter| counter.swap(0, Ordering::Relaxed))
+}
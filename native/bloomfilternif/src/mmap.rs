@@ -0,0 +1,325 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::{bit_indexes, optimal_size, random_seed, validate_params, MAX_NUM_BITS};
+use fastbloom::DefaultHasher;
+use memmap2::{Mmap, MmapMut};
+use rustler::{NifMap, Resource, ResourceArc, Term};
+use std::fs::{File, OpenOptions};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MMAP_MAGIC: &[u8; 4] = b"BFMM";
+const MMAP_VERSION: u8 = 1;
+// Files of more than 2^32 bits, which fastbloom's derivation of bit indexes
+// can't reach past the first 2^32 of. They use `wide_bit_indexes` instead.
+const MMAP_WIDE_VERSION: u8 = 2;
+
+// magic + version + padding + num_hashes + padding + capacity + false positive
+// rate + seed + num_bits + inserted_count. The header is a whole number of
+// words so the bit array that follows it is aligned for atomic access.
+const MMAP_HEADER_LEN: usize = 64;
+const INSERTED_COUNT_OFFSET: usize = 56;
+
+struct MmapHeader {
+    num_hashes: u32,
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u128,
+    num_bits: u64,
+}
+
+impl MmapHeader {
+    fn wide(&self) -> bool {
+        self.num_bits > MAX_NUM_BITS as u64
+    }
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(MMAP_MAGIC);
+        buf[4] = if self.wide() {
+            MMAP_WIDE_VERSION
+        } else {
+            MMAP_VERSION
+        };
+        buf[8..12].copy_from_slice(&self.num_hashes.to_le_bytes());
+        buf[16..24].copy_from_slice(&(self.capacity as u64).to_le_bytes());
+        buf[24..32].copy_from_slice(&self.false_positive_rate.to_le_bytes());
+        buf[32..48].copy_from_slice(&self.seed.to_le_bytes());
+        buf[48..56].copy_from_slice(&self.num_bits.to_le_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < MMAP_HEADER_LEN || &buf[0..4] != MMAP_MAGIC {
+            return Err(Error::InvalidData);
+        }
+        if buf[4] != MMAP_VERSION && buf[4] != MMAP_WIDE_VERSION {
+            return Err(Error::UnsupportedVersion);
+        }
+
+        let header = MmapHeader {
+            num_hashes: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            capacity: u64::from_le_bytes(buf[16..24].try_into().unwrap()) as usize,
+            false_positive_rate: f64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            seed: u128::from_le_bytes(buf[32..48].try_into().unwrap()),
+            num_bits: u64::from_le_bytes(buf[48..56].try_into().unwrap()),
+        };
+        if header.num_hashes == 0 || header.num_bits == 0 || !header.num_bits.is_multiple_of(64) {
+            return Err(Error::InvalidData);
+        }
+        if (buf.len() - MMAP_HEADER_LEN) as u64 != header.num_bits / 8 {
+            return Err(Error::CorruptData);
+        }
+        // Version 1 files bigger than 2^32 bits, from before version 2, keep
+        // the indexes they were written with.
+        if buf[4] == MMAP_WIDE_VERSION && !header.wide() {
+            return Err(Error::InvalidData);
+        }
+        Ok(header)
+    }
+}
+
+// Files opened by `open_shared_readonly` are mapped without write access, so
+// any write to them would fault; inserts check for that first.
+enum Mapping {
+    ReadWrite(MmapMut),
+    ReadOnly(Mmap),
+}
+
+impl Mapping {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Mapping::ReadWrite(map) => map,
+            Mapping::ReadOnly(map) => map,
+        }
+    }
+}
+
+// A bloom filter whose bit array lives in a memory-mapped file instead of the
+// process heap. Bits are set with atomic operations directly on the mapping,
+// so the page cache holds the filter, and reopening the file after a restart
+// maps it again without reading or decoding it. Items hash exactly as they do
+// in `BloomFilterResource`, so the bits match a regular filter with the same
+// parameters and seed, for the sizes a regular filter can have.
+pub struct MmapBloomFilterResource {
+    map: Mapping,
+    hasher: DefaultHasher,
+    num_hashes: u32,
+    num_bits: u64,
+    wide: bool,
+    capacity: usize,
+    false_positive_rate: f64,
+}
+
+impl Resource for MmapBloomFilterResource {}
+
+// `bit_indexes`, but mapping each hash onto the bit array with all 64 of its
+// bits, so that every bit of a filter of more than 2^32 bits can be reached.
+fn wide_bit_indexes(num_bits: u64, num_hashes: u32, source_hash: u64) -> Vec<u64> {
+    let mut h1 = source_hash;
+    let h2 = source_hash
+        .wrapping_shr(32)
+        .wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+
+    (0..num_hashes)
+        .map(|_| {
+            h1 = h1.wrapping_add(h2).rotate_left(5);
+            ((h1 as u128 * num_bits as u128) >> 64) as u64
+        })
+        .collect()
+}
+
+impl MmapBloomFilterResource {
+    fn from_map(map: Mapping) -> Result<Self, Error> {
+        let header = MmapHeader::decode(map.bytes())?;
+        Ok(MmapBloomFilterResource {
+            wide: map.bytes()[4] == MMAP_WIDE_VERSION,
+            map,
+            hasher: DefaultHasher::seeded(&header.seed.to_be_bytes()),
+            num_hashes: header.num_hashes,
+            num_bits: header.num_bits,
+            capacity: header.capacity,
+            false_positive_rate: header.false_positive_rate,
+        })
+    }
+
+    // SAFETY (for both): other processes may write to the file while it is
+    // mapped, but once validated the mapping is only accessed through atomics.
+    fn map_file(file: &File) -> Result<MmapMut, Error> {
+        Ok(unsafe { MmapMut::map_mut(file)? })
+    }
+
+    fn map_file_readonly(file: &File) -> Result<Mmap, Error> {
+        Ok(unsafe { Mmap::map(file)? })
+    }
+
+    fn words(&self) -> &[AtomicU64] {
+        let len = (self.num_bits / 64) as usize;
+        // SAFETY: `map` is page-aligned and was checked to hold `len` words
+        // after the header, which is itself a whole number of words. The words
+        // are only accessed atomically while `self` keeps the mapping alive.
+        unsafe {
+            std::slice::from_raw_parts(
+                self.map.bytes().as_ptr().add(MMAP_HEADER_LEN) as *const AtomicU64,
+                len,
+            )
+        }
+    }
+
+    fn inserted_count(&self) -> &AtomicU64 {
+        // SAFETY: as for `words`, the offset is word-aligned and inside the header.
+        unsafe { &*(self.map.bytes().as_ptr().add(INSERTED_COUNT_OFFSET) as *const AtomicU64) }
+    }
+
+    fn indexes(&self, item: &[u8]) -> Vec<u64> {
+        let hash = self.hasher.hash_one(item);
+        if self.wide {
+            wide_bit_indexes(self.num_bits, self.num_hashes, hash)
+        } else {
+            bit_indexes(self.num_bits, self.num_hashes, hash)
+        }
+    }
+
+    // Returns whether the item was new, counting it when it was.
+    fn insert(&self, item: &[u8]) -> Result<bool, Error> {
+        if let Mapping::ReadOnly(_) = self.map {
+            return Err(Error::ReadOnly);
+        }
+        let words = self.words();
+
+        let mut previously_contained = true;
+        for index in self.indexes(item) {
+            let mask = 1 << (index % 64);
+            previously_contained &=
+                words[(index / 64) as usize].fetch_or(mask, Ordering::Relaxed) & mask != 0;
+        }
+        if !previously_contained {
+            self.inserted_count().fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(!previously_contained)
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        let words = self.words();
+
+        self.indexes(item).into_iter().all(|index| {
+            words[(index / 64) as usize].load(Ordering::Relaxed) & (1 << (index % 64)) != 0
+        })
+    }
+}
+
+// Creates `path`, which must not exist yet, sized for `capacity` items at
+// `false_positive_rate`. The file is sparse until bits are set in it.
+#[rustler::nif(schedule = "DirtyIo")]
+fn new_mmap(
+    path: String,
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<MmapBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    let (num_words, num_hashes) = optimal_size(capacity, false_positive_rate)?;
+    let header = MmapHeader {
+        num_hashes,
+        capacity,
+        false_positive_rate,
+        seed: random_seed()?,
+        num_bits: num_words as u64 * 64,
+    };
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.set_len((MMAP_HEADER_LEN + num_words * 8) as u64)?;
+
+    let mut map = MmapBloomFilterResource::map_file(&file)?;
+    header.encode(&mut map[..MMAP_HEADER_LEN]);
+    map.flush_range(0, MMAP_HEADER_LEN)?;
+
+    Ok(ResourceArc::new(MmapBloomFilterResource::from_map(
+        Mapping::ReadWrite(map),
+    )?))
+}
+
+// Maps a file created by `new_mmap`, with the bits and count it had when it
+// was last written.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_mmap(path: String) -> Result<ResourceArc<MmapBloomFilterResource>, Error> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let map = MmapBloomFilterResource::map_file(&file)?;
+    Ok(ResourceArc::new(MmapBloomFilterResource::from_map(
+        Mapping::ReadWrite(map),
+    )?))
+}
+
+// Maps a file created by `new_mmap` for lookups only. The OS shares the pages
+// between every process mapping the file, so several nodes on one host can
+// probe a single copy of a large filter, and they see bits set through a
+// writable mapping as soon as they are set. Inserts return `ReadOnly`.
+#[rustler::nif(schedule = "DirtyIo")]
+fn open_shared_readonly(path: String) -> Result<ResourceArc<MmapBloomFilterResource>, Error> {
+    let file = File::open(path)?;
+    let map = MmapBloomFilterResource::map_file_readonly(&file)?;
+    Ok(ResourceArc::new(MmapBloomFilterResource::from_map(
+        Mapping::ReadOnly(map),
+    )?))
+}
+
+#[rustler::nif]
+fn mmap_add(resource: ResourceArc<MmapBloomFilterResource>, term: Term) -> Result<bool, Error> {
+    resource.insert(term.to_binary().as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn mmap_add_many(
+    resource: ResourceArc<MmapBloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<usize, Error> {
+    let mut added = 0;
+    for item in &items {
+        added += resource.insert(item.as_slice())? as usize;
+    }
+    Ok(added)
+}
+
+#[rustler::nif]
+fn mmap_member(resource: ResourceArc<MmapBloomFilterResource>, term: Term) -> bool {
+    resource.contains(term.to_binary().as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn mmap_member_many(resource: ResourceArc<MmapBloomFilterResource>, items: Vec<Item>) -> Vec<bool> {
+    items
+        .iter()
+        .map(|item| resource.contains(item.as_slice()))
+        .collect()
+}
+
+// Bits reach the file whenever the OS writes dirty pages back; this blocks
+// until every change so far is on disk.
+#[rustler::nif(schedule = "DirtyIo")]
+fn mmap_flush(resource: ResourceArc<MmapBloomFilterResource>) -> Result<(), Error> {
+    match &resource.map {
+        Mapping::ReadWrite(map) => Ok(map.flush()?),
+        Mapping::ReadOnly(_) => Ok(()),
+    }
+}
+
+#[derive(NifMap)]
+struct MmapStats {
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    false_positive_rate: f64,
+    inserted_count: u64,
+}
+
+#[rustler::nif]
+fn mmap_stats(resource: ResourceArc<MmapBloomFilterResource>) -> MmapStats {
+    MmapStats {
+        num_bits: resource.num_bits,
+        num_hashes: resource.num_hashes,
+        capacity: resource.capacity,
+        false_positive_rate: resource.false_positive_rate,
+        inserted_count: resource.inserted_count().load(Ordering::Relaxed),
+    }
+}
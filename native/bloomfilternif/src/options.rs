@@ -0,0 +1,111 @@
+use crate::error::{catch_panic, Error};
+use crate::hasher::HashAlgorithm;
+use crate::memory::Reservation;
+use crate::segmented::Filter;
+use crate::{optimal_size, random_seed, validate_params, BloomFilterResource};
+use rustler::{Atom, ResourceArc, Term};
+
+mod atoms {
+    rustler::atoms! {
+        capacity,
+        false_positive_rate,
+        seed,
+        num_hashes,
+        strict,
+        hash,
+    }
+}
+
+// Options accepted by `new_with_opts`, decoded from a keyword list so new
+// options can be added without changing the NIF's arity.
+struct FilterOptions {
+    capacity: Option<usize>,
+    false_positive_rate: Option<f64>,
+    seed: Option<u128>,
+    num_hashes: Option<u32>,
+    strict: bool,
+    hash: HashAlgorithm,
+}
+
+impl FilterOptions {
+    // Keys are read in reverse so the first occurrence of a repeated key wins,
+    // as with `Keyword.get/2`. Unknown keys are rejected rather than ignored,
+    // so a misspelled option can't silently fall back to a default.
+    // `block_size` is one of them: fastbloom 0.14 has no blocked layout to
+    // choose a size for.
+    fn decode(opts: Vec<(Atom, Term)>) -> Result<Self, Error> {
+        let mut options = FilterOptions {
+            capacity: None,
+            false_positive_rate: None,
+            seed: None,
+            num_hashes: None,
+            strict: false,
+            hash: HashAlgorithm::default(),
+        };
+
+        for (key, value) in opts.into_iter().rev() {
+            if key == atoms::capacity() {
+                options.capacity = Some(value.decode().map_err(|_| Error::InvalidCapacity)?);
+            } else if key == atoms::false_positive_rate() {
+                let rate = value
+                    .decode()
+                    .map_err(|_| Error::InvalidFalsePositiveRate)?;
+                options.false_positive_rate = Some(rate);
+            } else if key == atoms::seed() {
+                options.seed = Some(value.decode().map_err(|_| Error::InvalidOption)?);
+            } else if key == atoms::num_hashes() {
+                options.num_hashes = Some(value.decode().map_err(|_| Error::InvalidNumHashes)?);
+            } else if key == atoms::strict() {
+                options.strict = value.decode().map_err(|_| Error::InvalidOption)?;
+            } else if key == atoms::hash() {
+                let algorithm = value.decode().map_err(|_| Error::InvalidOption)?;
+                options.hash = HashAlgorithm::from_atom(algorithm)?;
+            } else {
+                return Err(Error::InvalidOption);
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Builds a filter from options. `capacity` and `false_positive_rate` size the
+// bit array as in `new`; `num_hashes` overrides the hash count fastbloom would
+// pick for that size, and the seed is random unless `seed` is given. `hash`
+// picks the hash algorithm, SipHash-1-3 by default.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn new_with_opts(opts: Vec<(Atom, Term)>) -> Result<ResourceArc<BloomFilterResource>, Error> {
+    let options = FilterOptions::decode(opts)?;
+    let capacity = options.capacity.ok_or(Error::InvalidCapacity)?;
+    let false_positive_rate = options
+        .false_positive_rate
+        .ok_or(Error::InvalidFalsePositiveRate)?;
+    validate_params(capacity, false_positive_rate)?;
+    if options.num_hashes == Some(0) {
+        return Err(Error::InvalidNumHashes);
+    }
+    let seed = match options.seed {
+        Some(seed) => seed,
+        None => random_seed()?,
+    };
+    let resource = catch_panic(|| match options.num_hashes {
+        Some(num_hashes) => {
+            let (num_words, _) = optimal_size(capacity, false_positive_rate)?;
+            let memory = Reservation::new(num_words * 8)?;
+            let filter = Filter::zeroed(num_words, options.hash, seed, num_hashes)?;
+            Ok(BloomFilterResource::from_filter(
+                filter,
+                memory,
+                capacity,
+                false_positive_rate,
+                seed,
+                options.hash,
+                0,
+            ))
+        }
+        None => BloomFilterResource::new(capacity, false_positive_rate, seed, options.hash),
+    })?;
+    Ok(ResourceArc::new(BloomFilterResource {
+        strict: options.strict,
+        ..resource
+    }))
+}
@@ -0,0 +1,225 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::{zeroed, Reservation};
+use crate::{collect_words, validate_params};
+use rustler::{Binary, Env, NifMap, OwnedBinary, Resource, ResourceArc};
+use std::f64::consts::LN_2;
+use std::sync::RwLock;
+
+// A bloom filter whose bit array is split into one slice per hash function,
+// as in Almeida et al.'s scalable bloom filters. Hash function `i` only sets
+// bits in slice `i`, so every item sets exactly `num_hashes` distinct bits and
+// the slices fill up evenly. Items are hashed as raw bytes with murmur3, which
+// other implementations can reproduce, so the bits can be exchanged with them
+// through `partitioned_to_bits` and `partitioned_from_bits`.
+struct PartitionedBloomFilter {
+    words: Vec<u64>,
+    num_hashes: u32,
+    slice_bits: u64,
+    seed: u32,
+    _memory: Reservation,
+}
+
+impl PartitionedBloomFilter {
+    // k = log2(1/p) slices of m/k bits, with m = -n ln(p) / ln(2)^2. Slices are
+    // rounded up to whole words so each one starts on a word boundary.
+    fn new(capacity: usize, false_positive_rate: f64, seed: u32) -> Result<Self, Error> {
+        let num_hashes = (1.0 / false_positive_rate).log2().ceil().max(1.0) as u32;
+        let num_bits = -(capacity as f64) * false_positive_rate.ln() / (LN_2 * LN_2);
+        let slice_words = (num_bits / num_hashes as f64 / 64.0).ceil().max(1.0) as u64;
+        let num_words = slice_words
+            .checked_mul(num_hashes as u64)
+            .ok_or(Error::AllocationFailed)?;
+        let (words, memory) = zeroed(num_words as usize)?;
+
+        Ok(PartitionedBloomFilter {
+            words,
+            num_hashes,
+            slice_bits: slice_words * 64,
+            seed,
+            _memory: memory,
+        })
+    }
+
+    // The array is `num_hashes * slice_bits` bits long, padded to whole words.
+    fn num_bytes(&self) -> usize {
+        (self.num_hashes as u64 * self.slice_bits).div_ceil(8) as usize
+    }
+
+    // Kirsch-Mitzenmacher double hashing over the two halves of the item's
+    // murmur3 x64 128-bit hash, h1 the low 64 bits and h2 the high 64 bits,
+    // with index i taken from slice i:
+    // bit_i = i * slice_bits + (h1 + i * h2) mod slice_bits, wrapping at 2^64.
+    fn bit_indexes(&self, item: &[u8]) -> impl Iterator<Item = u64> {
+        let hash =
+            murmur3::murmur3_x64_128(&mut &item[..], self.seed).expect("reading from a slice");
+        let (h1, h2) = (hash as u64, (hash >> 64) as u64);
+        let slice_bits = self.slice_bits;
+        (0..self.num_hashes as u64)
+            .map(move |i| i * slice_bits + h1.wrapping_add(i.wrapping_mul(h2)) % slice_bits)
+    }
+
+    fn insert(&mut self, item: &[u8]) -> bool {
+        let mut changed = false;
+        for index in self.bit_indexes(item) {
+            let (word, mask) = ((index / 64) as usize, 1 << (index % 64));
+            changed |= self.words[word] & mask == 0;
+            self.words[word] |= mask;
+        }
+        changed
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.bit_indexes(item)
+            .all(|index| self.words[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    // Each slice fills like a one-hash filter of `slice_bits` bits, so with a
+    // share x of the bits set, n = -slice_bits * ln(1 - x).
+    fn estimated_items(&self) -> usize {
+        let set: u64 = self.words.iter().map(|word| word.count_ones() as u64).sum();
+        let share = set as f64 / (self.num_hashes as u64 * self.slice_bits) as f64;
+        (-(self.slice_bits as f64) * (1.0 - share).ln()).round() as usize
+    }
+}
+
+pub struct PartitionedBloomFilterResource {
+    filter: RwLock<PartitionedBloomFilter>,
+}
+
+impl Resource for PartitionedBloomFilterResource {}
+
+#[derive(NifMap)]
+struct PartitionedLayout {
+    num_hashes: u32,
+    slice_bits: u64,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn partitioned_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u32,
+) -> Result<ResourceArc<PartitionedBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    Ok(ResourceArc::new(PartitionedBloomFilterResource {
+        filter: RwLock::new(PartitionedBloomFilter::new(
+            capacity,
+            false_positive_rate,
+            seed,
+        )?),
+    }))
+}
+
+#[rustler::nif]
+fn partitioned_layout(
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+) -> Result<PartitionedLayout, Error> {
+    let filter = resource.filter.read()?;
+    Ok(PartitionedLayout {
+        num_hashes: filter.num_hashes,
+        slice_bits: filter.slice_bits,
+    })
+}
+
+// Returns whether any bit changed, so repeated items aren't counted twice.
+#[rustler::nif]
+fn partitioned_add(
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+    item: Item,
+) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(filter.insert(item.as_slice()))
+}
+
+// Returns the number of items that changed any bit.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn partitioned_add_many(
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<usize, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(items
+        .iter()
+        .filter(|item| filter.insert(item.as_slice()))
+        .count())
+}
+
+#[rustler::nif]
+fn partitioned_member(
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+    item: Item,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn partitioned_member_many(
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+    items: Vec<Item>,
+) -> Result<Vec<bool>, Error> {
+    let filter = resource.filter.read()?;
+    Ok(items
+        .iter()
+        .map(|item| filter.contains(item.as_slice()))
+        .collect())
+}
+
+// The slices in order, in the same bit order as `to_bits`: bit `j` of the
+// array is bit `j % 8` of byte `j / 8`.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn partitioned_to_bits<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<PartitionedBloomFilterResource>,
+) -> Result<Binary<'a>, Error> {
+    let filter = resource.filter.read()?;
+
+    let mut binary = OwnedBinary::new(filter.num_bytes()).ok_or(Error::AllocationFailed)?;
+    let bytes = filter.words.iter().flat_map(|word| word.to_le_bytes());
+    for (byte, word_byte) in binary.as_mut_slice().iter_mut().zip(bytes) {
+        *byte = word_byte;
+    }
+    Ok(binary.release(env))
+}
+
+// Rebuilds a filter from `num_hashes` equal slices laid out as by
+// `partitioned_to_bits`, returning it with the number of items estimated from
+// the bits set. Slices need not be whole words, but must be whole bytes
+// between them.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn partitioned_from_bits(
+    bits: Binary,
+    num_hashes: u32,
+    seed: u32,
+) -> Result<(ResourceArc<PartitionedBloomFilterResource>, usize), Error> {
+    if num_hashes == 0 {
+        return Err(Error::InvalidNumHashes);
+    }
+    let num_bits = bits.len() as u64 * 8;
+    if num_bits == 0 || !num_bits.is_multiple_of(num_hashes as u64) {
+        return Err(Error::InvalidNumBits);
+    }
+
+    let memory = Reservation::new(bits.len().next_multiple_of(8))?;
+    let words = collect_words(bits.chunks(8).map(|chunk| {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        u64::from_le_bytes(word)
+    }))?;
+    let filter = PartitionedBloomFilter {
+        words,
+        num_hashes,
+        slice_bits: num_bits / num_hashes as u64,
+        seed,
+        _memory: memory,
+    };
+    let inserted_count = filter.estimated_items();
+    Ok((
+        ResourceArc::new(PartitionedBloomFilterResource {
+            filter: RwLock::new(filter),
+        }),
+        inserted_count,
+    ))
+}
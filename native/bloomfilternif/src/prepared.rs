@@ -0,0 +1,52 @@
+use crate::alarm::check_fill;
+use crate::error::Error;
+use crate::item::Item;
+use crate::{fold_hashes, BloomFilterResource};
+use rustler::{Resource, ResourceArc};
+use xxhash_rust::xxh3::xxh3_128;
+
+// An item hashed once, to probe any number of filters without rehashing it.
+// The hash is unseeded xxh3-128, split into the pair `add_hashed` takes, so a
+// prepared key is the same item as `add_hashed` given those halves and never
+// matches items added with `add`.
+pub struct PreparedKeyResource {
+    hash1: u64,
+    hash2: u64,
+}
+
+impl Resource for PreparedKeyResource {}
+
+impl PreparedKeyResource {
+    fn hash(&self) -> u64 {
+        fold_hashes(self.hash1, self.hash2)
+    }
+}
+
+#[rustler::nif]
+fn prepare(item: Item) -> ResourceArc<PreparedKeyResource> {
+    let hash = xxh3_128(item.as_slice());
+    ResourceArc::new(PreparedKeyResource {
+        hash1: (hash >> 64) as u64,
+        hash2: hash as u64,
+    })
+}
+
+#[rustler::nif]
+fn add_prepared(
+    resource: ResourceArc<BloomFilterResource>,
+    prepared: ResourceArc<PreparedKeyResource>,
+) -> Result<bool, Error> {
+    resource
+        .insert_hash(prepared.hash())
+        .inspect(|_| check_fill(&resource))
+}
+
+#[rustler::nif]
+fn member_prepared(
+    resource: ResourceArc<BloomFilterResource>,
+    prepared: ResourceArc<PreparedKeyResource>,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read();
+    let found = filter.contains_hash(prepared.hash());
+    Ok(resource.counters.record_lookup(found))
+}
@@ -0,0 +1,504 @@
+use crate::error::Error;
+use crate::memory::{zeroed, Reservation};
+use crate::{random_seed, validate_params};
+use fastbloom::DefaultHasher;
+use rustler::{Binary, NifMap, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// Quotient filters slow down sharply as they fill, so inserts stop here.
+const MAX_LOAD_FACTOR: f64 = 0.95;
+
+// The three metadata bits at the bottom of every slot. `OCCUPIED` belongs to
+// the slot's index: some stored fingerprint has that index as its quotient.
+// The other two belong to the remainder stored in the slot: `CONTINUATION`
+// when it is not the first remainder of its run, and `SHIFTED` when it is not
+// in its quotient's slot.
+const OCCUPIED: u64 = 1;
+const CONTINUATION: u64 = 2;
+const SHIFTED: u64 = 4;
+const METADATA_BITS: u32 = 3;
+
+fn is_empty(slot: u64) -> bool {
+    slot & (OCCUPIED | CONTINUATION | SHIFTED) == 0
+}
+
+fn is_cluster_start(slot: u64) -> bool {
+    slot & OCCUPIED != 0 && slot & (CONTINUATION | SHIFTED) == 0
+}
+
+fn is_run_start(slot: u64) -> bool {
+    slot & CONTINUATION == 0 && slot & (OCCUPIED | SHIFTED) != 0
+}
+
+// A quotient filter, following Bender et al., "Don't Thrash: How to Cache
+// Your Hash on Flash". Each item has a `quotient_bits + remainder_bits` bit
+// fingerprint taken from the top of its hash. The quotient picks a slot and
+// the remainder is stored there, or in the next free slot with the table kept
+// sorted by quotient, so lookups scan one short run of adjacent slots.
+// Fingerprints are stored exactly, which makes deletion possible, and they
+// can be read back out, which is how filters are merged and resized.
+struct QuotientFilter {
+    // Slots of `remainder_bits + METADATA_BITS` bits, packed end to end.
+    words: Vec<u64>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+    seed: u128,
+    hasher: DefaultHasher,
+    _memory: Reservation,
+}
+
+impl QuotientFilter {
+    fn new(quotient_bits: u32, remainder_bits: u32, seed: u128) -> Result<Self, Error> {
+        let slot_bits = (remainder_bits + METADATA_BITS) as usize;
+        let table_bits = 1usize
+            .checked_shl(quotient_bits)
+            .and_then(|num_slots| num_slots.checked_mul(slot_bits))
+            .ok_or(Error::AllocationFailed)?;
+        // One spare word, so reading a slot never needs a bounds check.
+        let (words, memory) = zeroed(table_bits / 64 + 1)?;
+        Ok(QuotientFilter {
+            words,
+            quotient_bits,
+            remainder_bits,
+            len: 0,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+            _memory: memory,
+        })
+    }
+
+    // The smallest table that holds `capacity` items, with enough fingerprint
+    // bits that about `false_positive_rate` of lookups for absent items find
+    // one: a lookup matches one of n fingerprints of p bits about n / 2^p of
+    // the time.
+    fn with_capacity(capacity: usize, false_positive_rate: f64, seed: u128) -> Result<Self, Error> {
+        let quotient_bits = quotient_bits_for(capacity);
+        let fingerprint_bits = (capacity as f64 / false_positive_rate).log2().ceil() as u32;
+        let remainder_bits = fingerprint_bits.saturating_sub(quotient_bits).max(1);
+        if quotient_bits + remainder_bits > 64 {
+            return Err(Error::InvalidFalsePositiveRate);
+        }
+        QuotientFilter::new(quotient_bits, remainder_bits, seed)
+    }
+
+    fn num_slots(&self) -> usize {
+        1 << self.quotient_bits
+    }
+
+    fn max_len(&self) -> usize {
+        (self.num_slots() as f64 * MAX_LOAD_FACTOR) as usize
+    }
+
+    fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits + self.remainder_bits
+    }
+
+    fn fingerprint(&self, item: &[u8]) -> u64 {
+        self.hasher.hash_one(item) >> (64 - self.fingerprint_bits())
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let remainder = fingerprint & ((1 << self.remainder_bits) - 1);
+        ((fingerprint >> self.remainder_bits) as usize, remainder)
+    }
+
+    fn get(&self, index: usize) -> u64 {
+        let slot_bits = self.remainder_bits + METADATA_BITS;
+        let bit = index * slot_bits as usize;
+        let (word, offset) = (bit / 64, (bit % 64) as u32);
+
+        let mut slot = self.words[word] >> offset;
+        if offset + slot_bits > 64 {
+            slot |= self.words[word + 1] << (64 - offset);
+        }
+        slot & (u64::MAX >> (64 - slot_bits))
+    }
+
+    fn set(&mut self, index: usize, slot: u64) {
+        let slot_bits = self.remainder_bits + METADATA_BITS;
+        let bit = index * slot_bits as usize;
+        let (word, offset) = (bit / 64, (bit % 64) as u32);
+        let mask = u64::MAX >> (64 - slot_bits);
+
+        self.words[word] = (self.words[word] & !(mask << offset)) | (slot << offset);
+        if offset + slot_bits > 64 {
+            let high = 64 - offset;
+            self.words[word + 1] = (self.words[word + 1] & !(mask >> high)) | (slot >> high);
+        }
+    }
+
+    fn next(&self, index: usize) -> usize {
+        (index + 1) & (self.num_slots() - 1)
+    }
+
+    fn prev(&self, index: usize) -> usize {
+        index.wrapping_sub(1) & (self.num_slots() - 1)
+    }
+
+    // The slot where the run of remainders for `quotient` starts, or would
+    // start: walk back to the start of the cluster, then forward one run per
+    // occupied quotient until reaching `quotient`.
+    fn run_start(&self, quotient: usize) -> usize {
+        let mut index = quotient;
+        while self.get(index) & SHIFTED != 0 {
+            index = self.prev(index);
+        }
+
+        let mut run = index;
+        while index != quotient {
+            loop {
+                run = self.next(run);
+                if self.get(run) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            loop {
+                index = self.next(index);
+                if self.get(index) & OCCUPIED != 0 {
+                    break;
+                }
+            }
+        }
+        run
+    }
+
+    // Puts `slot` at `index`, moving every slot after it up to the next empty
+    // one along by one. The `OCCUPIED` bits stay where they are.
+    fn shift_in(&mut self, mut index: usize, mut slot: u64) {
+        loop {
+            let mut displaced = self.get(index);
+            let empty = is_empty(displaced);
+            if !empty {
+                displaced |= SHIFTED;
+                if displaced & OCCUPIED != 0 {
+                    slot |= OCCUPIED;
+                    displaced &= !OCCUPIED;
+                }
+            }
+            self.set(index, slot);
+            if empty {
+                return;
+            }
+            slot = displaced;
+            index = self.next(index);
+        }
+    }
+
+    // Returns whether the fingerprint was new.
+    fn insert_fingerprint(&mut self, fingerprint: u64) -> Result<bool, Error> {
+        if self.len >= self.max_len() {
+            return match self.contains_fingerprint(fingerprint) {
+                true => Ok(false),
+                false => Err(Error::FilterFull),
+            };
+        }
+
+        let (quotient, remainder) = self.split(fingerprint);
+        let canonical = self.get(quotient);
+        let mut slot = remainder << METADATA_BITS;
+
+        if is_empty(canonical) {
+            self.set(quotient, slot | OCCUPIED);
+            self.len += 1;
+            return Ok(true);
+        }
+
+        if canonical & OCCUPIED == 0 {
+            self.set(quotient, canonical | OCCUPIED);
+        }
+        let start = self.run_start(quotient);
+        let mut index = start;
+
+        if canonical & OCCUPIED != 0 {
+            // Find where the remainder goes in the run, which is kept sorted.
+            loop {
+                let stored = self.get(index) >> METADATA_BITS;
+                if stored == remainder {
+                    return Ok(false);
+                }
+                if stored > remainder {
+                    break;
+                }
+                index = self.next(index);
+                if self.get(index) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+        }
+
+        if canonical & OCCUPIED != 0 {
+            if index == start {
+                let head = self.get(start);
+                self.set(start, head | CONTINUATION);
+            } else {
+                slot |= CONTINUATION;
+            }
+        }
+        if index != quotient {
+            slot |= SHIFTED;
+        }
+        self.shift_in(index, slot);
+        self.len += 1;
+        Ok(true)
+    }
+
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.get(quotient) & OCCUPIED == 0 {
+            return false;
+        }
+
+        let mut index = self.run_start(quotient);
+        loop {
+            let stored = self.get(index) >> METADATA_BITS;
+            if stored == remainder {
+                return true;
+            }
+            if stored > remainder {
+                return false;
+            }
+            index = self.next(index);
+            if self.get(index) & CONTINUATION == 0 {
+                return false;
+            }
+        }
+    }
+
+    // Returns whether the fingerprint was found and removed.
+    fn delete_fingerprint(&mut self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        let canonical = self.get(quotient);
+        if canonical & OCCUPIED == 0 {
+            return false;
+        }
+
+        let mut index = self.run_start(quotient);
+        loop {
+            let stored = self.get(index) >> METADATA_BITS;
+            if stored == remainder {
+                break;
+            }
+            if stored > remainder {
+                return false;
+            }
+            index = self.next(index);
+            if self.get(index) & CONTINUATION == 0 {
+                return false;
+            }
+        }
+
+        let removed = self.get(index);
+        let was_run_start = is_run_start(removed);
+        if was_run_start && self.get(self.next(index)) & CONTINUATION == 0 {
+            // The run's only remainder is going, so no fingerprint has this quotient.
+            self.set(quotient, self.get(quotient) & !OCCUPIED);
+        }
+
+        self.shift_out(index, quotient);
+
+        if was_run_start {
+            // The next remainder of the run, if any, now starts it.
+            let next = self.get(index);
+            let mut updated = next & !CONTINUATION;
+            if index == quotient && is_run_start(updated) {
+                updated &= !SHIFTED;
+            }
+            if updated != next {
+                self.set(index, updated);
+            }
+        }
+
+        self.len -= 1;
+        true
+    }
+
+    // Removes the remainder at `index`, moving the rest of the cluster after
+    // it down by one. `quotient` is the quotient of the removed remainder,
+    // and tracks the quotient of each run as it moves, so remainders that
+    // land back in their own slot stop being marked as shifted.
+    fn shift_out(&mut self, mut index: usize, mut quotient: usize) {
+        let start = index;
+        let mut current = self.get(index);
+        let mut following = self.next(index);
+
+        loop {
+            let next = self.get(following);
+            let occupied = current & OCCUPIED;
+            if is_empty(next) || is_cluster_start(next) || following == start {
+                self.set(index, 0);
+                return;
+            }
+
+            let mut updated = next;
+            if is_run_start(next) {
+                loop {
+                    quotient = self.next(quotient);
+                    if self.get(quotient) & OCCUPIED != 0 {
+                        break;
+                    }
+                }
+                if occupied != 0 && quotient == index {
+                    updated &= !SHIFTED;
+                }
+            }
+            self.set(index, (updated & !OCCUPIED) | occupied);
+
+            index = following;
+            following = self.next(following);
+            current = next;
+        }
+    }
+
+    // Every stored fingerprint, in no particular order.
+    fn fingerprints(&self) -> Vec<u64> {
+        let mut fingerprints = Vec::with_capacity(self.len);
+        if self.len == 0 {
+            return fingerprints;
+        }
+
+        let mut index = 0;
+        while !is_cluster_start(self.get(index)) {
+            index = self.next(index);
+        }
+
+        let mut quotient = index;
+        for _ in 0..self.num_slots() {
+            let slot = self.get(index);
+            if is_cluster_start(slot) {
+                quotient = index;
+            } else if is_run_start(slot) {
+                loop {
+                    quotient = self.next(quotient);
+                    if self.get(quotient) & OCCUPIED != 0 {
+                        break;
+                    }
+                }
+            }
+            if !is_empty(slot) {
+                let remainder = slot >> METADATA_BITS;
+                fingerprints.push(((quotient as u64) << self.remainder_bits) | remainder);
+            }
+            index = self.next(index);
+        }
+        fingerprints
+    }
+}
+
+// The quotient bits for a table holding `capacity` items below the load limit.
+fn quotient_bits_for(capacity: usize) -> u32 {
+    ((capacity as f64 / MAX_LOAD_FACTOR).ceil() as usize)
+        .max(2)
+        .next_power_of_two()
+        .trailing_zeros()
+}
+
+pub struct QuotientFilterResource {
+    filter: RwLock<QuotientFilter>,
+}
+
+impl Resource for QuotientFilterResource {}
+
+#[derive(NifMap)]
+struct QuotientLayout {
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn qf_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: Option<u128>,
+) -> Result<ResourceArc<QuotientFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    let seed = match seed {
+        Some(seed) => seed,
+        None => random_seed()?,
+    };
+    let filter = QuotientFilter::with_capacity(capacity, false_positive_rate, seed)?;
+
+    Ok(ResourceArc::new(QuotientFilterResource {
+        filter: RwLock::new(filter),
+    }))
+}
+
+#[rustler::nif]
+fn qf_layout(resource: ResourceArc<QuotientFilterResource>) -> Result<QuotientLayout, Error> {
+    let filter = resource.filter.read()?;
+    Ok(QuotientLayout {
+        quotient_bits: filter.quotient_bits,
+        remainder_bits: filter.remainder_bits,
+        len: filter.len,
+    })
+}
+
+#[rustler::nif]
+fn qf_add(resource: ResourceArc<QuotientFilterResource>, item: Binary) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    let fingerprint = filter.fingerprint(item.as_slice());
+    filter.insert_fingerprint(fingerprint)
+}
+
+#[rustler::nif]
+fn qf_member(resource: ResourceArc<QuotientFilterResource>, item: Binary) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains_fingerprint(filter.fingerprint(item.as_slice())))
+}
+
+#[rustler::nif]
+fn qf_delete(resource: ResourceArc<QuotientFilterResource>, item: Binary) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    let fingerprint = filter.fingerprint(item.as_slice());
+    Ok(filter.delete_fingerprint(fingerprint))
+}
+
+// Builds a filter holding the fingerprints of both, which must share a seed.
+// It keeps the fewer fingerprint bits of the two, dropping the low bits of the
+// other's fingerprints, and has twice as many slots as the larger, which is
+// always enough for both, so merging with an empty filter resizes a full one.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn qf_merge(
+    a: ResourceArc<QuotientFilterResource>,
+    b: ResourceArc<QuotientFilterResource>,
+) -> Result<ResourceArc<QuotientFilterResource>, Error> {
+    // Copy the fingerprints out so both locks are never held at once.
+    let (seed, a_bits, a_quotient_bits, a_fingerprints) = {
+        let filter = a.filter.read()?;
+        let bits = filter.fingerprint_bits();
+        (
+            filter.seed,
+            bits,
+            filter.quotient_bits,
+            filter.fingerprints(),
+        )
+    };
+    let (b_bits, b_quotient_bits, b_fingerprints) = {
+        let filter = b.filter.read()?;
+        if filter.seed != seed {
+            return Err(Error::IncompatibleFilters);
+        }
+        let bits = filter.fingerprint_bits();
+        (bits, filter.quotient_bits, filter.fingerprints())
+    };
+
+    let fingerprint_bits = a_bits.min(b_bits);
+    let quotient_bits = a_quotient_bits.max(b_quotient_bits) + 1;
+    if quotient_bits >= fingerprint_bits {
+        return Err(Error::FilterFull);
+    }
+
+    let mut merged = QuotientFilter::new(quotient_bits, fingerprint_bits - quotient_bits, seed)?;
+    for fingerprint in a_fingerprints {
+        merged.insert_fingerprint(fingerprint >> (a_bits - fingerprint_bits))?;
+    }
+    for fingerprint in b_fingerprints {
+        merged.insert_fingerprint(fingerprint >> (b_bits - fingerprint_bits))?;
+    }
+
+    Ok(ResourceArc::new(QuotientFilterResource {
+        filter: RwLock::new(merged),
+    }))
+}
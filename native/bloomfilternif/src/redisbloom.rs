@@ -0,0 +1,446 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::memory::{zeroed, Reservation};
+use crate::validate_params;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::f64::consts::LN_2;
+use std::sync::RwLock;
+
+// RedisBloom's chain options.
+const OPT_NOROUND: u32 = 1;
+const OPT_ENTS_IS_BITS: u32 = 2;
+const OPT_FORCE64: u32 = 4;
+const OPT_NO_SCALING: u32 = 8;
+
+// ln(2)^2, as RedisBloom rounds it.
+const LN2_SQUARED: f64 = 0.480453013918201;
+
+// Each new link in a chain halves the error rate of the one before it.
+const ERROR_TIGHTENING_RATIO: f64 = 0.5;
+
+// The most bytes of bits in one `BF.SCANDUMP` chunk, as in RedisBloom.
+const MAX_CHUNK_LEN: usize = 10 * 1024 * 1024;
+
+// The header `BF.SCANDUMP` returns first, in the packed, little-endian layout
+// of RedisBloom's `dumpedChainHeader`:
+//
+//   offset  size  field
+//        0     8  size, items in the chain (u64)
+//        8     4  nfilters (u32)
+//       12     4  options (u32)
+//       16     4  growth (u32)
+//       20   53n  a link per filter
+//
+// and each link, a `dumpedChainLink`:
+//
+//        0     8  bytes (u64)
+//        8     8  bits (u64), bytes * 8
+//       16     8  size, items in the link (u64)
+//       24     8  error (f64)
+//       32     8  bpe, bits per entry (f64)
+//       40     4  hashes (u32)
+//       44     8  entries, the link's capacity (u64)
+//       52     1  n2, log2 of the bits used, or 0 if not a power of two
+const CHAIN_HEADER_LEN: usize = 8 + 4 + 4 + 4;
+const LINK_HEADER_LEN: usize = 8 + 8 + 8 + 8 + 8 + 4 + 8 + 1;
+
+// One bloom filter of a chain, laid out like RedisBloom's `struct bloom`: bit
+// `x` is bit `x % 8` of byte `x / 8`.
+struct Link {
+    bytes: Vec<u8>,
+    size: u64,
+    error: f64,
+    bpe: f64,
+    hashes: u32,
+    entries: u64,
+    n2: u8,
+    _memory: Reservation,
+}
+
+impl Link {
+    // RedisBloom's `bloom_init`.
+    fn new(entries: u64, error: f64, options: u32) -> Result<Self, Error> {
+        let bpe = (error.ln() / LN2_SQUARED).abs();
+        let (bits, n2, entries) = if options & OPT_NOROUND != 0 {
+            ((entries as f64 * bpe) as u64, 0, entries)
+        } else {
+            let wanted = entries as f64 * bpe;
+            let exponent = wanted.log2().floor();
+            if !wanted.is_finite() || exponent >= 63.0 {
+                return Err(Error::InvalidCapacity);
+            }
+            let n2 = exponent as u8 + 1;
+            let extra_items = ((1u64 << n2) as f64 - wanted) as u64 as f64 / bpe;
+            (1 << n2, n2, entries + extra_items as u64)
+        };
+        let len =
+            usize::try_from(bits.max(1).div_ceil(64) * 8).map_err(|_| Error::InvalidCapacity)?;
+
+        let (bytes, memory) = zeroed(len)?;
+        Ok(Link {
+            bytes,
+            size: 0,
+            error,
+            bpe,
+            hashes: (LN_2 * bpe).ceil() as u32,
+            entries,
+            n2,
+            _memory: memory,
+        })
+    }
+
+    // Whatever the rounding, RedisBloom keeps `bits` at the allocated size,
+    // but a power-of-two link only uses the first 2^n2 of them.
+    fn modulus(&self) -> u64 {
+        match self.n2 {
+            0 => self.bytes.len() as u64 * 8,
+            n2 => 1 << n2,
+        }
+    }
+
+    fn indexes(&self, hash: Hash) -> impl Iterator<Item = u64> {
+        let modulus = self.modulus();
+        (0..self.hashes as u64).map(move |i| hash.a.wrapping_add(i.wrapping_mul(hash.b)) % modulus)
+    }
+
+    fn contains(&self, hash: Hash) -> bool {
+        self.indexes(hash)
+            .all(|x| self.bytes[(x / 8) as usize] & (1 << (x % 8)) != 0)
+    }
+
+    fn insert(&mut self, hash: Hash) {
+        for x in self.indexes(hash) {
+            self.bytes[(x / 8) as usize] |= 1 << (x % 8);
+        }
+        self.size += 1;
+    }
+
+    fn encode_header(&self, buf: &mut [u8]) {
+        let len = self.bytes.len() as u64;
+        buf[0..8].copy_from_slice(&len.to_le_bytes());
+        buf[8..16].copy_from_slice(&(len * 8).to_le_bytes());
+        buf[16..24].copy_from_slice(&self.size.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.error.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.bpe.to_le_bytes());
+        buf[40..44].copy_from_slice(&self.hashes.to_le_bytes());
+        buf[44..52].copy_from_slice(&self.entries.to_le_bytes());
+        buf[52] = self.n2;
+    }
+
+    // The bits start zeroed and are filled in by the chunks that follow.
+    fn decode_header(buf: &[u8]) -> Result<Self, Error> {
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+        let len = u64_at(0);
+        let bits = len.checked_mul(8).ok_or(Error::InvalidData)?;
+        let n2 = buf[52];
+        let hashes = u32::from_le_bytes(buf[40..44].try_into().unwrap());
+        if len == 0 || u64_at(8) != bits || hashes == 0 {
+            return Err(Error::InvalidData);
+        }
+        if n2 > 63 || (n2 > 0 && 1 << n2 > bits) {
+            return Err(Error::InvalidData);
+        }
+
+        let len = usize::try_from(len).map_err(|_| Error::InvalidData)?;
+        let (bytes, memory) = zeroed(len)?;
+        Ok(Link {
+            bytes,
+            size: u64_at(16),
+            error: f64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            bpe: f64::from_le_bytes(buf[32..40].try_into().unwrap()),
+            hashes,
+            entries: u64_at(44),
+            n2,
+            _memory: memory,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Hash {
+    a: u64,
+    b: u64,
+}
+
+// A scalable chain of bloom filters that hashes and lays out bits exactly like
+// RedisBloom's `SBChain`, so filters can be moved to and from Redis with
+// `BF.SCANDUMP` and `BF.LOADCHUNK`. Items are hashed as raw bytes, the way
+// Redis receives them. Inserts go to the last link, and a link that reaches its
+// capacity is followed by a new one `growth` times larger.
+struct Chain {
+    links: Vec<Link>,
+    size: u64,
+    options: u32,
+    growth: u32,
+}
+
+impl Chain {
+    // RedisBloom's `SB_NewChain`.
+    fn new(capacity: u64, error: f64, options: u32, growth: u32) -> Result<Self, Error> {
+        let tightening = if options & OPT_NO_SCALING != 0 {
+            1.0
+        } else {
+            ERROR_TIGHTENING_RATIO
+        };
+        Ok(Chain {
+            links: vec![Link::new(capacity, error * tightening, options)?],
+            size: 0,
+            options,
+            growth,
+        })
+    }
+
+    // Chains created by older RedisBloom releases hash with 32-bit MurmurHash2.
+    fn hash(&self, item: &[u8]) -> Hash {
+        if self.options & OPT_FORCE64 != 0 {
+            let a = murmur_hash64a(item, 0xc6a4a7935bd1e995);
+            Hash {
+                a,
+                b: murmur_hash64a(item, a),
+            }
+        } else {
+            let a = murmur_hash2(item, 0x9747b28c);
+            Hash {
+                a: a as u64,
+                b: murmur_hash2(item, a) as u64,
+            }
+        }
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        let hash = self.hash(item);
+        self.links.iter().rev().any(|link| link.contains(hash))
+    }
+
+    // RedisBloom's `SBChain_Add`. Returns whether the item was new.
+    fn insert(&mut self, item: &[u8]) -> Result<bool, Error> {
+        let hash = self.hash(item);
+        if self.links.iter().rev().any(|link| link.contains(hash)) {
+            return Ok(false);
+        }
+
+        let last = self.links.last().expect("a chain has at least one link");
+        if last.size >= last.entries {
+            if self.options & OPT_NO_SCALING != 0 {
+                return Err(Error::FilterFull);
+            }
+            let entries = last.entries.saturating_mul(self.growth as u64);
+            let error = last.error * ERROR_TIGHTENING_RATIO;
+            // The option only applies to the chain's first link.
+            let link = Link::new(entries, error, self.options & !OPT_ENTS_IS_BITS)?;
+            self.links.push(link);
+        }
+
+        self.links.last_mut().unwrap().insert(hash);
+        self.size += 1;
+        Ok(true)
+    }
+
+    fn encode_header(&self) -> Vec<u8> {
+        let mut buf = vec![0; CHAIN_HEADER_LEN + LINK_HEADER_LEN * self.links.len()];
+        buf[0..8].copy_from_slice(&self.size.to_le_bytes());
+        buf[8..12].copy_from_slice(&(self.links.len() as u32).to_le_bytes());
+        buf[12..16].copy_from_slice(&self.options.to_le_bytes());
+        buf[16..20].copy_from_slice(&self.growth.to_le_bytes());
+        for (link, buf) in self
+            .links
+            .iter()
+            .zip(buf[CHAIN_HEADER_LEN..].chunks_exact_mut(LINK_HEADER_LEN))
+        {
+            link.encode_header(buf);
+        }
+        buf
+    }
+
+    // RedisBloom's `SB_NewChainFromHeader`.
+    fn decode_header(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < CHAIN_HEADER_LEN {
+            return Err(Error::InvalidData);
+        }
+        let num_links = u32::from_le_bytes(buf[8..12].try_into().unwrap()) as usize;
+        let links = &buf[CHAIN_HEADER_LEN..];
+        if num_links == 0 || links.len() != num_links * LINK_HEADER_LEN {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Chain {
+            links: links
+                .chunks_exact(LINK_HEADER_LEN)
+                .map(Link::decode_header)
+                .collect::<Result<_, _>>()?,
+            size: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            options: u32::from_le_bytes(buf[12..16].try_into().unwrap()),
+            growth: u32::from_le_bytes(buf[16..20].try_into().unwrap()),
+        })
+    }
+
+    // The link holding byte `offset` of the links' bits laid end to end, and
+    // the offset within it, as RedisBloom's `getLinkPos` finds them.
+    fn link_at(&mut self, mut offset: usize) -> Option<(&mut Link, usize)> {
+        for link in &mut self.links {
+            if offset < link.bytes.len() {
+                return Some((link, offset));
+            }
+            offset -= link.bytes.len();
+        }
+        None
+    }
+}
+
+// The seeded 64-bit MurmurHash64A.
+fn murmur_hash64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^ (h >> R)
+}
+
+// The seeded 32-bit MurmurHash2.
+fn murmur_hash2(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0x5bd1e995;
+    const R: u32 = 24;
+
+    let mut h = seed ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+pub struct RedisBloomResource {
+    chain: RwLock<Chain>,
+}
+
+impl Resource for RedisBloomResource {}
+
+// Creates a chain the way `BF.RESERVE` does, hashing with MurmurHash64A and
+// without rounding the bits up to a power of two.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn redis_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    growth: u32,
+    scaling: bool,
+) -> Result<ResourceArc<RedisBloomResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    if growth == 0 {
+        return Err(Error::InvalidGrowthFactor);
+    }
+
+    let mut options = OPT_NOROUND | OPT_FORCE64;
+    if !scaling {
+        options |= OPT_NO_SCALING;
+    }
+    let chain = Chain::new(capacity as u64, false_positive_rate, options, growth)?;
+    Ok(ResourceArc::new(RedisBloomResource {
+        chain: RwLock::new(chain),
+    }))
+}
+
+// Returns whether the item was new, like `BF.ADD`.
+#[rustler::nif]
+fn redis_add(resource: ResourceArc<RedisBloomResource>, item: Item) -> Result<bool, Error> {
+    resource.chain.write()?.insert(item.as_slice())
+}
+
+#[rustler::nif]
+fn redis_member(resource: ResourceArc<RedisBloomResource>, item: Item) -> Result<bool, Error> {
+    Ok(resource.chain.read()?.contains(item.as_slice()))
+}
+
+// Returns every `{iterator, data}` pair `BF.SCANDUMP` would, in order, without
+// the final `{0, ""}`. The header comes first at iterator 1, then the bits in
+// chunks that never span two links, each paired with the iterator after it.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn redis_scandump<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<RedisBloomResource>,
+) -> Result<Vec<(u64, Binary<'a>)>, Error> {
+    let chain = resource.chain.read()?;
+    let mut chunks = vec![(1, binary(env, &chain.encode_header())?)];
+
+    let mut iterator = 1;
+    for link in &chain.links {
+        for chunk in link.bytes.chunks(MAX_CHUNK_LEN) {
+            iterator += chunk.len() as u64;
+            chunks.push((iterator, binary(env, chunk)?));
+        }
+    }
+    Ok(chunks)
+}
+
+fn binary<'a>(env: Env<'a>, bytes: &[u8]) -> Result<Binary<'a>, Error> {
+    let mut binary = OwnedBinary::new(bytes.len()).ok_or(Error::AllocationFailed)?;
+    binary.as_mut_slice().copy_from_slice(bytes);
+    Ok(binary.release(env))
+}
+
+// Rebuilds a chain from `{iterator, data}` pairs as `BF.LOADCHUNK` takes them:
+// the header at iterator 1 first, then chunks of bits in any order, each
+// written at `iterator - byte_size(data) - 1`. A final `{0, ""}` is ignored.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn redis_loadchunks(chunks: Vec<(u64, Binary)>) -> Result<ResourceArc<RedisBloomResource>, Error> {
+    let mut chunks = chunks.into_iter();
+    let mut chain = match chunks.next() {
+        Some((1, header)) => Chain::decode_header(header.as_slice())?,
+        _ => return Err(Error::InvalidData),
+    };
+
+    for (iterator, data) in chunks {
+        let data = data.as_slice();
+        if iterator == 0 && data.is_empty() {
+            continue;
+        }
+        let offset = iterator
+            .checked_sub(data.len() as u64 + 1)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .ok_or(Error::InvalidData)?;
+        let (link, offset) = chain.link_at(offset).ok_or(Error::InvalidData)?;
+        let bytes = link
+            .bytes
+            .get_mut(offset..offset + data.len())
+            .ok_or(Error::InvalidData)?;
+        bytes.copy_from_slice(data);
+    }
+
+    Ok(ResourceArc::new(RedisBloomResource {
+        chain: RwLock::new(chain),
+    }))
+}
@@ -0,0 +1,36 @@
+use crate::BloomFilterResource;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use rustler::ResourceArc;
+use std::sync::LazyLock;
+
+// Filters shared by name across the whole node. Registered filters stay alive
+// until they are unregistered, even if no Elixir process references them.
+static REGISTRY: LazyLock<DashMap<String, ResourceArc<BloomFilterResource>>> =
+    LazyLock::new(DashMap::new);
+
+#[rustler::nif]
+fn register(name: String, resource: ResourceArc<BloomFilterResource>) -> bool {
+    match REGISTRY.entry(name) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(resource);
+            true
+        }
+    }
+}
+
+#[rustler::nif]
+fn whereis(name: String) -> Option<ResourceArc<BloomFilterResource>> {
+    REGISTRY.get(&name).map(|entry| entry.value().clone())
+}
+
+#[rustler::nif]
+fn unregister(name: String) -> bool {
+    REGISTRY.remove(&name).is_some()
+}
+
+#[rustler::nif]
+fn list_registered() -> Vec<String> {
+    REGISTRY.iter().map(|entry| entry.key().clone()).collect()
+}
@@ -0,0 +1,56 @@
+use crate::error::Error;
+use crate::BloomFilterResource;
+use rustler::env::SavedTerm;
+use rustler::{Encoder, LocalPid, OwnedEnv, ResourceArc, Term};
+use std::thread;
+
+rustler::atoms! {
+    bloom_filter_released,
+}
+
+// Who to tell when a filter is garbage collected, and the tag to tell them.
+pub(crate) struct ReleaseNotice {
+    pid: LocalPid,
+    owned_env: OwnedEnv,
+    tag: SavedTerm,
+}
+
+impl ReleaseNotice {
+    // Destructors can run while the VM collects garbage outside of any
+    // process, where there is no environment to send from, so the message is
+    // sent from a thread of its own, like `add_many_async`'s completions.
+    pub(crate) fn send(self) {
+        let ReleaseNotice {
+            pid,
+            mut owned_env,
+            tag,
+        } = self;
+
+        thread::spawn(move || {
+            // The process may have exited by now, in which case there is no
+            // one to tell.
+            let _ = owned_env.send_and_clear(&pid, |env| {
+                (bloom_filter_released(), tag.load(env)).encode(env)
+            });
+        });
+    }
+}
+
+// Sends `{:bloom_filter_released, tag}` to `pid` once `resource` is garbage
+// collected. Each filter notifies at most one process; calling this again
+// replaces the previous pid and tag.
+#[rustler::nif]
+fn notify_on_release(
+    resource: ResourceArc<BloomFilterResource>,
+    pid: LocalPid,
+    tag: Term,
+) -> Result<(), Error> {
+    let owned_env = OwnedEnv::new();
+    let tag = owned_env.save(tag);
+    *resource.release_notice.lock()? = Some(ReleaseNotice {
+        pid,
+        owned_env,
+        tag,
+    });
+    Ok(())
+}
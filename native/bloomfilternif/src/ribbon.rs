@@ -0,0 +1,285 @@
+use crate::error::Error;
+use crate::item::Item;
+use crate::random_seed;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+
+// Version byte written at the start of every serialized filter.
+const RIBBON_FORMAT_VERSION: u8 = 1;
+
+// version + result_bits + num_slots + hasher seed
+const RIBBON_HEADER_LEN: usize = 1 + 1 + 8 + 16;
+
+// Results are solved as u32s.
+pub(crate) const MAX_RESULT_BITS: u32 = 32;
+
+// Each item's equation covers this many consecutive slots.
+const RIBBON_WIDTH: usize = 128;
+
+// Slots per item beyond one. Building fails now and then, and is retried with
+// a new seed and more slots.
+const SLOT_OVERHEAD: f64 = 0.05;
+const BUILD_ATTEMPTS: usize = 8;
+
+// Salts for the splitmix64 remixes of an item's hash.
+const COEFFICIENT_SALT: u64 = 0x2545_f491_4f6c_dd1d;
+const RESULT_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// A standard ribbon filter, following Dillinger and Walzer, "Ribbon filter:
+// practically smarter than Bloom and Xor". Every item hashes to an equation:
+// the XOR of the solution rows picked out by a 128-bit coefficient, starting
+// at the item's slot, must equal the item's `result_bits` bit fingerprint.
+// Building solves all of the equations at once, and a lookup checks one,
+// which holds for an absent item with probability 2^-result_bits. That takes
+// about 1.05 * result_bits bits per item, where a bloom filter needs about
+// 1.44 * result_bits for the same false positive rate.
+pub struct RibbonResource {
+    table: RibbonTable,
+}
+
+impl Resource for RibbonResource {}
+
+impl RibbonResource {
+    fn contains(&self, item: &[u8]) -> bool {
+        let hash = self.table.hash(item);
+        self.table.lookup(hash) == fingerprint(hash, self.table.result_bits)
+    }
+}
+
+// A solved system of ribbon equations, which maps each item it was built from
+// to a `result_bits` bit result. The filter's results are fingerprints of its
+// items; `bloomier.rs` stores values in them.
+pub(crate) struct RibbonTable {
+    // The solution in column-major order: `column_words` words per result bit.
+    columns: Vec<u64>,
+    column_words: usize,
+    num_slots: usize,
+    result_bits: u32,
+    seed: u128,
+    hasher: DefaultHasher,
+}
+
+// The splitmix64 finalizer, for more independent bits from the one item hash.
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+// A `bits` bit fingerprint of the item with this hash.
+pub(crate) fn fingerprint(hash: u64, bits: u32) -> u32 {
+    (mix(hash ^ RESULT_SALT) >> (64 - bits)) as u32
+}
+
+impl RibbonTable {
+    fn new(num_slots: usize, result_bits: u32, seed: u128) -> Self {
+        // Two spare words, so reading a window never needs a bounds check.
+        let column_words = num_slots.div_ceil(64) + 2;
+        RibbonTable {
+            columns: vec![0; column_words * result_bits as usize],
+            column_words,
+            num_slots,
+            result_bits,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+        }
+    }
+
+    // Solves for `items`, which must not repeat. `result` gives each item's
+    // result from its index and hash.
+    pub(crate) fn build(
+        items: &[&[u8]],
+        result_bits: u32,
+        result: impl Fn(usize, u64) -> u32,
+    ) -> Result<Self, Error> {
+        let mut overhead = SLOT_OVERHEAD;
+        for _ in 0..BUILD_ATTEMPTS {
+            let num_slots = (items.len() as f64 * (1.0 + overhead)).ceil() as usize + RIBBON_WIDTH;
+            let mut table = RibbonTable::new(num_slots, result_bits, random_seed()?);
+            if table.solve(items, &result) {
+                return Ok(table);
+            }
+            overhead *= 1.5;
+        }
+        Err(Error::ConstructionFailed)
+    }
+
+    pub(crate) fn hash(&self, item: &[u8]) -> u64 {
+        self.hasher.hash_one(item)
+    }
+
+    // The first slot of the item's equation and its coefficient, which always
+    // has bit 0 set.
+    fn equation(&self, hash: u64) -> (usize, u128) {
+        let starts = (self.num_slots - RIBBON_WIDTH + 1) as u128;
+        let start = ((hash as u128 * starts) >> 64) as usize;
+        let coefficient = (mix(hash) as u128) << 64 | mix(hash ^ COEFFICIENT_SALT) as u128 | 1;
+        (start, coefficient)
+    }
+
+    // Gaussian elimination as the equations arrive, keeping one row per slot
+    // with its lowest coefficient bit in that slot, then back substitution.
+    // Returns false if the equations have no solution.
+    fn solve(&mut self, items: &[&[u8]], result: impl Fn(usize, u64) -> u32) -> bool {
+        let mut coefficients = vec![0u128; self.num_slots];
+        let mut results = vec![0u32; self.num_slots];
+
+        for (index, item) in items.iter().enumerate() {
+            let hash = self.hash(item);
+            let (mut start, mut coefficient) = self.equation(hash);
+            let mut result = result(index, hash);
+            loop {
+                if coefficients[start] == 0 {
+                    coefficients[start] = coefficient;
+                    results[start] = result;
+                    break;
+                }
+                coefficient ^= coefficients[start];
+                result ^= results[start];
+                if coefficient == 0 {
+                    // Either a repeat of an earlier equation or a contradiction.
+                    if result != 0 {
+                        return false;
+                    }
+                    break;
+                }
+                let shift = coefficient.trailing_zeros();
+                start += shift as usize;
+                coefficient >>= shift;
+            }
+        }
+
+        // Slots without a row are free, and left as zero.
+        let mut solution = vec![0u32; self.num_slots];
+        for slot in (0..self.num_slots).rev() {
+            let mut value = results[slot];
+            let mut rest = coefficients[slot] >> 1;
+            while rest != 0 {
+                value ^= solution[slot + 1 + rest.trailing_zeros() as usize];
+                rest &= rest - 1;
+            }
+            solution[slot] = value;
+        }
+
+        for (slot, value) in solution.into_iter().enumerate() {
+            for bit in 0..self.result_bits as usize {
+                if value >> bit & 1 == 1 {
+                    self.columns[bit * self.column_words + slot / 64] |= 1 << (slot % 64);
+                }
+            }
+        }
+        true
+    }
+
+    // The 128 solution bits of column `bit` starting at slot `start`.
+    fn window(&self, bit: usize, start: usize) -> u128 {
+        let column = &self.columns[bit * self.column_words..];
+        let (word, offset) = (start / 64, start % 64);
+        let low = column[word] as u128 | (column[word + 1] as u128) << 64;
+        match offset {
+            0 => low,
+            _ => low >> offset | (column[word + 2] as u128) << (128 - offset),
+        }
+    }
+
+    // Evaluates the equation for `hash`, which gives the result of any item
+    // the table was built from.
+    pub(crate) fn lookup(&self, hash: u64) -> u32 {
+        let (start, coefficient) = self.equation(hash);
+        (0..self.result_bits as usize).fold(0, |result, bit| {
+            let parity = (self.window(bit, start) & coefficient).count_ones() & 1;
+            result | parity << bit
+        })
+    }
+}
+
+// The filter gets ceil(log2(1 / false_positive_rate)) result bits, so its
+// false positive rate is at most the one requested.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ribbon_build(
+    items: Vec<Item>,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<RibbonResource>, Error> {
+    if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+    let result_bits = (1.0 / false_positive_rate).log2().ceil().max(1.0) as u32;
+    if result_bits > MAX_RESULT_BITS {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+
+    let mut items: Vec<&[u8]> = items.iter().map(|item| item.as_slice()).collect();
+    items.sort_unstable();
+    items.dedup();
+
+    let table = RibbonTable::build(&items, result_bits, |_, hash| {
+        fingerprint(hash, result_bits)
+    })?;
+    Ok(ResourceArc::new(RibbonResource { table }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ribbon_member(resource: ResourceArc<RibbonResource>, item: Item) -> bool {
+    resource.contains(item.as_slice())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ribbon_member_many(resource: ResourceArc<RibbonResource>, items: Vec<Item>) -> Vec<bool> {
+    items
+        .iter()
+        .map(|item| resource.contains(item.as_slice()))
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ribbon_serialize<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<RibbonResource>,
+) -> Result<Binary<'a>, Error> {
+    let table = &resource.table;
+    let mut binary = OwnedBinary::new(RIBBON_HEADER_LEN + table.columns.len() * 8)
+        .ok_or(Error::AllocationFailed)?;
+    let buf = binary.as_mut_slice();
+    buf[0] = RIBBON_FORMAT_VERSION;
+    buf[1] = table.result_bits as u8;
+    buf[2..10].copy_from_slice(&(table.num_slots as u64).to_le_bytes());
+    buf[10..RIBBON_HEADER_LEN].copy_from_slice(&table.seed.to_le_bytes());
+    for (chunk, word) in buf[RIBBON_HEADER_LEN..]
+        .chunks_exact_mut(8)
+        .zip(&table.columns)
+    {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    Ok(binary.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ribbon_deserialize(binary: Binary) -> Result<ResourceArc<RibbonResource>, Error> {
+    let buf = binary.as_slice();
+    if buf.len() < RIBBON_HEADER_LEN {
+        return Err(Error::InvalidData);
+    }
+    if buf[0] != RIBBON_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion);
+    }
+
+    let result_bits = buf[1] as u32;
+    let num_slots = u64::from_le_bytes(buf[2..10].try_into().unwrap());
+    let seed = u128::from_le_bytes(buf[10..RIBBON_HEADER_LEN].try_into().unwrap());
+    if result_bits == 0 || result_bits > MAX_RESULT_BITS || num_slots < RIBBON_WIDTH as u64 {
+        return Err(Error::InvalidData);
+    }
+    let data = &buf[RIBBON_HEADER_LEN..];
+    let column_words = num_slots.div_ceil(64) + 2;
+    if column_words.checked_mul(result_bits as u64 * 8) != Some(data.len() as u64) {
+        return Err(Error::InvalidData);
+    }
+
+    let mut table = RibbonTable::new(num_slots as usize, result_bits, seed);
+    for (word, chunk) in table.columns.iter_mut().zip(data.chunks_exact(8)) {
+        *word = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    Ok(ResourceArc::new(RibbonResource { table }))
+}
@@ -0,0 +1,170 @@
+use crate::error::Error;
+use crate::memory::Reservation;
+use crate::{optimal_words, random_seed, validate_params};
+use fastbloom::BloomFilter;
+use rustler::{Binary, Resource, ResourceArc};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+// A fixed number of generational filters. Items are added to the newest
+// generation and members are looked up in all of them; rotating drops the
+// oldest generation, so items expire after `generations` rotations.
+struct RotatingBloomFilter {
+    generations: VecDeque<BloomFilter>,
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u128,
+    // `None` means generations only rotate through the `rotating_rotate` NIF.
+    interval: Option<Duration>,
+    last_rotation: Instant,
+    // One per generation.
+    _memory: Vec<Reservation>,
+}
+
+impl RotatingBloomFilter {
+    fn push_generation(&mut self) -> Result<(), Error> {
+        let (words, num_hashes, memory) = optimal_words(self.capacity, self.false_positive_rate)?;
+        let generation = BloomFilter::from_vec(words)
+            .seed(&self.seed)
+            .hashes(num_hashes);
+        self.generations.push_back(generation);
+        self._memory.push(memory);
+        Ok(())
+    }
+
+    // The oldest generation is cleared and reused as the newest, so rotating
+    // never allocates.
+    fn rotate(&mut self) {
+        let mut generation = self
+            .generations
+            .pop_back()
+            .expect("at least one generation");
+        generation.clear();
+        self.generations.push_front(generation);
+        self.last_rotation = Instant::now();
+    }
+
+    fn rotation_due(&self) -> bool {
+        self.interval
+            .is_some_and(|interval| self.last_rotation.elapsed() >= interval)
+    }
+
+    // Catches up on every interval that elapsed since the last rotation.
+    fn rotate_if_due(&mut self) {
+        let Some(interval) = self.interval.filter(|_| self.rotation_due()) else {
+            return;
+        };
+
+        let due = self.last_rotation.elapsed().as_nanos() / interval.as_nanos();
+        if due >= self.generations.len() as u128 {
+            for generation in self.generations.iter_mut() {
+                generation.clear();
+            }
+            self.last_rotation = Instant::now();
+        } else {
+            let last_rotation = self.last_rotation;
+            for _ in 0..due {
+                self.rotate();
+            }
+            // Keep the schedule anchored to the original interval boundaries.
+            self.last_rotation = last_rotation + interval * due as u32;
+        }
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.generations
+            .iter()
+            .any(|generation| generation.contains(item))
+    }
+}
+
+pub struct RotatingBloomFilterResource {
+    filter: RwLock<RotatingBloomFilter>,
+}
+
+impl Resource for RotatingBloomFilterResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn rotating_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    generations: usize,
+    interval_ms: u64,
+) -> Result<ResourceArc<RotatingBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    if generations == 0 {
+        return Err(Error::InvalidGenerations);
+    }
+
+    let mut filter = RotatingBloomFilter {
+        generations: VecDeque::new(),
+        capacity,
+        false_positive_rate,
+        seed: random_seed()?,
+        interval: (interval_ms > 0).then(|| Duration::from_millis(interval_ms)),
+        last_rotation: Instant::now(),
+        _memory: Vec::new(),
+    };
+    filter
+        .generations
+        .try_reserve_exact(generations)
+        .and_then(|_| filter._memory.try_reserve_exact(generations))
+        .map_err(|_| Error::AllocationFailed)?;
+    for _ in 0..generations {
+        filter.push_generation()?;
+    }
+
+    Ok(ResourceArc::new(RotatingBloomFilterResource {
+        filter: RwLock::new(filter),
+    }))
+}
+
+#[rustler::nif]
+fn rotating_add(
+    resource: ResourceArc<RotatingBloomFilterResource>,
+    item: Binary,
+) -> Result<ResourceArc<RotatingBloomFilterResource>, Error> {
+    {
+        let mut filter = resource.filter.write()?;
+
+        filter.rotate_if_due();
+        filter
+            .generations
+            .front_mut()
+            .expect("at least one generation")
+            .insert(item.as_slice());
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn rotating_member(
+    resource: ResourceArc<RotatingBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    {
+        let filter = resource.filter.read()?;
+        if !filter.rotation_due() {
+            return Ok(filter.contains(item.as_slice()));
+        }
+    }
+
+    // A rotation is due, which needs the write lock.
+    let mut filter = resource.filter.write()?;
+    filter.rotate_if_due();
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn rotating_rotate(
+    resource: ResourceArc<RotatingBloomFilterResource>,
+) -> Result<ResourceArc<RotatingBloomFilterResource>, Error> {
+    {
+        let mut filter = resource.filter.write()?;
+        filter.rotate();
+    } // Lock is dropped here
+
+    Ok(resource)
+}
@@ -0,0 +1,217 @@
+use crate::error::Error;
+use crate::memory::Reservation;
+use crate::{bounded_size, random_seed, validate_params, zeroed_words};
+use fastbloom::BloomFilter;
+use rustler::{Binary, Resource, ResourceArc};
+use std::sync::RwLock;
+
+// Each new layer gets a tighter false positive rate so the compound rate of
+// all layers converges to the configured target: p * (1 - r) * sum(r^i) <= p.
+const TIGHTENING_RATIO: f64 = 0.5;
+
+struct Layer {
+    filter: BloomFilter,
+    capacity: usize,
+    inserted_count: usize,
+}
+
+// A stack of bloom filters that grows a new, larger layer whenever the current
+// one has received its expected number of distinct items.
+struct ScalableBloomFilter {
+    layers: Vec<Layer>,
+    // The layer inserts go to. Layers after it were allocated ahead of time by
+    // `reserve` and are still empty.
+    current: usize,
+    false_positive_rate: f64,
+    growth_factor: usize,
+    seed: u128,
+    // One share for each layer pushed by an insert, and one for all the
+    // layers added by each `reserve`.
+    _memory: Vec<Reservation>,
+}
+
+impl ScalableBloomFilter {
+    fn new(
+        initial_capacity: usize,
+        false_positive_rate: f64,
+        growth_factor: usize,
+        seed: u128,
+    ) -> Result<Self, Error> {
+        let mut filter = ScalableBloomFilter {
+            layers: Vec::new(),
+            current: 0,
+            false_positive_rate,
+            growth_factor,
+            seed,
+            _memory: Vec::new(),
+        };
+        filter.push_layer(initial_capacity)?;
+        Ok(filter)
+    }
+
+    // The number of words and hashes of the layer at `index`.
+    fn layer_size(&self, index: usize, capacity: usize) -> Result<(usize, u32), Error> {
+        let layer_fpr = self.false_positive_rate
+            * (1.0 - TIGHTENING_RATIO)
+            * TIGHTENING_RATIO.powi(index as i32);
+        bounded_size(capacity, layer_fpr)
+    }
+
+    // An empty layer, for memory already reserved.
+    fn layer(&self, capacity: usize, num_words: usize, num_hashes: u32) -> Result<Layer, Error> {
+        let filter = BloomFilter::from_vec(zeroed_words(num_words)?)
+            .seed(&self.seed)
+            .hashes(num_hashes);
+        Ok(Layer {
+            filter,
+            capacity,
+            inserted_count: 0,
+        })
+    }
+
+    fn push_layer(&mut self, capacity: usize) -> Result<(), Error> {
+        let (num_words, num_hashes) = self.layer_size(self.layers.len(), capacity)?;
+        let memory = Reservation::new(num_words * 8)?;
+        let layer = self.layer(capacity, num_words, num_hashes)?;
+
+        self.layers.push(layer);
+        self._memory.push(memory);
+        Ok(())
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.layers[..=self.current]
+            .iter()
+            .any(|layer| layer.filter.contains(item))
+    }
+
+    // The next layer is `growth_factor` times the size of the last one.
+    fn push_next_layer(&mut self) -> Result<(), Error> {
+        let last = self.layers.last().expect("at least one layer");
+        let next_capacity = last.capacity.saturating_mul(self.growth_factor);
+        self.push_layer(next_capacity)
+    }
+
+    // Allocates layers until `additional` more distinct items fit without
+    // growing, returning how many were added. The layers are sized first and
+    // reserved together, so a reservation that would go over budget adds
+    // none of them.
+    fn reserve(&mut self, additional: usize) -> Result<usize, Error> {
+        let current = &self.layers[self.current];
+        let mut room = current.capacity.saturating_sub(current.inserted_count);
+        for layer in &self.layers[self.current + 1..] {
+            room = room.saturating_add(layer.capacity);
+        }
+
+        let mut sizes = Vec::new();
+        let mut capacity = self.layers.last().expect("at least one layer").capacity;
+        while room < additional {
+            capacity = capacity.saturating_mul(self.growth_factor);
+            let (num_words, num_hashes) =
+                self.layer_size(self.layers.len() + sizes.len(), capacity)?;
+            sizes.push((capacity, num_words, num_hashes));
+            room = room.saturating_add(capacity);
+        }
+
+        let num_words: usize = sizes.iter().map(|&(_, num_words, _)| num_words).sum();
+        let memory = Reservation::new(num_words * 8)?;
+        let mut layers = Vec::new();
+        layers
+            .try_reserve_exact(sizes.len())
+            .map_err(|_| Error::AllocationFailed)?;
+        for &(capacity, num_words, num_hashes) in &sizes {
+            layers.push(self.layer(capacity, num_words, num_hashes)?);
+        }
+
+        self.layers.append(&mut layers);
+        self._memory.push(memory);
+        Ok(sizes.len())
+    }
+
+    fn insert(&mut self, item: &[u8]) -> Result<(), Error> {
+        // Duplicates would only waste the capacity of the current layer.
+        if self.contains(item) {
+            return Ok(());
+        }
+
+        let current = &self.layers[self.current];
+        if current.inserted_count >= current.capacity {
+            if self.current + 1 == self.layers.len() {
+                self.push_next_layer()?;
+            }
+            self.current += 1;
+        }
+
+        let current = &mut self.layers[self.current];
+        current.filter.insert(item);
+        current.inserted_count += 1;
+        Ok(())
+    }
+}
+
+pub struct ScalableBloomFilterResource {
+    filter: RwLock<ScalableBloomFilter>,
+}
+
+impl Resource for ScalableBloomFilterResource {}
+
+#[rustler::nif]
+fn scalable_new(
+    initial_capacity: usize,
+    false_positive_rate: f64,
+    growth_factor: usize,
+) -> Result<ResourceArc<ScalableBloomFilterResource>, Error> {
+    validate_params(initial_capacity, false_positive_rate)?;
+    if growth_factor < 2 {
+        return Err(Error::InvalidGrowthFactor);
+    }
+
+    Ok(ResourceArc::new(ScalableBloomFilterResource {
+        filter: RwLock::new(ScalableBloomFilter::new(
+            initial_capacity,
+            false_positive_rate,
+            growth_factor,
+            random_seed()?,
+        )?),
+    }))
+}
+
+#[rustler::nif]
+fn scalable_add(
+    resource: ResourceArc<ScalableBloomFilterResource>,
+    item: Binary,
+) -> Result<ResourceArc<ScalableBloomFilterResource>, Error> {
+    {
+        let mut filter = resource.filter.write()?;
+        filter.insert(item.as_slice())?;
+    } // Lock is dropped here
+
+    Ok(resource)
+}
+
+#[rustler::nif]
+fn scalable_member(
+    resource: ResourceArc<ScalableBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+#[rustler::nif]
+fn scalable_num_layers(resource: ResourceArc<ScalableBloomFilterResource>) -> Result<usize, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.layers.len())
+}
+
+// Allocates the layers a bulk load of `additional` distinct items will need
+// up front, so the inserts don't stall to allocate them, and returns how many
+// were added. Layers are added just as inserts would add them.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn scalable_reserve(
+    resource: ResourceArc<ScalableBloomFilterResource>,
+    additional: usize,
+) -> Result<usize, Error> {
+    let mut filter = resource.filter.write()?;
+    filter.reserve(additional)
+}
@@ -0,0 +1,159 @@
+use crate::error::Error;
+use crate::hasher::{FilterHasher, HashAlgorithm};
+use crate::{bit_indexes, collect_words, MAX_NUM_BITS};
+use fastbloom::AtomicBloomFilter;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// The words in every segment but the last.
+const SEGMENT_WORDS: usize = MAX_NUM_BITS / 64;
+
+// The bit array of a `BloomFilterResource`. fastbloom can't reach past bit
+// 2^32, so bigger bit arrays are split into segments of 2^32 bits, the last
+// holding what is left, and each item sets and checks its bits in the one
+// segment its hash picks. A filter of up to 2^32 bits is a single segment,
+// with the same bits as a plain fastbloom filter.
+//
+// Each segment is allocated on its own, so a multi-GB filter never needs one
+// contiguous allocation. The words of the filter are those of its segments in
+// order, which is how they are serialized.
+pub(crate) struct Filter {
+    segments: Vec<AtomicBloomFilter<FilterHasher>>,
+    num_words: usize,
+}
+
+impl Filter {
+    pub(crate) fn zeroed(
+        num_words: usize,
+        hash: HashAlgorithm,
+        seed: u128,
+        num_hashes: u32,
+    ) -> Result<Self, Error> {
+        Self::from_words((0..num_words).map(|_| 0), hash, seed, num_hashes)
+    }
+
+    // Allocates each segment with `collect_words`, so a filter too big for the
+    // allocator fails with `AllocationFailed` instead of aborting the VM.
+    pub(crate) fn from_words(
+        mut words: impl ExactSizeIterator<Item = u64>,
+        hash: HashAlgorithm,
+        seed: u128,
+        num_hashes: u32,
+    ) -> Result<Self, Error> {
+        let num_words = words.len();
+        let mut segments = Vec::new();
+        segments
+            .try_reserve_exact(num_words.div_ceil(SEGMENT_WORDS))
+            .map_err(|_| Error::AllocationFailed)?;
+
+        let hasher = FilterHasher::new(hash, seed);
+        while words.len() > 0 {
+            let segment = collect_words(words.by_ref().take(SEGMENT_WORDS))?;
+            segments.push(
+                AtomicBloomFilter::from_vec(segment)
+                    .hasher(hasher.clone())
+                    .hashes(num_hashes),
+            );
+        }
+        Ok(Filter {
+            segments,
+            num_words,
+        })
+    }
+
+    pub(crate) fn num_words(&self) -> usize {
+        self.num_words
+    }
+
+    pub(crate) fn num_bits(&self) -> usize {
+        self.num_words * 64
+    }
+
+    pub(crate) fn num_hashes(&self) -> u32 {
+        self.segments[0].num_hashes()
+    }
+
+    pub(crate) fn word(&self, index: usize) -> &AtomicU64 {
+        &self.segments[index / SEGMENT_WORDS].as_slice()[index % SEGMENT_WORDS]
+    }
+
+    pub(crate) fn words(&self) -> impl Iterator<Item = &AtomicU64> {
+        self.segments.iter().flat_map(|segment| segment.as_slice())
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.words().map(|word| word.load(Ordering::Relaxed))
+    }
+
+    // Every segment hashes alike, so any of them gives the same hash.
+    pub(crate) fn source_hash(&self, item: &(impl Hash + ?Sized)) -> u64 {
+        self.segments[0].source_hash(item)
+    }
+
+    // Picks a word with the hash remixed by MurmurHash3's finalizer, so the
+    // choice doesn't follow the bits fastbloom derives from the hash, and
+    // returns the segment holding it. Segments get items in proportion to
+    // their size, so the last, smaller one fills at the same rate as the rest.
+    fn segment_index(&self, hash: u64) -> usize {
+        if self.segments.len() == 1 {
+            return 0;
+        }
+        let mut mixed = hash;
+        mixed ^= mixed >> 33;
+        mixed = mixed.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        mixed ^= mixed >> 33;
+        mixed = mixed.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        mixed ^= mixed >> 33;
+        let word = ((mixed as u128 * self.num_words as u128) >> 64) as usize;
+        word / SEGMENT_WORDS
+    }
+
+    fn segment(&self, hash: u64) -> &AtomicBloomFilter<FilterHasher> {
+        &self.segments[self.segment_index(hash)]
+    }
+
+    pub(crate) fn insert_hash(&self, hash: u64) -> bool {
+        self.segment(hash).insert_hash(hash)
+    }
+
+    pub(crate) fn contains_hash(&self, hash: u64) -> bool {
+        self.segment(hash).contains_hash(hash)
+    }
+
+    pub(crate) fn contains(&self, item: &(impl Hash + ?Sized)) -> bool {
+        self.contains_hash(self.source_hash(item))
+    }
+
+    // The indexes into the whole bit array of the bits `hash` maps to.
+    pub(crate) fn bit_indexes(&self, hash: u64) -> Vec<u64> {
+        let index = self.segment_index(hash);
+        let segment = &self.segments[index];
+        let offset = (index * SEGMENT_WORDS * 64) as u64;
+        bit_indexes(segment.num_bits() as u64, segment.num_hashes(), hash)
+            .into_iter()
+            .map(|bit| offset + bit)
+            .collect()
+    }
+
+    // Like fastbloom's, these panic unless both filters have the same number
+    // of bits and hashes.
+    pub(crate) fn union(&self, other: &Filter) {
+        assert_eq!(self.num_words, other.num_words);
+        for (segment, other) in self.segments.iter().zip(&other.segments) {
+            segment.union(other);
+        }
+    }
+
+    pub(crate) fn intersect(&self, other: &Filter) {
+        assert_eq!(self.num_words, other.num_words);
+        for (segment, other) in self.segments.iter().zip(&other.segments) {
+            segment.intersect(other);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        for segment in &self.segments {
+            segment.clear();
+        }
+    }
+}
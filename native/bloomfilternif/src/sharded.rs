@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::memory::Reservation;
+use crate::{optimal_words, random_seed, validate_params};
+use fastbloom::{BloomFilter, DefaultHasher};
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+// A bloom filter split into independently locked shards. Each item is routed
+// by hash to a single shard, so concurrent writers only contend when they hit
+// the same shard, and lookups only lock the shard that owns the item.
+pub struct ShardedBloomFilterResource {
+    shards: Vec<RwLock<BloomFilter>>,
+    // Routing uses its own hasher so the shard an item lands in is independent
+    // of the bits it sets within that shard.
+    router: DefaultHasher,
+    inserted_count: AtomicUsize,
+    // One per shard.
+    _memory: Vec<Reservation>,
+}
+
+impl Resource for ShardedBloomFilterResource {}
+
+impl ShardedBloomFilterResource {
+    fn shard_index(&self, item: &[u8]) -> usize {
+        (self.router.hash_one(item) % self.shards.len() as u64) as usize
+    }
+
+    fn shard(&self, item: &[u8]) -> &RwLock<BloomFilter> {
+        &self.shards[self.shard_index(item)]
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn sharded_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    num_shards: usize,
+) -> Result<ResourceArc<ShardedBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    if num_shards == 0 {
+        return Err(Error::InvalidShards);
+    }
+
+    // Items spread evenly across shards, so each needs an equal share of the
+    // capacity to keep the configured false positive rate.
+    let shard_capacity = capacity.div_ceil(num_shards);
+    let seed = random_seed()?;
+    let mut shards = Vec::new();
+    let mut memory = Vec::new();
+    shards
+        .try_reserve_exact(num_shards)
+        .and_then(|_| memory.try_reserve_exact(num_shards))
+        .map_err(|_| Error::AllocationFailed)?;
+    for _ in 0..num_shards {
+        let (words, num_hashes, reservation) = optimal_words(shard_capacity, false_positive_rate)?;
+        shards.push(RwLock::new(
+            BloomFilter::from_vec(words).seed(&seed).hashes(num_hashes),
+        ));
+        memory.push(reservation);
+    }
+
+    Ok(ResourceArc::new(ShardedBloomFilterResource {
+        shards,
+        router: DefaultHasher::seeded(&seed.rotate_left(64).to_le_bytes()),
+        inserted_count: AtomicUsize::new(0),
+        _memory: memory,
+    }))
+}
+
+// Returns whether the item was new, like `add`.
+#[rustler::nif]
+fn sharded_add(
+    resource: ResourceArc<ShardedBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let mut shard = resource.shard(item.as_slice()).write()?;
+
+    let previously_contained = shard.insert(item.as_slice());
+    if !previously_contained {
+        resource.inserted_count.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(!previously_contained)
+}
+
+// Groups the items by shard so each shard's lock is taken once. Returns the
+// number of new items, like `add_many`.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn sharded_add_many(
+    resource: ResourceArc<ShardedBloomFilterResource>,
+    items: Vec<Binary>,
+) -> Result<usize, Error> {
+    let mut by_shard = vec![Vec::new(); resource.shards.len()];
+    for item in &items {
+        by_shard[resource.shard_index(item.as_slice())].push(item.as_slice());
+    }
+
+    let mut new_items = 0;
+    for (shard, items) in resource.shards.iter().zip(by_shard) {
+        if items.is_empty() {
+            continue;
+        }
+        let mut shard = shard.write()?;
+        new_items += items
+            .into_iter()
+            .filter(|item| !shard.insert(*item))
+            .count();
+    }
+
+    resource
+        .inserted_count
+        .fetch_add(new_items, Ordering::Relaxed);
+    Ok(new_items)
+}
+
+#[rustler::nif]
+fn sharded_member(
+    resource: ResourceArc<ShardedBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let shard = resource.shard(item.as_slice()).read()?;
+    Ok(shard.contains(item.as_slice()))
+}
+
+#[rustler::nif]
+fn sharded_inserted_count(resource: ResourceArc<ShardedBloomFilterResource>) -> usize {
+    resource.inserted_count.load(Ordering::Relaxed)
+}
@@ -0,0 +1,154 @@
+use crate::error::Error;
+use crate::hasher::double_hash_indexes;
+use crate::memory::{zeroed, Reservation};
+use crate::random_seed;
+use fastbloom::DefaultHasher;
+use rustler::{Binary, NifMap, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+const MAX_CELL_BITS: u32 = 8;
+
+// A stable bloom filter, following Deng and Rafiei, "Approximately Detecting
+// Duplicates for Streaming Data using Stable Bloom Filters". Cells are small
+// counters rather than bits. Each insert first decrements `decrements` cells,
+// starting from a random one, then sets the item's cells to the maximum, so
+// old items fade out as new ones arrive. The fraction of zero cells converges
+// however long the stream runs, which bounds the false positive rate where a
+// plain bloom filter would fill up, at the cost of some false negatives for
+// items not seen recently.
+struct StableBloomFilter {
+    cells: Vec<u8>,
+    max: u8,
+    num_hashes: u32,
+    decrements: usize,
+    hasher: DefaultHasher,
+    // State for picking where to start decrementing (xorshift64).
+    rng: u64,
+    _memory: Reservation,
+}
+
+impl StableBloomFilter {
+    // k = ceil(log2(1 / fpr)) as for a plain bloom filter, and P from the
+    // paper's stable false positive rate, solved for the target:
+    // fpr = (1 - (1 / (1 + 1 / (P * (1/k - 1/m))))^max)^k.
+    fn new(num_cells: usize, cell_bits: u32, fp_target: f64, seed: u128) -> Result<Self, Error> {
+        let max = ((1u32 << cell_bits) - 1) as u8;
+        let num_hashes = (1.0 / fp_target).log2().ceil().max(1.0) as u32;
+        // The formula for P needs more cells than hash functions.
+        if num_hashes as usize >= num_cells {
+            return Err(Error::InvalidCapacity);
+        }
+
+        let zeros = (1.0 - fp_target.powf(1.0 / num_hashes as f64)).powf(1.0 / max as f64);
+        let spread = 1.0 / num_hashes as f64 - 1.0 / num_cells as f64;
+        let decrements = (1.0 / ((1.0 / zeros - 1.0) * spread)) as usize;
+        let (cells, memory) = zeroed(num_cells)?;
+
+        Ok(StableBloomFilter {
+            cells,
+            max,
+            num_hashes,
+            decrements: decrements.clamp(1, num_cells),
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+            rng: (seed as u64) | 1,
+            _memory: memory,
+        })
+    }
+
+    fn cells_for(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(item);
+        double_hash_indexes(hash, self.num_hashes, self.cells.len())
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.cells_for(item).all(|cell| self.cells[cell] > 0)
+    }
+
+    // Returns whether the item was present before it was added. The
+    // decremented cells are consecutive, which the paper shows behaves the
+    // same as picking each one at random.
+    fn insert(&mut self, item: &[u8]) -> bool {
+        let present = self.contains(item);
+
+        let num_cells = self.cells.len();
+        let start = (self.next_random() % num_cells as u64) as usize;
+        for offset in 0..self.decrements {
+            let cell = &mut self.cells[(start + offset) % num_cells];
+            *cell = cell.saturating_sub(1);
+        }
+
+        for cell in self.cells_for(item) {
+            self.cells[cell] = self.max;
+        }
+        present
+    }
+}
+
+pub struct StableBloomFilterResource {
+    filter: RwLock<StableBloomFilter>,
+}
+
+impl Resource for StableBloomFilterResource {}
+
+#[derive(NifMap)]
+struct StableLayout {
+    num_hashes: u32,
+    decrements: usize,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn stable_new(
+    num_cells: usize,
+    cell_bits: u32,
+    fp_target: f64,
+) -> Result<ResourceArc<StableBloomFilterResource>, Error> {
+    if num_cells == 0 {
+        return Err(Error::InvalidCapacity);
+    }
+    if cell_bits == 0 || cell_bits > MAX_CELL_BITS {
+        return Err(Error::InvalidCellBits);
+    }
+    if fp_target <= 0.0 || fp_target >= 1.0 {
+        return Err(Error::InvalidFalsePositiveRate);
+    }
+    let filter = StableBloomFilter::new(num_cells, cell_bits, fp_target, random_seed()?)?;
+
+    Ok(ResourceArc::new(StableBloomFilterResource {
+        filter: RwLock::new(filter),
+    }))
+}
+
+#[rustler::nif]
+fn stable_layout(resource: ResourceArc<StableBloomFilterResource>) -> Result<StableLayout, Error> {
+    let filter = resource.filter.read()?;
+    Ok(StableLayout {
+        num_hashes: filter.num_hashes,
+        decrements: filter.decrements,
+    })
+}
+
+#[rustler::nif]
+fn stable_add(
+    resource: ResourceArc<StableBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    Ok(filter.insert(item.as_slice()))
+}
+
+#[rustler::nif]
+fn stable_member(
+    resource: ResourceArc<StableBloomFilterResource>,
+    item: Binary,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
@@ -0,0 +1,125 @@
+use crate::error::Error;
+use crate::format::{CHECKSUM_LEN, HEADER_LEN};
+use crate::BloomFilterResource;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+// A cursor over the bytes `serialize` would return for a filter, handed out a
+// chunk at a time so huge filters never exist as one binary.
+//
+// The bit array is read from the live filter as each chunk is requested rather
+// than copied up front. Bits are only ever set, so the result still contains
+// every item that was present when the stream was opened; a `clear` while the
+// stream is being read leaves it with a mix of old and new bits.
+pub struct SerializeStreamResource {
+    resource: ResourceArc<BloomFilterResource>,
+    header: [u8; HEADER_LEN],
+    payload_len: usize,
+    chunk_size: usize,
+    state: Mutex<StreamState>,
+}
+
+struct StreamState {
+    position: usize,
+    // Covers every byte handed out so far.
+    checksum: crc32fast::Hasher,
+}
+
+impl Resource for SerializeStreamResource {}
+
+impl SerializeStreamResource {
+    fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.payload_len + CHECKSUM_LEN
+    }
+
+    // Appends the bytes in `start..end` of the serialized filter to `chunk`.
+    fn read(
+        &self,
+        state: &mut StreamState,
+        start: usize,
+        end: usize,
+        chunk: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let payload_end = HEADER_LEN + self.payload_len;
+
+        if start < HEADER_LEN {
+            let header = &self.header[start..end.min(HEADER_LEN)];
+            state.checksum.update(header);
+            chunk.extend_from_slice(header);
+        }
+
+        let (from, to) = (start.max(HEADER_LEN), end.min(payload_end));
+        if from < to {
+            let (from, to) = (from - HEADER_LEN, to - HEADER_LEN);
+            let filter = self.resource.filter.read();
+            let bytes: Vec<u8> = (from / 8..to.div_ceil(8))
+                .flat_map(|index| filter.word(index).load(Ordering::Relaxed).to_le_bytes())
+                .skip(from % 8)
+                .take(to - from)
+                .collect();
+            state.checksum.update(&bytes);
+            chunk.extend_from_slice(&bytes);
+        }
+
+        if end > payload_end {
+            let checksum = state.checksum.clone().finalize().to_le_bytes();
+            chunk.extend_from_slice(
+                &checksum[start.max(payload_end) - payload_end..end - payload_end],
+            );
+        }
+        Ok(())
+    }
+}
+
+#[rustler::nif]
+fn serialize_stream(
+    resource: ResourceArc<BloomFilterResource>,
+    chunk_size: usize,
+) -> Result<ResourceArc<SerializeStreamResource>, Error> {
+    if chunk_size == 0 {
+        return Err(Error::InvalidChunkSize);
+    }
+
+    let (header, payload_len) = {
+        let filter = resource.filter.read();
+        (resource.encode_header(&filter), filter.num_words() * 8)
+    };
+
+    Ok(ResourceArc::new(SerializeStreamResource {
+        resource,
+        header,
+        payload_len,
+        chunk_size,
+        state: Mutex::new(StreamState {
+            position: 0,
+            checksum: crc32fast::Hasher::new(),
+        }),
+    }))
+}
+
+// Returns the next chunk of at most `chunk_size` bytes, or nil once the whole
+// filter has been read. The chunks concatenated are a valid `serialize` binary.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn next_chunk<'a>(
+    env: Env<'a>,
+    stream: ResourceArc<SerializeStreamResource>,
+) -> Result<Option<Binary<'a>>, Error> {
+    let mut state = stream.state.lock()?;
+
+    let start = state.position;
+    let end = start
+        .saturating_add(stream.chunk_size)
+        .min(stream.encoded_len());
+    if start == end {
+        return Ok(None);
+    }
+
+    let mut chunk = Vec::with_capacity(end - start);
+    stream.read(&mut state, start, end, &mut chunk)?;
+    state.position = end;
+
+    let mut binary = OwnedBinary::new(chunk.len()).ok_or(Error::AllocationFailed)?;
+    binary.as_mut_slice().copy_from_slice(&chunk);
+    Ok(Some(binary.release(env)))
+}
@@ -0,0 +1,144 @@
+use crate::error::Error;
+use crate::memory::Reservation;
+use crate::{optimal_words, random_seed, validate_params};
+use fastbloom::{BloomFilter, DefaultHasher};
+use rustler::{Binary, Resource, ResourceArc};
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// One bloom filter per time bucket, created when the first item for that
+// bucket arrives. Only the newest `retention` buckets are kept: a new newest
+// bucket drops the ones that fall out of range, and items for buckets already
+// dropped are ignored. Every bucket shares the seed, so an item is hashed
+// once however many buckets a lookup checks.
+struct TimeSeriesFilter {
+    buckets: BTreeMap<u64, (BloomFilter, Reservation)>,
+    bucket_size: u64,
+    retention: u64,
+    capacity: usize,
+    false_positive_rate: f64,
+    seed: u128,
+    hasher: DefaultHasher,
+}
+
+impl TimeSeriesFilter {
+    fn newest(&self) -> Option<u64> {
+        self.buckets.last_key_value().map(|(&bucket, _)| bucket)
+    }
+
+    // Returns whether the item was recorded, which it isn't for a bucket that
+    // has already been dropped.
+    fn insert(&mut self, item: &[u8], timestamp: u64) -> Result<bool, Error> {
+        let bucket = timestamp / self.bucket_size;
+        let newest = self.newest().map_or(bucket, |newest| newest.max(bucket));
+        let oldest = (newest + 1).saturating_sub(self.retention);
+        if bucket < oldest {
+            return Ok(false);
+        }
+
+        // Buckets that fall out of range go first, so their memory can go to
+        // the new one.
+        while let Some(entry) = self.buckets.first_entry() {
+            if *entry.key() >= oldest {
+                break;
+            }
+            entry.remove();
+        }
+
+        if !self.buckets.contains_key(&bucket) {
+            let (words, num_hashes, memory) =
+                optimal_words(self.capacity, self.false_positive_rate)?;
+            let filter = BloomFilter::from_vec(words)
+                .seed(&self.seed)
+                .hashes(num_hashes);
+            self.buckets.insert(bucket, (filter, memory));
+        }
+        let hash = self.hasher.hash_one(item);
+        let (filter, _) = self.buckets.get_mut(&bucket).expect("bucket just created");
+        filter.insert_hash(hash);
+
+        Ok(true)
+    }
+
+    // Checks every bucket overlapping [from, to], both inclusive.
+    fn contains_in_range(&self, item: &[u8], from: u64, to: u64) -> bool {
+        if from > to {
+            return false;
+        }
+        let hash = self.hasher.hash_one(item);
+        self.buckets
+            .range(from / self.bucket_size..=to / self.bucket_size)
+            .any(|(_, (filter, _))| filter.contains_hash(hash))
+    }
+}
+
+pub struct TimeSeriesFilterResource {
+    filter: RwLock<TimeSeriesFilter>,
+}
+
+impl Resource for TimeSeriesFilterResource {}
+
+// Timestamps can be in any unit, as long as `bucket_size` is in the same one.
+#[rustler::nif]
+fn ts_new(
+    capacity: usize,
+    false_positive_rate: f64,
+    bucket_size: u64,
+    retention: u64,
+) -> Result<ResourceArc<TimeSeriesFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+    if bucket_size == 0 {
+        return Err(Error::InvalidBucketSize);
+    }
+    if retention == 0 {
+        return Err(Error::InvalidGenerations);
+    }
+    let seed = random_seed()?;
+
+    Ok(ResourceArc::new(TimeSeriesFilterResource {
+        filter: RwLock::new(TimeSeriesFilter {
+            buckets: BTreeMap::new(),
+            bucket_size,
+            retention,
+            capacity,
+            false_positive_rate,
+            seed,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+        }),
+    }))
+}
+
+// Creating a bucket allocates a whole filter, so this runs on a dirty
+// scheduler.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ts_add(
+    resource: ResourceArc<TimeSeriesFilterResource>,
+    item: Binary,
+    timestamp: u64,
+) -> Result<bool, Error> {
+    let mut filter = resource.filter.write()?;
+    filter.insert(item.as_slice(), timestamp)
+}
+
+#[rustler::nif]
+fn ts_member_in_range(
+    resource: ResourceArc<TimeSeriesFilterResource>,
+    item: Binary,
+    from: u64,
+    to: u64,
+) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains_in_range(item.as_slice(), from, to))
+}
+
+// The start time of every bucket kept, oldest first.
+#[rustler::nif]
+fn ts_buckets(resource: ResourceArc<TimeSeriesFilterResource>) -> Result<Vec<u64>, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter
+        .buckets
+        .keys()
+        .map(|bucket| bucket * filter.bucket_size)
+        .collect())
+}
@@ -0,0 +1,94 @@
+use crate::cms::CountMinSketch;
+use crate::error::Error;
+use rustler::{Binary, Env, OwnedBinary, Resource, ResourceArc};
+use std::sync::RwLock;
+
+// Tracks the `k` most frequent items of a stream: a count-min sketch estimates
+// every item's count, and the items with the highest estimates seen so far are
+// kept with their estimates. An item enters the list once its estimate beats
+// the smallest one in it, which then drops out. Like the sketch, the counts
+// never underestimate.
+struct TopK {
+    sketch: CountMinSketch,
+    k: usize,
+    // Unordered. `k` is expected to be small, so it's scanned linearly.
+    heavy_hitters: Vec<(Vec<u8>, u64)>,
+}
+
+impl TopK {
+    fn add(&mut self, item: &[u8]) {
+        self.sketch.increment(item, 1);
+        let estimate = self.sketch.estimate(item);
+
+        if let Some(entry) = self.heavy_hitters.iter_mut().find(|(i, _)| i == item) {
+            entry.1 = estimate;
+        } else if self.heavy_hitters.len() < self.k {
+            self.heavy_hitters.push((item.to_vec(), estimate));
+        } else if let Some(smallest) = self
+            .heavy_hitters
+            .iter_mut()
+            .min_by_key(|(_, count)| *count)
+            .filter(|(_, count)| *count < estimate)
+        {
+            *smallest = (item.to_vec(), estimate);
+        }
+    }
+}
+
+pub struct TopKResource {
+    topk: RwLock<TopK>,
+}
+
+impl Resource for TopKResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn topk_new(k: usize, width: usize, depth: usize) -> Result<ResourceArc<TopKResource>, Error> {
+    if k == 0 {
+        return Err(Error::InvalidK);
+    }
+    if width == 0 {
+        return Err(Error::InvalidWidth);
+    }
+    if depth == 0 {
+        return Err(Error::InvalidDepth);
+    }
+    let sketch = CountMinSketch::new(width, depth)?;
+    let mut heavy_hitters = Vec::new();
+    heavy_hitters
+        .try_reserve_exact(k)
+        .map_err(|_| Error::AllocationFailed)?;
+
+    Ok(ResourceArc::new(TopKResource {
+        topk: RwLock::new(TopK {
+            sketch,
+            k,
+            heavy_hitters,
+        }),
+    }))
+}
+
+#[rustler::nif]
+fn topk_add(resource: ResourceArc<TopKResource>, item: Binary) -> Result<(), Error> {
+    let mut topk = resource.topk.write()?;
+    topk.add(item.as_slice());
+    Ok(())
+}
+
+// Returns the tracked items and their estimated counts, most frequent first.
+#[rustler::nif]
+fn topk_list<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<TopKResource>,
+) -> Result<Vec<(Binary<'a>, u64)>, Error> {
+    let mut heavy_hitters = resource.topk.read()?.heavy_hitters.clone();
+    heavy_hitters.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+    heavy_hitters
+        .into_iter()
+        .map(|(item, count)| {
+            let mut binary = OwnedBinary::new(item.len()).ok_or(Error::AllocationFailed)?;
+            binary.as_mut_slice().copy_from_slice(&item);
+            Ok((binary.release(env), count))
+        })
+        .collect()
+}
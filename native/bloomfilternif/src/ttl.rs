@@ -0,0 +1,105 @@
+use crate::error::Error;
+use crate::hasher::double_hash_indexes;
+use crate::memory::{zeroed, Reservation};
+use crate::{random_seed, slot_size, validate_params};
+use fastbloom::DefaultHasher;
+use rustler::{Binary, Resource, ResourceArc};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+// A bloom filter whose items expire a number of buckets after they are added.
+// Each slot holds the bucket its newest item expires in rather than a bit, so
+// an item is present while every one of its slots expires after the current
+// bucket, and `advance` ages every item by one bucket at once without touching
+// the slots. Slots store absolute buckets, so 2^32 buckets can pass in all.
+struct TtlBloomFilter {
+    expiries: Vec<u32>,
+    num_hashes: u32,
+    now: u32,
+    hasher: DefaultHasher,
+    _memory: Reservation,
+}
+
+impl TtlBloomFilter {
+    fn new(capacity: usize, false_positive_rate: f64, seed: u128) -> Result<Self, Error> {
+        let (num_slots, num_hashes) = slot_size(capacity, false_positive_rate);
+        let (expiries, memory) = zeroed(num_slots)?;
+
+        Ok(TtlBloomFilter {
+            expiries,
+            num_hashes,
+            now: 0,
+            hasher: DefaultHasher::seeded(&seed.to_be_bytes()),
+            _memory: memory,
+        })
+    }
+
+    fn slots(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(item);
+        double_hash_indexes(hash, self.num_hashes, self.expiries.len())
+    }
+
+    // Adding an item again only ever extends how long it lasts.
+    fn insert(&mut self, item: &[u8], ttl: u32) {
+        let expiry = self.now.saturating_add(ttl);
+        for slot in self.slots(item) {
+            self.expiries[slot] = self.expiries[slot].max(expiry);
+        }
+    }
+
+    fn contains(&self, item: &[u8]) -> bool {
+        self.slots(item).all(|slot| self.expiries[slot] > self.now)
+    }
+}
+
+pub struct TtlBloomFilterResource {
+    filter: RwLock<TtlBloomFilter>,
+}
+
+impl Resource for TtlBloomFilterResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ttl_new(
+    capacity: usize,
+    false_positive_rate: f64,
+) -> Result<ResourceArc<TtlBloomFilterResource>, Error> {
+    validate_params(capacity, false_positive_rate)?;
+
+    Ok(ResourceArc::new(TtlBloomFilterResource {
+        filter: RwLock::new(TtlBloomFilter::new(
+            capacity,
+            false_positive_rate,
+            random_seed()?,
+        )?),
+    }))
+}
+
+// The item is present until `ttl_buckets` more calls to `ttl_advance`.
+#[rustler::nif]
+fn ttl_add(
+    resource: ResourceArc<TtlBloomFilterResource>,
+    item: Binary,
+    ttl_buckets: u32,
+) -> Result<(), Error> {
+    if ttl_buckets == 0 {
+        return Err(Error::InvalidTtl);
+    }
+    let mut filter = resource.filter.write()?;
+    filter.insert(item.as_slice(), ttl_buckets);
+    Ok(())
+}
+
+#[rustler::nif]
+fn ttl_member(resource: ResourceArc<TtlBloomFilterResource>, item: Binary) -> Result<bool, Error> {
+    let filter = resource.filter.read()?;
+    Ok(filter.contains(item.as_slice()))
+}
+
+// Moves on to the next bucket, expiring every item whose last bucket was the
+// current one. Returns the number of buckets passed so far.
+#[rustler::nif]
+fn ttl_advance(resource: ResourceArc<TtlBloomFilterResource>) -> Result<u32, Error> {
+    let mut filter = resource.filter.write()?;
+    filter.now = filter.now.checked_add(1).ok_or(Error::CapacityExceeded)?;
+    Ok(filter.now)
+}
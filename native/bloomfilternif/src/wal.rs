@@ -0,0 +1,156 @@
+use crate::alarm::check_fill;
+use crate::error::Error;
+use crate::hasher::HashAlgorithm;
+use crate::BloomFilterResource;
+use rustler::ResourceArc;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// A write-ahead log is a header followed by the hash of every item inserted
+// while it was enabled, each a little-endian u64:
+//
+//   offset  size  field
+//        0     4  magic, "BFWL"
+//        4     1  format version, 2
+//        5    16  seed (u128) of the filter that wrote it
+//       21     1  hash algorithm, as in the serialized format
+//       22  8 * n  hashes
+//
+// Version 1 logs have no hash algorithm byte and were all written by
+// SipHash-1-3 filters, so their hashes start at offset 21. Logs keep the
+// version they were created with when appended to.
+//
+// The hashes depend on the seed and the hash algorithm, so a log can only be
+// replayed into a filter with the same ones. Inserting is idempotent, so
+// replaying hashes that are already in the filter does no harm.
+const WAL_MAGIC: &[u8; 4] = b"BFWL";
+const WAL_FORMAT_VERSION: u8 = 2;
+const SIP13_WAL_FORMAT_VERSION: u8 = 1;
+const WAL_HEADER_LEN: usize = WAL_MAGIC.len() + 1 + 16 + 1;
+const SIP13_WAL_HEADER_LEN: usize = WAL_MAGIC.len() + 1 + 16;
+
+// The log a filter appends to, if any. `enabled` lets inserts skip the mutex
+// when there is none.
+#[derive(Default)]
+pub(crate) struct WalLog {
+    enabled: AtomicBool,
+    file: Mutex<Option<File>>,
+}
+
+impl WalLog {
+    fn open(&self, path: &str, seed: u128, hash: HashAlgorithm) -> Result<(), Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&header(seed, hash))?;
+            file.sync_all()?;
+        } else {
+            let mut buf = Vec::with_capacity(WAL_HEADER_LEN);
+            (&mut file)
+                .take(WAL_HEADER_LEN as u64)
+                .read_to_end(&mut buf)?;
+            let header_len = check_header(&buf, seed, hash)?;
+
+            // Drop a record torn by a crash, so new records start on a record
+            // boundary instead of being read shifted by the torn bytes.
+            let len = file.metadata()?.len();
+            let header_len = header_len as u64;
+            let whole = header_len + (len - header_len) / 8 * 8;
+            if whole < len {
+                file.set_len(whole)?;
+            }
+        }
+
+        *self.file.lock()? = Some(file);
+        self.enabled.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn close(&self) -> Result<(), Error> {
+        self.enabled.store(false, Ordering::Release);
+        self.file.lock()?.take();
+        Ok(())
+    }
+
+    // Each call is a single write, so a crash loses at most a torn last record,
+    // which `replay_wal` skips and `open` truncates. The data reaches the OS before the insert
+    // returns, but is not synced to disk.
+    pub(crate) fn append(&self, hashes: &[u64]) -> Result<(), Error> {
+        if !self.enabled.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        if let Some(file) = self.file.lock()?.as_mut() {
+            let buf: Vec<u8> = hashes.iter().flat_map(|hash| hash.to_le_bytes()).collect();
+            file.write_all(&buf)?;
+        }
+        Ok(())
+    }
+}
+
+fn header(seed: u128, hash: HashAlgorithm) -> [u8; WAL_HEADER_LEN] {
+    let mut header = [0; WAL_HEADER_LEN];
+    header[0..4].copy_from_slice(WAL_MAGIC);
+    header[4] = WAL_FORMAT_VERSION;
+    header[5..21].copy_from_slice(&seed.to_le_bytes());
+    header[21] = hash as u8;
+    header
+}
+
+// Checks that the log at the start of `buf` was written by a filter hashing
+// like this one, and returns the length of its header.
+fn check_header(buf: &[u8], seed: u128, hash: HashAlgorithm) -> Result<usize, Error> {
+    if buf.len() < WAL_MAGIC.len() + 1 || &buf[0..4] != WAL_MAGIC {
+        return Err(Error::InvalidData);
+    }
+    let header_len = match buf[4] {
+        WAL_FORMAT_VERSION => WAL_HEADER_LEN,
+        SIP13_WAL_FORMAT_VERSION => SIP13_WAL_HEADER_LEN,
+        _ => return Err(Error::UnsupportedVersion),
+    };
+    if buf.len() < header_len {
+        return Err(Error::InvalidData);
+    }
+
+    let logged_hash = match header_len {
+        WAL_HEADER_LEN => HashAlgorithm::from_byte(buf[21])?,
+        _ => HashAlgorithm::Sip13,
+    };
+    if u128::from_le_bytes(buf[5..21].try_into().unwrap()) != seed || logged_hash != hash {
+        return Err(Error::IncompatibleFilters);
+    }
+    Ok(header_len)
+}
+
+// Appends the hash of every item inserted from now on to the log at `path`,
+// creating it if needed, in place of any log already enabled. Only inserts
+// are logged: merges, unions and `clear` are not.
+#[rustler::nif(schedule = "DirtyIo")]
+fn enable_wal(resource: ResourceArc<BloomFilterResource>, path: String) -> Result<(), Error> {
+    resource.wal.open(&path, resource.seed, resource.hash)
+}
+
+#[rustler::nif]
+fn disable_wal(resource: ResourceArc<BloomFilterResource>) -> Result<(), Error> {
+    resource.wal.close()
+}
+
+// Inserts every hash in the log at `path`, returning how many were new. The
+// hashes are not logged again.
+#[rustler::nif(schedule = "DirtyIo")]
+fn replay_wal(resource: ResourceArc<BloomFilterResource>, path: String) -> Result<usize, Error> {
+    let buf = fs::read(path)?;
+    let header_len = check_header(&buf, resource.seed, resource.hash)?;
+
+    let hashes = buf[header_len..]
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    resource
+        .set_hashes_locked(&resource.filter.read(), hashes, false, false)
+        .inspect(|_| check_fill(&resource))
+}
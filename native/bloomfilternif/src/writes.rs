@@ -0,0 +1,60 @@
+use std::hint;
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+
+// Attempts `WriteVersion::read` makes before giving up.
+const READ_ATTEMPTS: usize = 4;
+
+// Counts writes to a filter's bits and inserted count, so readers can tell
+// whether they saw both as of the same write. Inserts set bits under the shared
+// read lock, so several writers can be active at once; this works like a
+// seqlock that allows that. Every write holds a `Write` while it changes either
+// value, and a read is only consistent if no write was in progress when it
+// started and none started or finished before it ended.
+#[derive(Default)]
+pub struct WriteVersion {
+    completed: AtomicU64,
+    in_progress: AtomicUsize,
+}
+
+// Held for the duration of one write.
+pub struct Write<'a>(&'a WriteVersion);
+
+impl WriteVersion {
+    pub fn begin(&self) -> Write<'_> {
+        self.in_progress.fetch_add(1, Ordering::SeqCst);
+        Write(self)
+    }
+
+    // The number of writes completed so far.
+    pub fn current(&self) -> u64 {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    // Runs `read` until no write overlaps it, returning its result with the
+    // version it is consistent with, or `None` if writes kept overlapping.
+    pub fn read<T>(&self, read: impl Fn() -> T) -> Option<(T, u64)> {
+        for _ in 0..READ_ATTEMPTS {
+            let version = self.current();
+            if self.in_progress.load(Ordering::SeqCst) != 0 {
+                hint::spin_loop();
+                continue;
+            }
+
+            let value = read();
+            // Keeps the relaxed loads in `read` from moving past the checks.
+            fence(Ordering::Acquire);
+            if self.in_progress.load(Ordering::SeqCst) == 0 && self.current() == version {
+                return Some((value, version));
+            }
+        }
+        None
+    }
+}
+
+impl Drop for Write<'_> {
+    // Completing before leaving means a reader always sees one or the other.
+    fn drop(&mut self) {
+        self.0.completed.fetch_add(1, Ordering::SeqCst);
+        self.0.in_progress.fetch_sub(1, Ordering::SeqCst);
+    }
+}
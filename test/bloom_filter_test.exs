@@ -1,6 +1,33 @@
 defmodule BloomFilterExTest do
   use ExUnit.Case
   doctest BloomFilterEx
+  doctest BloomFilterEx.Counting
+  doctest BloomFilterEx.Scalable
+  doctest BloomFilterEx.Rotating
+  doctest BloomFilterEx.TTL
+  doctest BloomFilterEx.Stable
+  doctest BloomFilterEx.TimeSeries
+  doctest BloomFilterEx.Cuckoo
+  doctest BloomFilterEx.Fuse
+  doctest BloomFilterEx.GCS
+  doctest BloomFilterEx.Ribbon
+  doctest BloomFilterEx.Bloomier
+  doctest BloomFilterEx.CountMinSketch
+  doctest BloomFilterEx.TopK
+  doctest BloomFilterEx.HyperLogLog
+  doctest BloomFilterEx.Sharded
+  doctest BloomFilterEx.Frozen
+  doctest BloomFilterEx.Guava
+  doctest BloomFilterEx.RedisBloom
+  doctest BloomFilterEx.Partitioned
+  doctest BloomFilterEx.Quotient
+  doctest BloomFilterEx.FilterMap
+  doctest BloomFilterEx.Branch
+
+  # Tests that lower the shared memory budget leave it unlimited for the rest.
+  setup do
+    on_exit(fn -> BloomFilterEx.configure(memory_budget: :infinity) end)
+  end
 
   test "basic bloom filter operations" do
     bloom = BloomFilterEx.new(1000, 0.01)
@@ -16,4 +43,326 @@ defmodule BloomFilterExTest do
     assert bloom.inserted_count == 0
     assert BloomFilterEx.member?(bloom, "test_item") == false
   end
+
+  test "notify_on_release/3 reports when the filter is garbage collected" do
+    parent = self()
+
+    spawn(fn ->
+      BloomFilterEx.new(100, 0.01) |> BloomFilterEx.notify_on_release(:leak_check, parent)
+    end)
+
+    assert_receive {:bloom_filter_released, :leak_check}, 1000
+  end
+
+  @tag :tmp_dir
+  test "save and load through a file", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "filter.bloom")
+    bloom = BloomFilterEx.new(1000, 0.01) |> BloomFilterEx.add("saved")
+
+    assert :ok = BloomFilterEx.save(bloom, path)
+    assert File.read!(path) == BloomFilterEx.serialize(bloom)
+
+    assert {:ok, loaded} = BloomFilterEx.load(path)
+    assert loaded.inserted_count == 1
+    assert BloomFilterEx.member?(loaded, "saved")
+
+    assert {:error, :enoent} = BloomFilterEx.load(Path.join(tmp_dir, "missing"))
+  end
+
+  @tag :tmp_dir
+  test "reopening a write-ahead log drops a torn last record", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "filter.wal")
+    bloom = BloomFilterEx.new_with_seed(1000, 0.01, 7)
+
+    :ok = BloomFilterEx.enable_wal(bloom, path)
+    bloom = BloomFilterEx.add(bloom, "before")
+    :ok = BloomFilterEx.disable_wal(bloom)
+    File.write!(path, <<1, 2, 3>>, [:append])
+
+    :ok = BloomFilterEx.enable_wal(bloom, path)
+    bloom = BloomFilterEx.add(bloom, "after")
+    :ok = BloomFilterEx.disable_wal(bloom)
+
+    recovered = BloomFilterEx.new_with_seed(1000, 0.01, 7)
+    assert {:ok, recovered} = BloomFilterEx.replay_wal(recovered, path)
+    assert BloomFilterEx.member?(recovered, "before")
+    assert BloomFilterEx.member?(recovered, "after")
+    assert recovered.inserted_count == 2
+  end
+
+  @tag :tmp_dir
+  test "write-ahead logs only replay into filters with the same hash", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "filter.wal")
+    opts = [capacity: 1000, false_positive_rate: 0.01, seed: 7]
+    {:ok, bloom} = BloomFilterEx.new_with_opts([hash: :xxh3] ++ opts)
+
+    :ok = BloomFilterEx.enable_wal(bloom, path)
+    _bloom = BloomFilterEx.add(bloom, "logged")
+
+    {:ok, sip13} = BloomFilterEx.new_with_opts(opts)
+    assert BloomFilterEx.replay_wal(sip13, path) == {:error, :incompatible_filters}
+    assert BloomFilterEx.enable_wal(sip13, path) == {:error, :incompatible_filters}
+
+    {:ok, xxh3} = BloomFilterEx.new_with_opts([hash: :xxh3] ++ opts)
+    assert {:ok, xxh3} = BloomFilterEx.replay_wal(xxh3, path)
+    assert BloomFilterEx.member?(xxh3, "logged")
+  end
+
+  @tag :tmp_dir
+  test "new_from_file/4 adds each record of a file", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "items.txt")
+    File.write!(path, "alice\nbob\n\ncarol\n")
+
+    assert {:ok, bloom} = BloomFilterEx.new_from_file(path, 100, 0.01)
+    assert bloom.inserted_count == 3
+    assert BloomFilterEx.member_many_iodata(bloom, ["alice", "bob", "carol", ""]) ==
+             [true, true, true, false]
+
+    File.write!(path, "x,y")
+    assert {:ok, bloom} = BloomFilterEx.new_from_file(path, 100, 0.01, ",")
+    assert BloomFilterEx.member_iodata?(bloom, "y")
+
+    File.write!(path, Enum.map_join(1..1000, "\n", &Integer.to_string/1))
+    assert {:ok, bloom} = BloomFilterEx.new_from_file(path, 1000, 0.01, "\n", parallel: true)
+    assert bloom.inserted_count > 990
+    assert BloomFilterEx.member_iodata?(bloom, "1000")
+
+    assert {:error, :enoent} =
+             BloomFilterEx.new_from_file(Path.join(tmp_dir, "missing"), 100, 0.01)
+  end
+
+  @tag :tmp_dir
+  test "memory-mapped filters persist across opens", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "filter.bfmm")
+
+    assert {:ok, filter} = BloomFilterEx.new_mmap(path, 1000, 0.01)
+    filter = BloomFilterEx.Mmap.add_many(filter, ["a", "b"])
+    assert :ok = BloomFilterEx.Mmap.flush(filter)
+
+    assert {:ok, reopened} = BloomFilterEx.Mmap.open(path)
+    assert reopened.size == BloomFilterEx.new(1000, 0.01).size
+    assert BloomFilterEx.Mmap.inserted_count(reopened) == 2
+    assert BloomFilterEx.Mmap.member_many(reopened, ["a", "b", "c"]) == [true, true, false]
+
+    assert {:error, :eexist} = BloomFilterEx.new_mmap(path, 1000, 0.01)
+  end
+
+  @tag :tmp_dir
+  test "read-only mappings see writes and reject inserts", %{tmp_dir: tmp_dir} do
+    path = Path.join(tmp_dir, "shared.bfmm")
+    {:ok, writer} = BloomFilterEx.new_mmap(path, 1000, 0.01)
+    {:ok, reader} = BloomFilterEx.Mmap.open_shared_readonly(path)
+
+    BloomFilterEx.Mmap.add(writer, "blocked")
+    assert BloomFilterEx.Mmap.member?(reader, "blocked")
+    assert {:error, :read_only} = BloomFilterEx.Mmap.add(reader, "other")
+    assert {:error, :read_only} = BloomFilterEx.Mmap.add_many(reader, ["other"])
+  end
+
+  test "stats are consistent while items are being added" do
+    bloom = BloomFilterEx.new(100_000, 0.01)
+
+    writers =
+      for n <- 1..4 do
+        Task.async(fn -> Enum.each(1..5_000, &BloomFilterEx.add(bloom, {n, &1})) end)
+      end
+
+    snapshots = for _ <- 1..200, do: BloomFilterEx.stats(bloom)
+    Enum.each(writers, &Task.await/1)
+
+    for {_version, [first | rest]} <- Enum.group_by(snapshots, & &1.write_version) do
+      assert Enum.all?(rest, &(&1.bits_set == first.bits_set))
+      assert Enum.all?(rest, &(&1.inserted_count == first.inserted_count))
+    end
+
+    versions = Enum.map(snapshots, & &1.write_version)
+    assert versions == Enum.sort(versions)
+  end
+
+  test "TTL filter items expire after their own number of buckets" do
+    filter =
+      BloomFilterEx.TTL.new(1000, 0.01)
+      |> BloomFilterEx.TTL.add("short", 1)
+      |> BloomFilterEx.TTL.add("long", 3)
+      |> BloomFilterEx.TTL.add("extended", 1)
+      |> BloomFilterEx.TTL.add("extended", 2)
+      |> BloomFilterEx.TTL.add("extended", 1)
+
+    members = fn filter ->
+      Enum.filter(["short", "long", "extended"], &BloomFilterEx.TTL.member?(filter, &1))
+    end
+
+    filter = BloomFilterEx.TTL.advance(filter)
+    assert members.(filter) == ["long", "extended"]
+    filter = BloomFilterEx.TTL.advance(filter)
+    assert members.(filter) == ["long"]
+    filter = BloomFilterEx.TTL.advance(filter)
+    assert members.(filter) == []
+  end
+
+  test "stable filters keep a bounded false positive rate on long streams" do
+    filter = BloomFilterEx.Stable.new(10_000, 3, 0.01)
+    filter = Enum.reduce(1..100_000, filter, &BloomFilterEx.Stable.add(&2, &1))
+
+    false_positives = Enum.count(-1..-1000//-1, &BloomFilterEx.Stable.member?(filter, &1))
+    assert false_positives < 50
+  end
+
+  test "time-series filters drop old buckets and ignore items for them" do
+    ts = BloomFilterEx.TimeSeries.new(100, 0.01, bucket_size: 10, retention: 2)
+    ts = BloomFilterEx.TimeSeries.add(ts, "first", 5)
+    ts = BloomFilterEx.TimeSeries.add(ts, "second", 15)
+    assert BloomFilterEx.TimeSeries.member_in_range?(ts, "first", 0, 100)
+
+    ts = BloomFilterEx.TimeSeries.add(ts, "third", 25)
+    refute BloomFilterEx.TimeSeries.member_in_range?(ts, "first", 0, 100)
+    assert BloomFilterEx.TimeSeries.member_in_range?(ts, "second", 10, 19)
+    refute BloomFilterEx.TimeSeries.member_in_range?(ts, "second", 20, 29)
+
+    ts = BloomFilterEx.TimeSeries.add(ts, "late", 9)
+    refute BloomFilterEx.TimeSeries.member_in_range?(ts, "late", 0, 100)
+    assert BloomFilterEx.TimeSeries.buckets(ts) == [10, 20]
+  end
+
+  test "stats report the derived parameters and their expected false positive rates" do
+    bloom = BloomFilterEx.new(1000, 0.01)
+    bloom = Enum.reduce(1..500, bloom, &BloomFilterEx.add(&2, &1))
+    %{derived: derived} = BloomFilterEx.stats(bloom)
+
+    assert derived.fpr_at_capacity > 0.005 and derived.fpr_at_capacity < 0.02
+    assert derived.fpr_at_inserted_count < derived.fpr_at_capacity
+    assert derived.num_bits >= 1000 * 9
+  end
+
+  test "filter maps keep tenants apart and free dropped tenants" do
+    map = BloomFilterEx.FilterMap.new(10, 0.01)
+    map = Enum.reduce(1..1000, map, &BloomFilterEx.FilterMap.add(&2, "tenant-#{&1}", &1))
+
+    assert BloomFilterEx.FilterMap.member?(map, "tenant-7", 7)
+    false_positives = Enum.count(1..1000, &BloomFilterEx.FilterMap.member?(map, "tenant-7", -&1))
+    assert false_positives < 50
+
+    %{tenants: 1000, memory_bytes: memory_bytes} = BloomFilterEx.FilterMap.stats(map)
+    map = BloomFilterEx.FilterMap.drop_tenant(map, "tenant-7")
+    refute BloomFilterEx.FilterMap.member?(map, "tenant-7", 7)
+    assert %{tenants: 999, inserted_count: 999} = BloomFilterEx.FilterMap.stats(map)
+    assert BloomFilterEx.FilterMap.stats(map).memory_bytes < memory_bytes
+  end
+
+  test "frozen filters hash with the hash of the filter they were frozen from" do
+    for hash <- [:xxh3, :wyhash] do
+      {:ok, bloom} =
+        BloomFilterEx.new_with_opts(capacity: 1000, false_positive_rate: 0.01, hash: hash)
+
+      bloom = BloomFilterEx.add_many(bloom, Enum.to_list(1..100))
+      frozen = BloomFilterEx.freeze(bloom)
+
+      assert Enum.all?(1..100, &BloomFilterEx.Frozen.member?(frozen, &1))
+      assert BloomFilterEx.Frozen.member_many(frozen, [1, 50, 100]) == [true, true, true]
+    end
+  end
+
+  test "branches share the parent's bits and keep their own inserts" do
+    base = Enum.reduce(1..1000, BloomFilterEx.new(10_000, 0.01), &BloomFilterEx.add(&2, &1))
+    branch =
+      Enum.reduce(1001..1100, BloomFilterEx.branch(base), &BloomFilterEx.Branch.add(&2, &1))
+
+    assert Enum.all?(1..1100, &BloomFilterEx.Branch.member?(branch, &1))
+    assert Enum.count(1001..1100, &BloomFilterEx.member?(base, &1)) < 10
+    assert BloomFilterEx.Branch.inserted_count(branch) in 1090..1100
+    assert BloomFilterEx.Branch.copied_bytes(branch) <= BloomFilterEx.byte_size(base)
+  end
+
+  test "very small false positive rates keep their full precision" do
+    bloom = BloomFilterEx.new(1000, 1.0e-9)
+    assert BloomFilterEx.stats(bloom).false_positive_rate == 1.0e-9
+
+    {:ok, restored} = bloom |> BloomFilterEx.serialize() |> BloomFilterEx.deserialize()
+    assert restored.false_positive_rate == 1.0e-9
+    assert restored.hash_count == bloom.hash_count
+  end
+
+  test "filters that need more than 2^32 bits are only limited by memory" do
+    :ok = BloomFilterEx.configure(memory_budget: 1_000_000)
+
+    assert BloomFilterEx.optimal_params(1_000_000_000, 1.0e-9).num_bits > Bitwise.bsl(1, 32)
+    assert BloomFilterEx.new(1_000_000_000, 1.0e-9) == {:error, :memory_budget_exceeded}
+
+    assert BloomFilterEx.new_with_bits(Bitwise.bsl(1, 32) + 64, 3) ==
+             {:error, :memory_budget_exceeded}
+  end
+
+  test "serialized filters of more than 2^32 bits must hold all of their bits" do
+    num_bits = Bitwise.bsl(1, 32) + 64
+    legacy = <<1, 3::little-32, 100::little-64, 0.01::little-float-64, 0::little-64>>
+    legacy = <<legacy::binary, 0::little-128, num_bits::little-64, 0::64>>
+    assert BloomFilterEx.deserialize(legacy) == {:error, :invalid_data}
+  end
+
+  test "the memory budget covers every filter type" do
+    :ok = BloomFilterEx.configure(memory_budget: 1_000_000)
+
+    assert BloomFilterEx.Counting.new(10_000_000, 0.01) == {:error, :memory_budget_exceeded}
+    assert BloomFilterEx.CountMinSketch.new(1_000_000, 4) == {:error, :memory_budget_exceeded}
+
+    map = BloomFilterEx.FilterMap.new(10_000_000, 0.01)
+    assert BloomFilterEx.FilterMap.add(map, "tenant", 1) == {:error, :memory_budget_exceeded}
+  end
+
+  test "scalable filters reserve every layer or none within the memory budget" do
+    bloom = BloomFilterEx.Scalable.new(10, 0.01)
+    :ok = BloomFilterEx.configure(memory_budget: 1_000_000)
+
+    assert BloomFilterEx.Scalable.reserve(bloom, 10_000_000) == {:error, :memory_budget_exceeded}
+    assert BloomFilterEx.Scalable.num_layers(bloom) == 1
+  end
+
+  test "scalable filters reserve the layers a bulk load will need" do
+    bloom = BloomFilterEx.Scalable.new(10, 0.01)
+    bloom = BloomFilterEx.Scalable.reserve(bloom, 1000)
+    layers = BloomFilterEx.Scalable.num_layers(bloom)
+    assert layers > 1
+
+    bloom = Enum.reduce(1..1000, bloom, &BloomFilterEx.Scalable.add(&2, &1))
+    assert BloomFilterEx.Scalable.num_layers(bloom) == layers
+    assert Enum.all?(1..1000, &BloomFilterEx.Scalable.member?(bloom, &1))
+  end
+
+  test "fuse filters refuse encoded lengths that don't match the data" do
+    {:ok, filter} = BloomFilterEx.Fuse.build(Enum.to_list(1..100))
+    <<header::binary-size(17), fields::binary-size(20), _count::64, rest::binary>> =
+      BloomFilterEx.Fuse.serialize(filter)
+
+    huge = <<header::binary, fields::binary, 0xFFFF_FFFF_FFFF::little-64, rest::binary>>
+    assert BloomFilterEx.Fuse.deserialize(huge) == {:error, :invalid_data}
+  end
+
+  test "RedisBloom chains dump a RedisBloom header and load from chunks in any order" do
+    filter = BloomFilterEx.RedisBloom.new(100, 0.01)
+    filter = Enum.reduce(1..1000, filter, &BloomFilterEx.RedisBloom.add(&2, "#{&1}"))
+    [{1, header} | chunks] = BloomFilterEx.RedisBloom.scandump(filter)
+
+    # size, nfilters, options (NOROUND | FORCE64), growth, then the first link
+    <<1000::little-64, nfilters::little-32, 5::little-32, 2::little-32, link::binary-size(53),
+      _::binary>> = header
+
+    assert nfilters > 1
+    <<144::little-64, 1152::little-64, 100::little-64, _error::64, _bpe::64, 8::little-32,
+      100::little-64, 0>> = link
+
+    {:ok, restored} = BloomFilterEx.RedisBloom.load_chunks([{1, header} | Enum.reverse(chunks)])
+    assert Enum.all?(1..1000, &BloomFilterEx.RedisBloom.member?(restored, "#{&1}"))
+    assert BloomFilterEx.RedisBloom.scandump(restored) == [{1, header} | chunks]
+  end
+
+  test "partitioned filters set the bits their documented murmur3 scheme gives" do
+    # murmur3_x64_128("hello", 0) has h1 = 0xCBD8A7B341BD9B02 and h2 = 0x5B1E906A48AE1D19,
+    # so with two 64-bit slices it sets bit 2 of the first and bit 27 of the second.
+    {:ok, bloom} = BloomFilterEx.Partitioned.from_bits(<<0::128>>, 2)
+    assert {bloom.num_hashes, bloom.slice_bits, bloom.inserted_count} == {2, 64, 0}
+
+    bloom = BloomFilterEx.Partitioned.add(bloom, "hello")
+    bits = <<Bitwise.bsl(1, 2)::little-64, Bitwise.bsl(1, 27)::little-64>>
+    assert BloomFilterEx.Partitioned.to_bits(bloom) == bits
+  end
 end